@@ -0,0 +1,34 @@
+use saba_core::clock::Clock;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// OSの時計を使うClock
+pub struct SystemClock {}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs(),
+            Err(_) => 0,
+        }
+    }
+
+    fn now_millis(&self) -> u64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_millis() as u64,
+            Err(_) => 0,
+        }
+    }
+}
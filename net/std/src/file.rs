@@ -0,0 +1,244 @@
+// file: URLでローカルのファイルを読み込むSchemeHandler
+// https://datatracker.ietf.org/doc/html/rfc8089
+
+use saba_core::error::Error;
+use saba_core::fetcher::SchemeHandler;
+use saba_core::http::HttpResponse;
+use saba_core::url::percent_decode;
+use saba_core::url::Url;
+use saba_core::utils::escape_html;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// file: URLのパスのファイルを読み込む
+/// ディレクトリの場合は、中身の一覧をリンクにしたHTMLを返す。
+pub struct FileHandler {}
+
+impl FileHandler {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for FileHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchemeHandler for FileHandler {
+    fn load(&mut self, url: &Url) -> Result<HttpResponse, Error> {
+        let path = file_path(url)?;
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                return Err(Error::Network(format!(
+                    "failed to read {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+
+        if metadata.is_dir() {
+            let html = directory_listing(url, &path)?;
+            return HttpResponse::from_body("text/html; charset=utf-8", &html);
+        }
+
+        match fs::read(&path) {
+            Ok(body) => HttpResponse::from_body(content_type(&path), &body),
+            Err(e) => Err(Error::Network(format!(
+                "failed to read {}: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+}
+
+// URLのパスをパーセントデコードして、ファイルシステムのパスにする
+// ホストは空か"localhost"だけを受け付ける
+fn file_path(url: &Url) -> Result<PathBuf, Error> {
+    let host = url.host();
+    if !host.is_empty() && host != "localhost" {
+        return Err(Error::UnexpectedInput(format!(
+            "file url with a remote host is not supported: {}",
+            url.href()
+        )));
+    }
+    match String::from_utf8(percent_decode(&url.path())) {
+        Ok(path) => Ok(PathBuf::from(path)),
+        Err(_) => Err(Error::UnexpectedInput(format!(
+            "file path is not valid UTF-8: {}",
+            url.href()
+        ))),
+    }
+}
+
+// 拡張子からContent-Typeを決める
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("html") | Some("htm") => "text/html",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("txt") => "text/plain",
+        Some("json") => "application/json",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+// ディレクトリの中身の一覧をHTMLにする
+// URLが"/"で終わらない場合でもリンクを正しく解決できるように、<base>でディレクトリのURLを指定する。
+fn directory_listing(url: &Url, path: &Path) -> Result<Vec<u8>, Error> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Err(Error::Network(format!(
+                "failed to read {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+
+    let mut names = Vec::new();
+    for entry in entries.flatten() {
+        let mut name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            name.push('/');
+        }
+        names.push(name);
+    }
+    names.sort();
+
+    let mut base = url.clone();
+    if !url.path().ends_with('/') {
+        if let Ok(u) = url.resolve(&format!("{}/", url.path())) {
+            base = u;
+        }
+    }
+    let title =
+        escape_html(&String::from_utf8_lossy(&percent_decode(&base.path())));
+
+    let mut html = format!(
+        "<html><head><base href=\"{}\"></head><body><h1>Index of {}</h1>",
+        escape_html(&base.href()),
+        title
+    );
+    if path.parent().is_some() {
+        html.push_str("<p><a href=\"../\">../</a></p>");
+    }
+    for name in names {
+        html.push_str(&format!(
+            "<p><a href=\"{}\">{}</a></p>",
+            escape_html(&encode_path_segment(&name)),
+            escape_html(&name)
+        ));
+    }
+    html.push_str("</body></html>");
+    Ok(html.into_bytes())
+}
+
+// ファイル名をURLのパスの1セグメントとしてパーセントエンコードする
+// ディレクトリを表す末尾の"/"はそのまま残す
+fn encode_path_segment(name: &str) -> String {
+    let mut output = String::new();
+    let (name, slash) = match name.strip_suffix('/') {
+        Some(name) => (name, "/"),
+        None => (name, ""),
+    };
+    for c in name.bytes() {
+        if c.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&c) {
+            output.push(c as char);
+        } else {
+            output.push_str(&format!("%{:02X}", c));
+        }
+    }
+    output.push_str(slash);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    // テストごとに別の一時ディレクトリを作る
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "net_std_file_{}_{}",
+            process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("failed to create a temp dir");
+        dir
+    }
+
+    fn load(url: &str) -> Result<HttpResponse, Error> {
+        let url = Url::parse(url).expect("failed to parse url");
+        FileHandler::new().load(&url)
+    }
+
+    #[test]
+    fn test_file() {
+        let dir = temp_dir("file");
+        fs::write(dir.join("a b.html"), "<p>hello</p>").expect("write");
+
+        let url = format!("file://{}/a%20b.html", dir.display());
+        let response = load(&url).expect("failed to load");
+        assert_eq!(200, response.status_code());
+        assert_eq!(
+            Some("text/html".to_string()),
+            response.headers().get("Content-Type")
+        );
+        assert_eq!("<p>hello</p>", response.body());
+
+        let url = format!("file://localhost{}/a%20b.html", dir.display());
+        assert!(load(&url).is_ok());
+        let url = format!("file://example.com{}/a%20b.html", dir.display());
+        assert!(load(&url).is_err());
+        let url = format!("file://{}/missing.html", dir.display());
+        assert!(load(&url).is_err());
+
+        fs::remove_dir_all(&dir).expect("failed to remove a temp dir");
+    }
+
+    #[test]
+    fn test_directory_listing() {
+        let dir = temp_dir("listing");
+        fs::write(dir.join("index.html"), "").expect("write");
+        fs::write(dir.join("<a>#.txt"), "").expect("write");
+        fs::create_dir(dir.join("sub")).expect("failed to create a dir");
+
+        let url = format!("file://{}", dir.display());
+        let response = load(&url).expect("failed to load");
+        let body = response.body();
+        assert!(body
+            .contains(&format!("<base href=\"file://{}/\">", dir.display())));
+        assert!(body.contains("<p><a href=\"../\">../</a></p>"));
+        assert!(body
+            .contains("<p><a href=\"%3Ca%3E%23.txt\">&lt;a&gt;#.txt</a></p>"));
+        assert!(body.contains("<p><a href=\"index.html\">index.html</a></p>"));
+        assert!(body.contains("<p><a href=\"sub/\">sub/</a></p>"));
+
+        fs::remove_dir_all(&dir).expect("failed to remove a temp dir");
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!("text/html", content_type(Path::new("/a/b.HTM")));
+        assert_eq!("text/css", content_type(Path::new("style.css")));
+        assert_eq!("application/octet-stream", content_type(Path::new("a")));
+    }
+}
@@ -0,0 +1,4 @@
+use crate::transport::TcpTransport;
+
+/// std::net::TcpStreamを使うHTTPクライアント
+pub type HttpClient = saba_core::http::HttpClient<TcpTransport>;
@@ -0,0 +1,6 @@
+// std環境(Linuxなどのホスト)で動作するネットワーク実装
+
+pub mod clock;
+pub mod file;
+pub mod http;
+pub mod transport;
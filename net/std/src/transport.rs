@@ -0,0 +1,163 @@
+use saba_core::error::Error;
+use saba_core::transport::Connection;
+use saba_core::transport::Transport;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+
+/// std::net::TcpStreamを使うTransport
+pub struct TcpTransport {}
+
+impl TcpTransport {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for TcpTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for TcpTransport {
+    type Connection = TcpConnection;
+
+    fn connect(&self, host: &str, port: u16) -> Result<TcpConnection, Error> {
+        let socket_addrs = match (host, port).to_socket_addrs() {
+            Ok(addrs) => addrs.collect::<Vec<_>>(),
+            Err(e) => {
+                return Err(Error::Network(format!(
+                    "Failed to find IP addresses: {:?}",
+                    e
+                )))
+            }
+        };
+
+        if socket_addrs.is_empty() {
+            return Err(Error::Network(
+                "Failed to find IP addresses".to_string(),
+            ));
+        }
+
+        match TcpStream::connect(&socket_addrs[..]) {
+            Ok(stream) => Ok(TcpConnection { stream }),
+            Err(_) => Err(Error::Network(
+                "Failed to connect to TCP stream".to_string(),
+            )),
+        }
+    }
+}
+
+pub struct TcpConnection {
+    stream: TcpStream,
+}
+
+impl Connection for TcpConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self.stream.write(buf) {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(Error::Network(
+                "Failed to send a request to TCP stream".to_string(),
+            )),
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self.stream.read(buf) {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(Error::Network(
+                "Failed to receive a request from TCP stream".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saba_core::http::HttpClient;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_loopback_server() {
+        // ローカルのループバックサーバーを実サイトの代わりに使う
+        let listener =
+            TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("failed to get addr").port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("failed to accept");
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).expect("failed to read");
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nDate: xx\r\n\r\n<p>loopback</p>",
+                )
+                .expect("failed to write");
+        });
+
+        let client = HttpClient::new(TcpTransport::new());
+        let res = client
+            .get("127.0.0.1".to_string(), port, "index.html".to_string())
+            .expect("failed to get http response");
+        server.join().expect("server thread panicked");
+
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.body(), "<p>loopback</p>");
+    }
+
+    // リクエストを空行まで読み込む
+    fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1];
+        while !request.ends_with(b"\n\n") && !request.ends_with(b"\r\n\r\n") {
+            if stream.read(&mut buf).expect("failed to read") == 0 {
+                break;
+            }
+            request.push(buf[0]);
+        }
+        String::from_utf8_lossy(&request).to_string()
+    }
+
+    #[test]
+    fn test_keep_alive_server() {
+        let listener =
+            TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("failed to get addr").port();
+        let server = thread::spawn(move || {
+            // 1つの接続だけを受け付ける。クライアントが新しい接続を作ると、接続に失敗する
+            let (mut stream, _) = listener.accept().expect("failed to accept");
+            drop(listener);
+
+            let first = read_request(&mut stream);
+            assert!(first.starts_with("GET /first HTTP/1.1"));
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nfirst")
+                .expect("failed to write");
+
+            let second = read_request(&mut stream);
+            assert!(second.starts_with("GET /second HTTP/1.1"));
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                      6\r\nsecond\r\n0\r\n\r\n",
+                )
+                .expect("failed to write");
+        });
+
+        let client = HttpClient::new(TcpTransport::new());
+        let first = client
+            .get("127.0.0.1".to_string(), port, "first".to_string())
+            .expect("failed to get http response");
+        assert_eq!(first.body(), "first");
+        let second = client
+            .get("127.0.0.1".to_string(), port, "second".to_string())
+            .expect("failed to get http response");
+        assert_eq!(second.body(), "second");
+        server.join().expect("server thread panicked");
+
+        assert_eq!(client.idle_connection_count("127.0.0.1", port), 1);
+    }
+}
@@ -0,0 +1,29 @@
+use noli::prelude::SystemApi;
+use noli::sys::wasabi::Api;
+use saba_core::clock::Clock;
+
+/// wasabi OSの時計を使うClock
+pub struct SystemClock {}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        self.now_millis() / 1000
+    }
+
+    fn now_millis(&self) -> u64 {
+        // OSから、UNIXエポックからのミリ秒を取得する
+        Api::get_time_millis()
+    }
+}
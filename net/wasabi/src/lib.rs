@@ -1,3 +1,5 @@
 #![no_std]
 
+pub mod clock;
 pub mod http;
+pub mod transport;
@@ -0,0 +1,77 @@
+extern crate alloc;
+use alloc::string::ToString;
+use noli::net::lookup_host;
+use noli::net::SocketAddr;
+use noli::net::TcpStream;
+use saba_core::error::Error;
+use saba_core::transport::Connection;
+use saba_core::transport::Transport;
+
+/// noliのTCPスタックを使うTransport
+pub struct TcpTransport {}
+
+impl TcpTransport {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for TcpTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for TcpTransport {
+    type Connection = TcpConnection;
+
+    fn connect(&self, host: &str, port: u16) -> Result<TcpConnection, Error> {
+        let ips = match lookup_host(host) {
+            Ok(ips) => ips,
+            Err(_) => {
+                return Err(Error::Network(
+                    "Failed to find IP addresses".to_string(),
+                ))
+            }
+        };
+
+        if ips.is_empty() {
+            return Err(Error::Network(
+                "Failed to find IP addresses".to_string(),
+            ));
+        }
+
+        let socket_addr: SocketAddr = (ips[0], port).into();
+
+        match TcpStream::connect(socket_addr) {
+            Ok(stream) => Ok(TcpConnection { stream }),
+            Err(_) => Err(Error::Network(
+                "Failed to connect to TCP stream".to_string(),
+            )),
+        }
+    }
+}
+
+pub struct TcpConnection {
+    stream: TcpStream,
+}
+
+impl Connection for TcpConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self.stream.write(buf) {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(Error::Network(
+                "Failed to send a request to TCP stream".to_string(),
+            )),
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self.stream.read(buf) {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(Error::Network(
+                "Failed to receive a request from TCP stream".to_string(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,265 @@
+// ブラウザが自分で生成するabout:のページ
+// https://datatracker.ietf.org/doc/html/rfc6694
+
+use crate::browser::Browser;
+use crate::error::Error;
+use crate::fetcher::SchemeHandler;
+use crate::http::HttpResponse;
+use crate::url::Url;
+use crate::utils::escape_html;
+use alloc::format;
+use alloc::rc::Weak;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+/// ビルドしたターゲットのアーキテクチャ
+const TARGET_ARCH: &str = if cfg!(target_arch = "x86_64") {
+    "x86_64"
+} else if cfg!(target_arch = "aarch64") {
+    "aarch64"
+} else {
+    "unknown"
+};
+
+/// about:blank, about:history, about:version, about:net-internalsのページを生成する
+/// ネットワークは使わず、Browserの履歴やNetLogからHTMLを作る。
+pub struct AboutHandler {
+    browser: Weak<RefCell<Browser>>,
+    // about:versionに表示する、有効になっている機能(example: "headless")
+    features: Vec<String>,
+}
+
+impl AboutHandler {
+    pub fn new(browser: Weak<RefCell<Browser>>) -> Self {
+        Self {
+            browser,
+            features: Vec::new(),
+        }
+    }
+
+    /// about:versionに表示する、有効になっている機能を設定する
+    pub fn set_features(&mut self, features: Vec<String>) {
+        self.features = features;
+    }
+
+    fn history(&self) -> String {
+        let history = match self.browser.upgrade() {
+            Some(browser) => browser.borrow().history(),
+            None => Vec::new(),
+        };
+
+        let mut body = String::from("<h1>History</h1>");
+        if history.is_empty() {
+            body.push_str("<p>No pages have been visited.</p>");
+        }
+        // 新しいものから順に表示する
+        for url in history.iter().rev() {
+            let url = escape_html(url);
+            body.push_str(&format!("<p><a href=\"{}\">{}</a></p>", url, url));
+        }
+        body
+    }
+
+    fn version(&self) -> String {
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+
+        let mut body = format!(
+            "<h1>SaBA</h1><p>Version: {}</p><p>Build: {} ({})</p>\
+             <h2>Features</h2>",
+            env!("CARGO_PKG_VERSION"),
+            TARGET_ARCH,
+            profile
+        );
+        if self.features.is_empty() {
+            body.push_str("<p>none</p>");
+        }
+        for feature in &self.features {
+            body.push_str(&format!("<p>{}</p>", escape_html(feature)));
+        }
+        body
+    }
+
+    fn net_internals(&self) -> String {
+        let entries = match self.browser.upgrade() {
+            Some(browser) => browser.borrow().net_log().borrow().entries(),
+            None => Vec::new(),
+        };
+
+        let mut body = String::from("<h1>Network</h1>");
+        if entries.is_empty() {
+            body.push_str("<p>No requests have been sent.</p>");
+        }
+        // 新しいものから順に、"GET http://example.com/ 200 15ms"の形式で表示する
+        for entry in entries.iter().rev() {
+            let result = match (entry.status_code(), entry.error()) {
+                (Some(status_code), _) => format!("{}", status_code),
+                (None, Some(error)) => error,
+                (None, None) => String::from("-"),
+            };
+            let duration = match entry.duration() {
+                Some(duration) => format!("{}ms", duration),
+                None => String::from("-"),
+            };
+            let cache = if entry.from_cache() { " (cache)" } else { "" };
+            body.push_str(&format!(
+                "<p>{} {} {} {}{}</p>",
+                entry.method().as_str(),
+                escape_html(&entry.url()),
+                escape_html(&result),
+                duration,
+                cache
+            ));
+        }
+        body
+    }
+}
+
+impl SchemeHandler for AboutHandler {
+    fn load(&mut self, url: &Url) -> Result<HttpResponse, Error> {
+        let body = match url.path().as_str() {
+            "blank" => String::new(),
+            "history" => self.history(),
+            "version" => self.version(),
+            "net-internals" => self.net_internals(),
+            _ => {
+                return Err(Error::UnexpectedInput(format!(
+                    "unknown about page: {}",
+                    url.href()
+                )))
+            }
+        };
+        let html = format!("<html><head></head><body>{}</body></html>", body);
+        HttpResponse::from_body("text/html; charset=utf-8", html.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_item::DisplayItem;
+    use crate::fetcher::FetchResponse;
+    use crate::fetcher::Fetcher;
+    use crate::fetcher::SchemeFetcher;
+    use crate::http::netlog::NetLogEntry;
+    use crate::http::request::HttpRequest;
+    use crate::http::request::Method;
+    use crate::url::Scheme;
+    use alloc::boxed::Box;
+    use alloc::rc::Rc;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    // about:のページを読み込み、Pageに表示したテキストを返す
+    fn open(browser: &Rc<RefCell<Browser>>, url: &str) -> Vec<String> {
+        let mut handler = AboutHandler::new(Rc::downgrade(browser));
+        handler.set_features(vec!["headless".to_string()]);
+        let response = handler
+            .load(&Url::parse(url).expect("failed to parse url"))
+            .expect("failed to load");
+
+        let page = browser.borrow().current_page();
+        page.borrow_mut()
+            .set_url(Url::parse(url).expect("failed to parse url"));
+        page.borrow_mut().receive_response(response);
+        let texts = page
+            .borrow()
+            .display_items()
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .collect();
+        texts
+    }
+
+    #[test]
+    fn test_blank() {
+        let browser = Browser::new();
+        assert!(open(&browser, "about:blank").is_empty());
+        // about:のページは履歴に残さない
+        assert!(browser.borrow().history().is_empty());
+    }
+
+    #[test]
+    fn test_history() {
+        let browser = Browser::new();
+        assert_eq!(
+            vec!["History", "No pages have been visited."],
+            open(&browser, "about:history")
+        );
+
+        browser
+            .borrow_mut()
+            .add_history("http://a.com/".to_string());
+        browser
+            .borrow_mut()
+            .add_history("http://b.com/".to_string());
+        assert_eq!(
+            vec!["History", "http://b.com/", "http://a.com/"],
+            open(&browser, "about:history")
+        );
+    }
+
+    #[test]
+    fn test_version() {
+        let browser = Browser::new();
+        let texts = open(&browser, "about:version");
+        assert_eq!("SaBA", texts[0]);
+        assert_eq!(format!("Version: {}", env!("CARGO_PKG_VERSION")), texts[1]);
+        assert_eq!(vec!["Features", "headless"], texts[3..].to_vec());
+    }
+
+    #[test]
+    fn test_net_internals() {
+        let browser = Browser::new();
+        let request = HttpRequest::new(
+            Method::Get,
+            "example.com".to_string(),
+            80,
+            "/".to_string(),
+        );
+        let response = HttpResponse::new("HTTP/1.1 200 OK\n\n".to_string());
+        let net_log = browser.borrow().net_log();
+        net_log.borrow_mut().record(NetLogEntry::new(
+            &request,
+            &response,
+            false,
+            Some(100),
+            Some(115),
+        ));
+        net_log
+            .borrow_mut()
+            .record(NetLogEntry::new(&request, &response, true, None, None));
+        assert_eq!(
+            vec![
+                "Network",
+                "GET http://example.com/ 200 - (cache)",
+                "GET http://example.com/ 200 15ms"
+            ],
+            open(&browser, "about:net-internals")
+        );
+    }
+
+    #[test]
+    fn test_scheme_fetcher() {
+        let browser = Browser::new();
+        let mut fetcher = SchemeFetcher::new(|_: String| {
+            Err::<HttpResponse, Error>(Error::Network("offline".to_string()))
+        });
+        fetcher.register(
+            Scheme::About,
+            Box::new(AboutHandler::new(Rc::downgrade(&browser))),
+        );
+        let response: FetchResponse = fetcher
+            .fetch("about:blank".to_string())
+            .expect("failed to fetch");
+        assert_eq!("about:blank", response.url());
+        assert!(fetcher.fetch("about:unknown".to_string()).is_err());
+    }
+}
@@ -1,5 +1,9 @@
+use crate::http::cache::HttpCache;
+use crate::http::cookie::CookieJar;
+use crate::http::netlog::NetLog;
 use crate::renderer::page::Page;
 use alloc::rc::Rc;
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 
@@ -7,6 +11,14 @@ use core::cell::RefCell;
 pub struct Browser {
     active_page_index: usize,
     pages: Vec<Rc<RefCell<Page>>>,
+    // すべてのページとHttpClientで共有するCookie
+    cookie_jar: Rc<RefCell<CookieJar>>,
+    // ナビゲーションをまたいで、すべてのページで共有するHTTPキャッシュ
+    http_cache: Rc<RefCell<HttpCache>>,
+    // HttpClientが送信したリクエストの記録。about:net-internalsで表示する
+    net_log: Rc<RefCell<NetLog>>,
+    // ページが表示したURL。古いものから順に並ぶ
+    history: Vec<String>,
 }
 
 impl Browser {
@@ -16,6 +28,10 @@ impl Browser {
         let browser = Rc::new(RefCell::new(Browser {
             active_page_index: 0,
             pages: Vec::new(),
+            cookie_jar: Rc::new(RefCell::new(CookieJar::new())),
+            http_cache: Rc::new(RefCell::new(HttpCache::new())),
+            net_log: Rc::new(RefCell::new(NetLog::new())),
+            history: Vec::new(),
         }));
 
         page.set_browser(Rc::downgrade(&browser));
@@ -30,4 +46,26 @@ impl Browser {
     pub fn current_page(&self) -> Rc<RefCell<Page>> {
         self.pages[self.active_page_index].clone()
     }
+
+    pub fn cookie_jar(&self) -> Rc<RefCell<CookieJar>> {
+        self.cookie_jar.clone()
+    }
+
+    pub fn http_cache(&self) -> Rc<RefCell<HttpCache>> {
+        self.http_cache.clone()
+    }
+
+    pub fn net_log(&self) -> Rc<RefCell<NetLog>> {
+        self.net_log.clone()
+    }
+
+    /// 表示したURLを履歴に追加する
+    pub fn add_history(&mut self, url: String) {
+        self.history.push(url);
+    }
+
+    /// 表示したURLの履歴。古いものから順に並ぶ
+    pub fn history(&self) -> Vec<String> {
+        self.history.clone()
+    }
 }
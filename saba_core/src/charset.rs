@@ -0,0 +1,199 @@
+// レスポンスボディの文字エンコーディングを判定し、文字列にデコードする
+// 判定の優先順位は、BOM、Content-Typeのcharset、<meta charset>の順
+// https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use encoding_rs::Encoding;
+use encoding_rs::UTF_16BE;
+use encoding_rs::UTF_16LE;
+use encoding_rs::UTF_8;
+
+// <meta>タグを探すのはボディの先頭1024バイトまで
+const PRESCAN_LENGTH: usize = 1024;
+
+/// 文字エンコーディングを判定する。判定できなかった場合はUTF-8として扱う。
+/// 返り値の2つ目はBOMのバイト数
+pub fn detect_encoding(
+    body: &[u8],
+    content_type: Option<&str>,
+) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(body) {
+        return (encoding, bom_length);
+    }
+
+    if let Some(encoding) = content_type.and_then(charset_from_content_type) {
+        return (encoding, 0);
+    }
+
+    if let Some(encoding) = prescan_meta_charset(body) {
+        return (encoding, 0);
+    }
+
+    (UTF_8, 0)
+}
+
+/// ボディを文字列にデコードする。不正なバイト列はU+FFFDに置き換える。
+pub fn decode(body: &[u8], content_type: Option<&str>) -> String {
+    let (encoding, bom_length) = detect_encoding(body, content_type);
+    match encoding.decode_without_bom_handling(&body[bom_length..]) {
+        (Cow::Borrowed(s), _) => String::from(s),
+        (Cow::Owned(s), _) => s,
+    }
+}
+
+// "text/html; charset=Shift_JIS"のような値からcharsetパラメータを取り出す
+fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    for param in content_type.split(';').skip(1) {
+        if let Some((name, value)) = param.split_once('=') {
+            if name.trim().eq_ignore_ascii_case("charset") {
+                let value = value.trim().trim_matches('"').trim_matches('\'');
+                return Encoding::for_label(value.as_bytes());
+            }
+        }
+    }
+    None
+}
+
+// ボディの先頭から<meta charset="...">または
+// <meta http-equiv="Content-Type" content="text/html; charset=...">を探す
+fn prescan_meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = &body[..core::cmp::min(body.len(), PRESCAN_LENGTH)];
+    let lower = head.to_ascii_lowercase();
+
+    let mut pos = 0;
+    while let Some(i) = find(&lower[pos..], b"<meta") {
+        let tag_start = pos + i;
+        let tag_end = match find(&lower[tag_start..], b">") {
+            Some(end) => tag_start + end,
+            None => lower.len(),
+        };
+        let tag = &lower[tag_start..tag_end];
+
+        if let Some(j) = find(tag, b"charset") {
+            let value = parse_charset_value(&tag[j + b"charset".len()..]);
+            if let Some(encoding) = Encoding::for_label(value) {
+                // UTF-16の<meta>指定はUTF-8として扱う
+                if encoding == UTF_16BE || encoding == UTF_16LE {
+                    return Some(UTF_8);
+                }
+                return Some(encoding);
+            }
+        }
+        pos = tag_end;
+    }
+    None
+}
+
+// "charset"の直後の`="utf-8"`や`=utf-8;`から値を取り出す
+fn parse_charset_value(s: &[u8]) -> &[u8] {
+    let s = skip_whitespace(s);
+    let s = match s.strip_prefix(b"=") {
+        Some(s) => skip_whitespace(s),
+        None => return &[],
+    };
+    let s = match s.first() {
+        Some(b'"') | Some(b'\'') => &s[1..],
+        _ => s,
+    };
+    let end = s
+        .iter()
+        .position(|c| {
+            matches!(c, b'"' | b'\'' | b';' | b'/' | b'>')
+                || c.is_ascii_whitespace()
+        })
+        .unwrap_or(s.len());
+    &s[..end]
+}
+
+fn skip_whitespace(s: &[u8]) -> &[u8] {
+    let start =
+        s.iter().position(|c| !c.is_ascii_whitespace()).unwrap_or(s.len());
+    &s[start..]
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::EUC_JP;
+    use encoding_rs::SHIFT_JIS;
+    use encoding_rs::WINDOWS_1252;
+
+    #[test]
+    fn test_default_utf8() {
+        let body = "<p>こんにちは</p>".as_bytes();
+        assert_eq!((UTF_8, 0), detect_encoding(body, None));
+        assert_eq!("<p>こんにちは</p>", decode(body, None));
+    }
+
+    #[test]
+    fn test_bom() {
+        let body = b"\xEF\xBB\xBF<p>a</p>";
+        assert_eq!((UTF_8, 3), detect_encoding(body, Some("text/html")));
+        assert_eq!("<p>a</p>", decode(body, None));
+
+        // BOMはContent-Typeのcharsetより優先される
+        let body = b"\xFF\xFEa\x00";
+        assert_eq!(
+            (UTF_16LE, 2),
+            detect_encoding(body, Some("text/html; charset=Shift_JIS"))
+        );
+        assert_eq!("a", decode(body, None));
+    }
+
+    #[test]
+    fn test_content_type() {
+        // "日本語"のShift_JIS表現
+        let body = b"\x93\xfa\x96\x7b\x8c\xea";
+        assert_eq!(
+            (SHIFT_JIS, 0),
+            detect_encoding(body, Some("text/html; charset=Shift_JIS"))
+        );
+        assert_eq!("日本語", decode(body, Some("text/html;charset=\"sjis\"")));
+
+        // "日本語"のEUC-JP表現
+        let body = b"\xc6\xfc\xcb\xdc\xb8\xec";
+        assert_eq!("日本語", decode(body, Some("text/html; charset=EUC-JP")));
+    }
+
+    #[test]
+    fn test_latin1() {
+        // ISO-8859-1はWindows-1252として扱う
+        let body = b"caf\xe9 \x80";
+        assert_eq!(
+            (WINDOWS_1252, 0),
+            detect_encoding(body, Some("text/html; charset=ISO-8859-1"))
+        );
+        assert_eq!("café €", decode(body, Some("text/html; charset=latin1")));
+    }
+
+    #[test]
+    fn test_meta_charset() {
+        let body =
+            b"<html><head><META Charset=\"euc-jp\"></head>\xc6\xfc\xcb\xdc</html>";
+        assert_eq!((EUC_JP, 0), detect_encoding(body, Some("text/html")));
+        assert_eq!(
+            "<html><head><META Charset=\"euc-jp\"></head>日本</html>",
+            decode(body, None)
+        );
+    }
+
+    #[test]
+    fn test_meta_http_equiv() {
+        let body = b"<meta name=\"viewport\"><meta http-equiv=\"Content-Type\" content=\"text/html; charset=shift_jis\">";
+        assert_eq!((SHIFT_JIS, 0), detect_encoding(body, None));
+    }
+
+    #[test]
+    fn test_unknown_charset() {
+        let body = b"<meta charset=\"unknown\">";
+        assert_eq!(
+            (UTF_8, 0),
+            detect_encoding(body, Some("text/html; charset=unknown"))
+        );
+    }
+}
@@ -0,0 +1,58 @@
+// 現在時刻の取得の抽象化
+// 接続プールのアイドル時間の判定などに使用する。wasabi OS, std, テストで実装を差し替えられるようにする。
+
+use alloc::rc::Rc;
+use core::cell::Cell;
+
+/// 現在時刻を、UNIXエポックからの秒数で返す
+pub trait Clock {
+    fn now(&self) -> u64;
+
+    /// 現在時刻をミリ秒単位で返す。リクエストにかかった時間の計測に使用する
+    /// ミリ秒単位の時刻を取得できない環境では、秒単位の時刻を変換した値になる。
+    fn now_millis(&self) -> u64 {
+        self.now() * 1000
+    }
+}
+
+/// 手動で時刻を進めるClock。テストで時間の経過を再現するために使用する。
+/// cloneしたインスタンス同士は同じ時刻を共有する。
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<u64>>,
+}
+
+impl ManualClock {
+    pub fn new(now: u64) -> Self {
+        Self { now: Rc::new(Cell::new(now)) }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, seconds: u64) {
+        self.now.set(self.now.get() + seconds);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.now.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new(100);
+        let shared = clock.clone();
+        shared.advance(5);
+        assert_eq!(105, clock.now());
+        clock.set(1);
+        assert_eq!(1, shared.now());
+    }
+}
@@ -0,0 +1,257 @@
+// data: URLの解釈
+// https://datatracker.ietf.org/doc/html/rfc2397
+// https://fetch.spec.whatwg.org/#data-urls
+
+use crate::error::Error;
+use crate::http::header::ContentType;
+use crate::http::HttpResponse;
+use crate::url::percent_decode;
+use crate::url::Scheme;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+// メディアタイプが省略された、または不正な場合のメディアタイプ
+const DEFAULT_CONTENT_TYPE: &str = "text/plain;charset=US-ASCII";
+
+/// data: URLに埋め込まれたデータ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataUrl {
+    // Content-Typeとして扱うメディアタイプ(example: "text/html;charset=utf-8")
+    content_type: String,
+    body: Vec<u8>,
+}
+
+impl DataUrl {
+    /// "data:[<mediatype>][;base64],<data>"の形式のURLを解釈する
+    /// https://fetch.spec.whatwg.org/#data-url-processor
+    pub fn parse(url: &Url) -> Result<Self, Error> {
+        if url.scheme() != Scheme::Data {
+            return Err(Error::UnexpectedInput(format!(
+                "{} is not a data url",
+                url.href()
+            )));
+        }
+
+        // フラグメントを除いた"data:"より後ろの部分
+        let href = url.href();
+        let href = match href.find('#') {
+            Some(i) => &href[..i],
+            None => &href,
+        };
+        let input = &href["data:".len()..];
+
+        let (media_type, encoded_body) = match input.split_once(',') {
+            Some(parts) => parts,
+            None => {
+                return Err(Error::UnexpectedInput(format!(
+                    "data url has no comma: {}",
+                    href
+                )))
+            }
+        };
+        let mut media_type =
+            media_type.trim_matches(|c: char| c.is_ascii_whitespace());
+        let mut body = percent_decode(encoded_body);
+
+        // ";base64"で終わる場合は、パーセントデコードした後にbase64デコードする
+        if let Some(rest) = strip_base64_suffix(media_type) {
+            let encoded = String::from_utf8_lossy(&body).to_string();
+            body = match base64_decode(&encoded) {
+                Some(body) => body,
+                None => {
+                    return Err(Error::UnexpectedInput(format!(
+                        "invalid base64 in data url: {}",
+                        href
+                    )))
+                }
+            };
+            media_type =
+                rest.trim_end_matches(|c: char| c.is_ascii_whitespace());
+        }
+
+        let content_type = if media_type.starts_with(';') {
+            format!("text/plain{}", media_type)
+        } else {
+            media_type.to_string()
+        };
+        let content_type = if is_valid_media_type(&content_type) {
+            content_type
+        } else {
+            DEFAULT_CONTENT_TYPE.to_string()
+        };
+
+        Ok(Self { content_type, body })
+    }
+
+    pub fn content_type(&self) -> String {
+        self.content_type.clone()
+    }
+
+    /// "text/html"のように、パラメーターを除いて小文字にしたメディアタイプ
+    pub fn mime_type(&self) -> String {
+        ContentType::parse(&self.content_type).mime_type()
+    }
+
+    pub fn body(&self) -> Vec<u8> {
+        self.body.clone()
+    }
+
+    /// データをボディとし、メディアタイプをContent-Typeとする200 OKのレスポンスを作成する
+    /// HTTPのレスポンスと同じように、Page::receive_responseに渡すことができる。
+    pub fn to_response(&self) -> Result<HttpResponse, Error> {
+        HttpResponse::from_body(&self.content_type, &self.body)
+    }
+}
+
+// メディアタイプが";"と0個以上の空白と"base64"で終わる場合は、それらを除いた部分を返す
+fn strip_base64_suffix(media_type: &str) -> Option<&str> {
+    let length = media_type.len();
+    if length < 6 || !media_type[length - 6..].eq_ignore_ascii_case("base64") {
+        return None;
+    }
+    let rest = media_type[..length - 6].trim_end_matches(' ');
+    rest.strip_suffix(';')
+}
+
+// "type/subtype"の形式で、typeとsubtypeがトークン文字だけからなるか
+fn is_valid_media_type(content_type: &str) -> bool {
+    let mime_type = ContentType::parse(content_type).mime_type();
+    let is_token = |s: &str| {
+        !s.is_empty()
+            && s.bytes().all(|c| {
+                c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+            })
+    };
+    match mime_type.split_once('/') {
+        Some((t, subtype)) => is_token(t) && is_token(subtype),
+        None => false,
+    }
+}
+
+/// base64の文字列をデコードする。空白は無視し、末尾の"="は省略できる
+/// 不正な文字が含まれる場合や、長さが不正な場合はNoneを返す。
+/// https://infra.spec.whatwg.org/#forgiving-base64-decode
+pub fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    let mut data: Vec<u8> = input
+        .bytes()
+        .filter(|c| !matches!(c, b' ' | b'\t' | b'\n' | b'\x0c' | b'\r'))
+        .collect();
+
+    if data.len() % 4 == 0 {
+        if data.ends_with(b"==") {
+            data.truncate(data.len() - 2);
+        } else if data.ends_with(b"=") {
+            data.truncate(data.len() - 1);
+        }
+    }
+    if data.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut output = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in data {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    // 最後に余ったビットは捨てる
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> Result<DataUrl, Error> {
+        DataUrl::parse(&Url::parse(url).expect("failed to parse url"))
+    }
+
+    #[test]
+    fn test_base64_decode() {
+        assert_eq!(Some(b"hello".to_vec()), base64_decode("aGVsbG8="));
+        assert_eq!(Some(b"hello".to_vec()), base64_decode("aGVsbG8"));
+        assert_eq!(Some(b"hi!?".to_vec()), base64_decode("aGkh Pw =="));
+        assert_eq!(Some(Vec::new()), base64_decode(""));
+        assert_eq!(Some([0xfb, 0xff].to_vec()), base64_decode("+/8="));
+        assert_eq!(None, base64_decode("aGVsbG8=="));
+        assert_eq!(None, base64_decode("a"));
+        assert_eq!(None, base64_decode("aGV*"));
+        assert_eq!(None, base64_decode("aG=sbG8="));
+    }
+
+    #[test]
+    fn test_percent_encoded() {
+        let data = parse("data:text/html,<p>hello%20world</p>#frag")
+            .expect("failed to parse data url");
+        assert_eq!("text/html", data.content_type());
+        assert_eq!("text/html", data.mime_type());
+        assert_eq!(b"<p>hello world</p>".to_vec(), data.body());
+
+        let data = parse("data:,a%2Cb").expect("failed to parse data url");
+        assert_eq!(DEFAULT_CONTENT_TYPE, data.content_type());
+        assert_eq!(b"a,b".to_vec(), data.body());
+    }
+
+    #[test]
+    fn test_base64() {
+        let data = parse("data:text/css;charset=utf-8;BASE64,cCB7fQ==")
+            .expect("failed to parse data url");
+        assert_eq!("text/css;charset=utf-8", data.content_type());
+        assert_eq!(b"p {}".to_vec(), data.body());
+
+        let data = parse("data:image/png ; base64,iVBORw0K")
+            .expect("failed to parse data url");
+        assert_eq!("image/png", data.content_type());
+        assert_eq!("image/png", data.mime_type());
+        assert_eq!(b"\x89PNG\r\n".to_vec(), data.body());
+
+        let data = parse("data:;base64,aGk=").expect("failed to parse");
+        assert_eq!(DEFAULT_CONTENT_TYPE, data.content_type());
+        assert_eq!(b"hi".to_vec(), data.body());
+
+        assert!(parse("data:;base64,a").is_err());
+        // ";base64"で終わらない場合は、base64として扱わない
+        let data = parse("data:text/plain;base64x,aGk=").expect("failed");
+        assert_eq!(b"aGk=".to_vec(), data.body());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse("data:text/html").is_err());
+        assert!(parse("http://example.com/,a").is_err());
+
+        let data = parse("data:html,a").expect("failed to parse data url");
+        assert_eq!(DEFAULT_CONTENT_TYPE, data.content_type());
+    }
+
+    #[test]
+    fn test_to_response() {
+        let response = parse("data:text/html;charset=Shift_JIS,%82%A0")
+            .expect("failed to parse data url")
+            .to_response()
+            .expect("failed to create response");
+        assert_eq!(200, response.status_code());
+        assert_eq!(
+            Some("text/html;charset=Shift_JIS".to_string()),
+            response.headers().get("Content-Type")
+        );
+        assert_eq!("あ", response.body());
+    }
+}
@@ -6,4 +6,32 @@ pub enum Error {
     UnexpectedInput(String),
     InvalidUI(String),
     Other(String),
+    Http(HttpError),
+}
+
+/// HTTPレスポンスが文法に沿っていない、またはリダイレクトを辿れない時のエラー
+/// 値には、問題のあった行や値、URLが入る。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpError {
+    /// ステータスラインを受信する前に接続が閉じられた
+    MissingStatusLine,
+    /// ステータスラインが"HTTP-version SP status-code SP reason-phrase"の形式ではない
+    MalformedStatusLine(String),
+    /// HTTP-versionが"HTTP/x.y"の形式ではない
+    InvalidVersion(String),
+    /// ステータスコードが100から599の3桁の数字ではない
+    InvalidStatusCode(String),
+    /// ヘッダーの行が"name: value"の形式ではない
+    InvalidHeader(String),
+    InvalidContentLength(String),
+    /// チャンク形式のボディが不正、または途中で切れている
+    InvalidChunk(String),
+    /// Content-Encodingで圧縮されたボディを復元できない
+    InvalidContentEncoding(String),
+    /// リダイレクトのLocationヘッダーをURLとして解釈できない
+    InvalidLocation(String),
+    /// リダイレクトの回数が上限を超えた。値は最後にリクエストしたURL
+    TooManyRedirects(String),
+    /// 同じURLから同じLocationへのリダイレクトが繰り返された。値はループしているURL
+    RedirectLoop(String),
 }
@@ -0,0 +1,274 @@
+// URLからHTTPレスポンスを取得する処理の抽象化
+// UIは、このトレイトを通してページの内容を取得する。
+
+use crate::data_url::DataUrl;
+use crate::error::Error;
+use crate::http::redirect::follow_redirects;
+use crate::http::request::HttpRequest;
+use crate::http::request::Method;
+use crate::http::HttpClient;
+use crate::http::HttpResponse;
+use crate::transport::Transport;
+use crate::url::Scheme;
+use crate::url::Url;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Fetcherが返すレスポンス
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchResponse {
+    // リダイレクトされた場合は、最後にリクエストしたURL。フラグメントを含む
+    url: String,
+    response: HttpResponse,
+}
+
+impl FetchResponse {
+    pub fn new(url: String, response: HttpResponse) -> Self {
+        Self { url, response }
+    }
+
+    /// レスポンスを受信したURL。アドレスバーに表示する
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn response(&self) -> HttpResponse {
+        self.response.clone()
+    }
+}
+
+/// URLを受け取り、そのURLのレスポンスを返す
+pub trait Fetcher {
+    fn fetch(&mut self, url: String) -> Result<FetchResponse, Error>;
+}
+
+// fn(String) -> Result<HttpResponse, Error>のような関数やクロージャもFetcherとして扱えるようにする
+// リダイレクトは辿らないため、レスポンスのURLは受け取ったURLのままになる
+impl<F> Fetcher for F
+where
+    F: FnMut(String) -> Result<HttpResponse, Error>,
+{
+    fn fetch(&mut self, url: String) -> Result<FetchResponse, Error> {
+        let response = self(url.clone())?;
+        Ok(FetchResponse::new(url, response))
+    }
+}
+
+// 301, 302, 303, 307, 308のリダイレクトを辿り、最後のレスポンスを返す
+// data: URLの場合は、ネットワークを使わずにURLに埋め込まれたデータをレスポンスとして返す
+impl<T: Transport> Fetcher for HttpClient<T> {
+    fn fetch(&mut self, url: String) -> Result<FetchResponse, Error> {
+        let parsed_url = match Url::parse(&url) {
+            Ok(url) => url,
+            Err(e) => return Err(Error::UnexpectedInput(e)),
+        };
+        if parsed_url.scheme() == Scheme::Data {
+            let response = DataUrl::parse(&parsed_url)?.to_response()?;
+            return Ok(FetchResponse::new(parsed_url.href(), response));
+        }
+        let request = HttpRequest::from_url(Method::Get, &parsed_url)?;
+        let (_, response, mut final_url) =
+            follow_redirects(request, |request| self.send(request))?;
+        // Locationにフラグメントがない場合は、元のURLのフラグメントを引き継ぐ
+        if let (None, Some(fragment)) =
+            (final_url.fragment(), parsed_url.fragment())
+        {
+            if let Ok(url) = final_url.resolve(&format!("#{}", fragment)) {
+                final_url = url;
+            }
+        }
+        Ok(FetchResponse::new(final_url.href(), response))
+    }
+}
+
+/// 1つのスキームのURLを読み込み、レスポンスを返す処理
+/// file:のようにホスト環境に依存するスキームは、対応する環境でだけSchemeFetcherに登録する。
+pub trait SchemeHandler {
+    fn load(&mut self, url: &Url) -> Result<HttpResponse, Error>;
+}
+
+// fn(&Url) -> Result<HttpResponse, Error>のような関数やクロージャもSchemeHandlerとして扱えるようにする
+impl<F> SchemeHandler for F
+where
+    F: FnMut(&Url) -> Result<HttpResponse, Error>,
+{
+    fn load(&mut self, url: &Url) -> Result<HttpResponse, Error> {
+        self(url)
+    }
+}
+
+/// スキームごとに登録したSchemeHandlerでURLを読み込むFetcher
+/// 登録されていないスキームのURLは、fallbackのFetcher(通常はHttpClient)に渡す。
+pub struct SchemeFetcher<F: Fetcher> {
+    fallback: F,
+    handlers: Vec<(Scheme, Box<dyn SchemeHandler>)>,
+}
+
+impl<F: Fetcher> SchemeFetcher<F> {
+    pub fn new(fallback: F) -> Self {
+        Self {
+            fallback,
+            handlers: Vec::new(),
+        }
+    }
+
+    /// schemeのURLを読み込むhandlerを登録する。すでに登録されている場合は置き換える
+    pub fn register(
+        &mut self,
+        scheme: Scheme,
+        handler: Box<dyn SchemeHandler>,
+    ) {
+        self.handlers.retain(|(s, _)| *s != scheme);
+        self.handlers.push((scheme, handler));
+    }
+
+    /// schemeのhandlerが登録されているか
+    pub fn is_registered(&self, scheme: &Scheme) -> bool {
+        self.handlers.iter().any(|(s, _)| s == scheme)
+    }
+}
+
+impl<F: Fetcher> Fetcher for SchemeFetcher<F> {
+    fn fetch(&mut self, url: String) -> Result<FetchResponse, Error> {
+        let parsed_url = match Url::parse(&url) {
+            Ok(url) => url,
+            Err(_) => return self.fallback.fetch(url),
+        };
+        let scheme = parsed_url.scheme();
+        match self.handlers.iter_mut().find(|(s, _)| *s == scheme) {
+            Some((_, handler)) => {
+                let response = handler.load(&parsed_url)?;
+                Ok(FetchResponse::new(parsed_url.href(), response))
+            }
+            None => self.fallback.fetch(url),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MemoryTransport;
+    use alloc::format;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_closure() {
+        let mut fetcher = |url: String| {
+            HttpResponse::new(format!("HTTP/1.1 200 OK\nDate: xx\n\n{}", url))
+        };
+        let res = fetcher
+            .fetch("http://example.com".to_string())
+            .expect("failed to fetch");
+        assert_eq!(res.url(), "http://example.com");
+        assert_eq!(res.response().body(), "http://example.com");
+    }
+
+    #[test]
+    fn test_http_client() {
+        let mut transport = MemoryTransport::new();
+        transport.add_response(
+            "localhost",
+            8000,
+            "/test.html",
+            b"HTTP/1.1 200 OK\r\nDate: xx\r\n\r\ntest",
+        );
+        let mut client = HttpClient::new(transport);
+        let res = client
+            .fetch("http://localhost:8000/test.html".to_string())
+            .expect("failed to fetch");
+        assert_eq!(res.url(), "http://localhost:8000/test.html");
+        assert_eq!(res.response().status_code(), 200);
+        assert_eq!(res.response().body(), "test");
+    }
+
+    #[test]
+    fn test_http_client_redirect() {
+        let mut transport = MemoryTransport::new();
+        transport.add_response(
+            "localhost",
+            8000,
+            "/old/index.html",
+            b"HTTP/1.1 301 Moved Permanently\r\nLocation: ../new/\r\nContent-Length: 0\r\n\r\n",
+        );
+        transport.add_response(
+            "localhost",
+            8000,
+            "/new/",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nnew",
+        );
+        let mut client = HttpClient::new(transport.clone());
+        let res = client
+            .fetch("http://localhost:8000/old/index.html".to_string())
+            .expect("failed to fetch");
+        assert_eq!(res.url(), "http://localhost:8000/new/");
+        assert_eq!(res.response().body(), "new");
+        assert_eq!(2, transport.requests().len());
+
+        // 元のURLのフラグメントは、Locationにフラグメントがない場合だけ引き継ぐ
+        let res = client
+            .fetch("http://localhost:8000/old/index.html#x".to_string())
+            .expect("failed to fetch");
+        assert_eq!(res.url(), "http://localhost:8000/new/#x");
+    }
+
+    #[test]
+    fn test_data_url() {
+        let transport = MemoryTransport::new();
+        let mut client = HttpClient::new(transport.clone());
+        let res = client
+            .fetch("data:text/html;base64,PHA+aGk8L3A+".to_string())
+            .expect("failed to fetch");
+        assert_eq!(res.url(), "data:text/html;base64,PHA+aGk8L3A+");
+        assert_eq!(res.response().body(), "<p>hi</p>");
+        assert_eq!(0, transport.requests().len());
+
+        assert!(client.fetch("data:text/html".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_scheme_fetcher() {
+        let mut transport = MemoryTransport::new();
+        transport.add_response(
+            "localhost",
+            8000,
+            "/",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nhttp",
+        );
+        let mut fetcher = SchemeFetcher::new(HttpClient::new(transport));
+        assert!(!fetcher.is_registered(&Scheme::File));
+        // 登録されていないスキームは読み込めない
+        assert!(fetcher.fetch("file:///index.html".to_string()).is_err());
+
+        fetcher.register(
+            Scheme::File,
+            Box::new(|url: &Url| {
+                HttpResponse::new(format!("HTTP/1.1 200 OK\n\n{}", url.path()))
+            }),
+        );
+        assert!(fetcher.is_registered(&Scheme::File));
+        let res = fetcher
+            .fetch("file:///tmp/index.html#a".to_string())
+            .expect("failed to fetch");
+        assert_eq!(res.url(), "file:///tmp/index.html#a");
+        assert_eq!(res.response().body(), "/tmp/index.html");
+
+        // HTTPとdata:はfallbackに渡す
+        let res = fetcher
+            .fetch("http://localhost:8000/".to_string())
+            .expect("failed to fetch");
+        assert_eq!(res.response().body(), "http");
+        let res = fetcher
+            .fetch("data:,data".to_string())
+            .expect("failed to fetch");
+        assert_eq!(res.response().body(), "data");
+    }
+
+    #[test]
+    fn test_unsupported_url() {
+        let mut client = HttpClient::new(MemoryTransport::new());
+        assert!(client.fetch("example.com".to_string()).is_err());
+    }
+}
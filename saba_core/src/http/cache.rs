@@ -0,0 +1,548 @@
+// HTTPレスポンスをメモリ上に保存し、同じURLへのリクエストに再利用するキャッシュ
+// https://datatracker.ietf.org/doc/html/rfc9111
+
+use super::date::parse_date;
+use super::header::HeaderMap;
+use super::request::HttpRequest;
+use super::request::Method;
+use super::HttpResponse;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// キャッシュに保存するボディの合計サイズのデフォルト値(バイト)
+pub const DEFAULT_MAX_CACHE_SIZE: usize = 4 * 1024 * 1024;
+
+// Last-Modifiedから有効期間を推測する場合の割合(%)
+// https://datatracker.ietf.org/doc/html/rfc9111#section-4.2.2
+const HEURISTIC_FRESHNESS_PERCENT: u64 = 10;
+
+// 有効期間を指定していなくても保存できるステータスコード
+// https://datatracker.ietf.org/doc/html/rfc9110#section-15.1
+const HEURISTICALLY_CACHEABLE: [u32; 11] =
+    [200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// キャッシュを調べた結果
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheLookup {
+    /// 有効期間内のレスポンス。ネットワークにリクエストを送らずに使える
+    Fresh(HttpResponse),
+    /// 有効期間が切れているか、使う前に検証が必要なレスポンス。
+    /// 値は、条件付きリクエストにするために追加するヘッダー
+    Stale(HeaderMap),
+    /// 保存されていない
+    Miss,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    // "http://host:port/target"の形式のURL
+    key: String,
+    response: HttpResponse,
+    // Varyヘッダーで指定されたリクエストヘッダーの、保存した時の値
+    vary: Vec<(String, Option<String>)>,
+    // レスポンスを受信した時刻。Clockがない場合はNone
+    response_time: Option<u64>,
+    // 最後に使われた順番。容量を超えた時に、最も長く使われていないものから削除する
+    last_used: u64,
+}
+
+impl CacheEntry {
+    fn size(&self) -> usize {
+        self.response.body.len()
+    }
+
+    // 受信してからの経過時間と、Ageヘッダーの値を合わせた秒数
+    // https://datatracker.ietf.org/doc/html/rfc9111#section-4.2.3
+    fn current_age(&self, now: u64) -> Option<u64> {
+        let response_time = self.response_time?;
+        let age = self
+            .response
+            .headers
+            .get("Age")
+            .and_then(|age| age.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        Some(now.saturating_sub(response_time) + age)
+    }
+
+    // 有効期間の秒数。max-age、Expires、Last-Modifiedの順に使う
+    // https://datatracker.ietf.org/doc/html/rfc9111#section-4.2.1
+    fn freshness_lifetime(&self) -> Option<u64> {
+        let headers = &self.response.headers;
+        if let Some(max_age) = cache_control_value(headers, "max-age") {
+            return Some(max_age.parse::<u64>().unwrap_or(0));
+        }
+
+        let date = headers
+            .get("Date")
+            .and_then(|date| parse_date(&date))
+            .or(self.response_time)?;
+        if let Some(expires) = headers.get("Expires") {
+            // 解釈できないExpires("0"など)は、期限切れとして扱う
+            return Some(match parse_date(&expires) {
+                Some(expires) => expires.saturating_sub(date),
+                None => 0,
+            });
+        }
+
+        let last_modified =
+            headers.get("Last-Modified").and_then(|d| parse_date(&d))?;
+        Some(date.saturating_sub(last_modified) * HEURISTIC_FRESHNESS_PERCENT / 100)
+    }
+
+    fn is_fresh(&self, now: Option<u64>) -> bool {
+        if has_cache_directive(&self.response.headers, "no-cache") {
+            return false;
+        }
+        match (now, self.freshness_lifetime()) {
+            (Some(now), Some(lifetime)) => match self.current_age(now) {
+                Some(age) => age < lifetime,
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn matches_vary(&self, request: &HttpRequest) -> bool {
+        let headers = request.headers();
+        self.vary.iter().all(|(name, value)| &headers.get(name) == value)
+    }
+}
+
+/// メモリ上のHTTPキャッシュ。保存するボディの合計サイズが上限を超えると、
+/// 最も長く使われていないレスポンスから削除する。
+/// 時刻(now)が分からない場合は有効期間を判断できないため、保存したレスポンスは毎回検証する。
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    entries: Vec<CacheEntry>,
+    max_size: usize,
+    use_count: u64,
+}
+
+impl HttpCache {
+    pub fn new() -> Self {
+        Self::with_max_size(DEFAULT_MAX_CACHE_SIZE)
+    }
+
+    pub fn with_max_size(max_size: usize) -> Self {
+        Self { entries: Vec::new(), max_size, use_count: 0 }
+    }
+
+    /// 保存しているレスポンスの数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 保存しているボディの合計サイズ
+    pub fn size(&self) -> usize {
+        self.entries.iter().map(|e| e.size()).sum()
+    }
+
+    /// 保存しているレスポンスのURL
+    pub fn urls(&self) -> Vec<String> {
+        self.entries.iter().map(|e| e.key.clone()).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// requestに使えるレスポンスを探す
+    /// https://datatracker.ietf.org/doc/html/rfc9111#section-4
+    pub fn lookup(
+        &mut self,
+        request: &HttpRequest,
+        now: Option<u64>,
+    ) -> CacheLookup {
+        if request.method() != Method::Get
+            || has_cache_directive(&request.headers(), "no-store")
+        {
+            return CacheLookup::Miss;
+        }
+
+        let key = cache_key(request);
+        let index = match self
+            .entries
+            .iter()
+            .position(|e| e.key == key && e.matches_vary(request))
+        {
+            Some(index) => index,
+            None => return CacheLookup::Miss,
+        };
+
+        self.use_count += 1;
+        let entry = &mut self.entries[index];
+        entry.last_used = self.use_count;
+
+        // リクエストでno-cacheが指定された場合は、有効期間内でも検証する
+        let request_no_cache =
+            has_cache_directive(&request.headers(), "no-cache")
+                || request.headers().has_token("Pragma", "no-cache");
+        if !request_no_cache && entry.is_fresh(now) {
+            return CacheLookup::Fresh(entry.response.clone());
+        }
+
+        let mut conditions = HeaderMap::new();
+        if let Some(etag) = entry.response.headers.get("ETag") {
+            conditions.set("If-None-Match", &etag);
+        }
+        if let Some(last_modified) = entry.response.headers.get("Last-Modified")
+        {
+            conditions.set("If-Modified-Since", &last_modified);
+        }
+        // 検証に使える値がない場合は、保存したレスポンスは使えない
+        if conditions.is_empty() {
+            self.entries.remove(index);
+            return CacheLookup::Miss;
+        }
+        CacheLookup::Stale(conditions)
+    }
+
+    /// ネットワークから受信したレスポンスを保存する。保存できないレスポンスの場合は何もしない
+    /// 304 Not Modifiedの場合は、保存しているレスポンスのヘッダーを更新し、そのレスポンスを返す。
+    /// それ以外の場合は、受け取ったレスポンスをそのまま返す。
+    /// https://datatracker.ietf.org/doc/html/rfc9111#section-3
+    pub fn store(
+        &mut self,
+        request: &HttpRequest,
+        response: HttpResponse,
+        now: Option<u64>,
+    ) -> HttpResponse {
+        // GET以外のメソッドは、対象のURLを変更する可能性があるため、保存しているレスポンスを削除する
+        // https://datatracker.ietf.org/doc/html/rfc9111#section-4.4
+        if request.method() != Method::Get {
+            if request.method() != Method::Head {
+                let key = cache_key(request);
+                self.entries.retain(|e| e.key != key);
+            }
+            return response;
+        }
+
+        let key = cache_key(request);
+        if response.status_code == 304 {
+            return self.update(request, &key, response, now);
+        }
+
+        if !is_storable(request, &response) {
+            self.entries.retain(|e| !(e.key == key && e.matches_vary(request)));
+            return response;
+        }
+
+        let vary = response
+            .headers
+            .get_all("Vary")
+            .iter()
+            .flat_map(|v| {
+                v.split(',')
+                    .map(|name| name.trim().to_string())
+                    .collect::<Vec<String>>()
+            })
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let value = request.headers().get(&name);
+                (name, value)
+            })
+            .collect();
+
+        self.use_count += 1;
+        let entry = CacheEntry {
+            key: key.clone(),
+            response: response.clone(),
+            vary,
+            response_time: now,
+            last_used: self.use_count,
+        };
+        // 上限より大きいレスポンスは保存しない
+        if entry.size() > self.max_size {
+            return response;
+        }
+
+        self.entries.retain(|e| !(e.key == key && e.matches_vary(request)));
+        self.entries.push(entry);
+        self.evict();
+        response
+    }
+
+    // 304 Not Modifiedを受信した時に、保存しているレスポンスを新しいヘッダーで更新する
+    // https://datatracker.ietf.org/doc/html/rfc9111#section-4.3.4
+    fn update(
+        &mut self,
+        request: &HttpRequest,
+        key: &str,
+        not_modified: HttpResponse,
+        now: Option<u64>,
+    ) -> HttpResponse {
+        let entry = match self
+            .entries
+            .iter_mut()
+            .find(|e| e.key == key && e.matches_vary(request))
+        {
+            Some(entry) => entry,
+            // 条件付きリクエストを送っていない場合は、304をそのまま返す
+            None => return not_modified,
+        };
+
+        // ボディの長さや符号化に関するヘッダーは、保存しているボディのものを使う
+        for header in &not_modified.headers {
+            let name = header.name();
+            if name.eq_ignore_ascii_case("Content-Length")
+                || name.eq_ignore_ascii_case("Content-Encoding")
+                || name.eq_ignore_ascii_case("Transfer-Encoding")
+            {
+                continue;
+            }
+            entry.response.headers.set(&name, &header.value());
+        }
+        entry.response_time = now;
+        self.use_count += 1;
+        entry.last_used = self.use_count;
+        entry.response.clone()
+    }
+
+    // 合計サイズが上限を下回るまで、最も長く使われていないレスポンスを削除する
+    fn evict(&mut self) {
+        while self.size() > self.max_size {
+            let oldest = self
+                .entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(i, _)| i);
+            match oldest {
+                Some(index) => {
+                    self.entries.remove(index);
+                }
+                None => return,
+            }
+        }
+    }
+}
+
+impl Default for HttpCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn cache_key(request: &HttpRequest) -> String {
+    format!("http://{}:{}{}", request.host(), request.port(), request.target())
+}
+
+// レスポンスを保存してよいかどうか
+// https://datatracker.ietf.org/doc/html/rfc9111#section-3
+fn is_storable(request: &HttpRequest, response: &HttpResponse) -> bool {
+    let headers = &response.headers;
+    if has_cache_directive(&request.headers(), "no-store")
+        || has_cache_directive(headers, "no-store")
+        || headers.has_token("Vary", "*")
+    {
+        return false;
+    }
+
+    // 有効期間か、検証に使う値のどちらかがなければ再利用できない
+    let explicit = cache_control_value(headers, "max-age").is_some()
+        || headers.contains("Expires");
+    let validator =
+        headers.contains("ETag") || headers.contains("Last-Modified");
+    if !explicit && !validator {
+        return false;
+    }
+
+    explicit || HEURISTICALLY_CACHEABLE.contains(&response.status_code)
+}
+
+// Cache-Controlに、値を持たないディレクティブ(no-cache, no-storeなど)が含まれるかどうか
+// https://datatracker.ietf.org/doc/html/rfc9111#section-5.2
+fn has_cache_directive(headers: &HeaderMap, directive: &str) -> bool {
+    headers.get_all("Cache-Control").iter().any(|value| {
+        value.split(',').any(|d| {
+            let name = d.split('=').next().unwrap_or("");
+            name.trim().eq_ignore_ascii_case(directive)
+        })
+    })
+}
+
+// Cache-Controlの"name=value"形式のディレクティブの値
+fn cache_control_value(headers: &HeaderMap, directive: &str) -> Option<String> {
+    for value in headers.get_all("Cache-Control") {
+        for d in value.split(',') {
+            if let Some((name, value)) = d.split_once('=') {
+                if name.trim().eq_ignore_ascii_case(directive) {
+                    return Some(value.trim().trim_matches('"').to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(target: &str) -> HttpRequest {
+        HttpRequest::new(
+            Method::Get,
+            "example.com".to_string(),
+            80,
+            target.to_string(),
+        )
+    }
+
+    fn response(headers: &str, body: &str) -> HttpResponse {
+        HttpResponse::new(format!("HTTP/1.1 200 OK\r\n{}\r\n{}", headers, body))
+            .expect("failed to parse http response")
+    }
+
+    #[test]
+    fn test_max_age() {
+        let mut cache = HttpCache::new();
+        let req = request("/a");
+        assert_eq!(CacheLookup::Miss, cache.lookup(&req, Some(1000)));
+
+        cache.store(
+            &req,
+            response("Cache-Control: max-age=60\r\n", "a"),
+            Some(1000),
+        );
+        match cache.lookup(&req, Some(1059)) {
+            CacheLookup::Fresh(res) => assert_eq!("a", res.body()),
+            lookup => panic!("unexpected lookup result {:?}", lookup),
+        }
+        // 有効期間が切れていて、検証に使う値もない
+        assert_eq!(CacheLookup::Miss, cache.lookup(&req, Some(1060)));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_expires() {
+        let mut cache = HttpCache::new();
+        let req = request("/a");
+        cache.store(
+            &req,
+            response(
+                "Date: Wed, 21 Oct 2015 07:28:00 GMT\r\n\
+                 Expires: Wed, 21 Oct 2015 07:29:00 GMT\r\n",
+                "a",
+            ),
+            Some(1445412480),
+        );
+        assert!(matches!(
+            cache.lookup(&req, Some(1445412530)),
+            CacheLookup::Fresh(_)
+        ));
+        assert_eq!(CacheLookup::Miss, cache.lookup(&req, Some(1445412540)));
+
+        // 解釈できないExpiresは期限切れとして扱う
+        cache.store(
+            &req,
+            response("Expires: 0\r\nETag: \"x\"\r\n", "a"),
+            Some(1000),
+        );
+        assert!(matches!(
+            cache.lookup(&req, Some(1000)),
+            CacheLookup::Stale(_)
+        ));
+    }
+
+    #[test]
+    fn test_validation() {
+        let mut cache = HttpCache::new();
+        let req = request("/a");
+        cache.store(
+            &req,
+            response(
+                "ETag: \"v1\"\r\nLast-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\n\
+                 Cache-Control: no-cache\r\nContent-Length: 4\r\n",
+                "body",
+            ),
+            None,
+        );
+
+        let conditions = match cache.lookup(&req, None) {
+            CacheLookup::Stale(conditions) => conditions,
+            lookup => panic!("unexpected lookup result {:?}", lookup),
+        };
+        assert_eq!(Some("\"v1\"".to_string()), conditions.get("If-None-Match"));
+        assert_eq!(
+            Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            conditions.get("If-Modified-Since")
+        );
+
+        let not_modified = HttpResponse::new(
+            "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nX-Updated: yes\r\n\r\n"
+                .to_string(),
+        )
+        .expect("failed to parse http response");
+        let res = cache.store(&req, not_modified, None);
+        assert_eq!(200, res.status_code());
+        assert_eq!("body", res.body());
+        assert_eq!(Ok("yes".to_string()), res.header_value("X-Updated"));
+        assert_eq!(Ok("4".to_string()), res.header_value("Content-Length"));
+    }
+
+    #[test]
+    fn test_not_storable() {
+        let mut cache = HttpCache::new();
+        let req = request("/a");
+        cache.store(
+            &req,
+            response("Cache-Control: no-store, max-age=60\r\n", "a"),
+            Some(0),
+        );
+        cache.store(&req, response("", "a"), Some(0));
+        cache.store(&req, response("Vary: *\r\nETag: \"x\"\r\n", "a"), Some(0));
+        assert!(cache.is_empty());
+
+        // POSTは保存せず、同じURLのレスポンスを削除する
+        cache.store(&req, response("ETag: \"x\"\r\n", "a"), Some(0));
+        assert_eq!(1, cache.len());
+        let post = HttpRequest::new(
+            Method::Post,
+            "example.com".to_string(),
+            80,
+            "/a".to_string(),
+        );
+        cache.store(&post, response("ETag: \"y\"\r\n", "b"), Some(0));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_vary() {
+        let mut cache = HttpCache::new();
+        let ja = request("/a").with_header("Accept-Language", "ja");
+        let en = request("/a").with_header("Accept-Language", "en");
+        cache.store(
+            &ja,
+            response("Vary: Accept-Language\r\nCache-Control: max-age=60\r\n", "ja"),
+            Some(0),
+        );
+        assert!(matches!(cache.lookup(&ja, Some(1)), CacheLookup::Fresh(_)));
+        assert_eq!(CacheLookup::Miss, cache.lookup(&en, Some(1)));
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = HttpCache::with_max_size(10);
+        let headers = "Cache-Control: max-age=60\r\n";
+        cache.store(&request("/a"), response(headers, "aaaa"), Some(0));
+        cache.store(&request("/b"), response(headers, "bbbb"), Some(0));
+        // /aを使うと、/bの方が長く使われていないことになる
+        cache.lookup(&request("/a"), Some(0));
+        cache.store(&request("/c"), response(headers, "cccc"), Some(0));
+        assert_eq!(
+            ["http://example.com:80/a", "http://example.com:80/c"].to_vec(),
+            cache.urls()
+        );
+        assert_eq!(8, cache.size());
+
+        // 上限より大きいレスポンスは保存しない
+        cache.store(&request("/d"), response(headers, "ddddddddddd"), Some(0));
+        assert_eq!(2, cache.len());
+    }
+}
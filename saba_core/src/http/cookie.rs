@@ -0,0 +1,690 @@
+// Cookieの解釈と保存
+// https://datatracker.ietf.org/doc/html/rfc6265
+
+use super::date::parse_date;
+use crate::clock::Clock;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+
+/// Cookieを設定・参照する主体
+/// HttpOnly属性を持つCookieは、Httpからのみ設定・参照できる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieSource {
+    /// HTTPのSet-Cookie, Cookieヘッダー
+    Http,
+    /// JavaScriptのdocument.cookie
+    Script,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    // 小文字に揃えたドメイン
+    domain: String,
+    path: String,
+    // 有効期限(UNIXエポックからの秒数)。Noneの場合はブラウザを閉じるまで有効
+    expires: Option<u64>,
+    secure: bool,
+    http_only: bool,
+    // Domain属性がない場合はtrueになり、設定したホストにのみ送る
+    host_only: bool,
+    // 同じパスの長さのCookieを、作成された順に並べるために使用する
+    creation: u64,
+}
+
+impl Cookie {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+
+    pub fn domain(&self) -> String {
+        self.domain.clone()
+    }
+
+    pub fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    pub fn expires(&self) -> Option<u64> {
+        self.expires
+    }
+
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    pub fn host_only(&self) -> bool {
+        self.host_only
+    }
+
+    fn is_expired(&self, now: Option<u64>) -> bool {
+        match (self.expires, now) {
+            (Some(expires), Some(now)) => expires <= now,
+            _ => false,
+        }
+    }
+}
+
+/// Set-Cookieヘッダーを解釈した結果。保存する前の状態
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SetCookie {
+    name: String,
+    value: String,
+    domain: Option<String>,
+    path: Option<String>,
+    expires: Option<u64>,
+    // Max-Ageの秒数。負の値は即座に期限切れになることを表す
+    max_age: Option<i64>,
+    secure: bool,
+    http_only: bool,
+}
+
+/// Cookieを保存する入れ物。HttpClientとページ(document.cookie)の間で共有して使う。
+/// 時刻を取得するClockが設定されていない場合は、有効期限による削除は行わず、
+/// Max-Ageが0以下のCookieによる削除のみを行う。
+#[derive(Clone, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+    clock: Option<Rc<dyn Clock>>,
+    next_creation: u64,
+}
+
+impl Debug for CookieJar {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        f.debug_struct("CookieJar").field("cookies", &self.cookies).finish()
+    }
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self { cookies: Vec::new(), clock: None, next_creation: 0 }
+    }
+
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = Some(clock);
+    }
+
+    fn now(&self) -> Option<u64> {
+        self.clock.as_ref().map(|clock| clock.now())
+    }
+
+    /// 有効期限が切れていないCookieを、保存された順に返す
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let now = self.now();
+        self.cookies.iter().filter(|c| !c.is_expired(now)).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+
+    /// hostのpathへのレスポンスに含まれていたSet-Cookieの値(document.cookieへの代入の場合は代入された文字列)を保存する
+    /// 保存した場合はtrueを返す。
+    /// https://datatracker.ietf.org/doc/html/rfc6265#section-5.3
+    pub fn store(
+        &mut self,
+        set_cookie: &str,
+        host: &str,
+        path: &str,
+        source: CookieSource,
+    ) -> bool {
+        let parsed = match parse_set_cookie(set_cookie) {
+            Some(parsed) => parsed,
+            None => return false,
+        };
+        let host = host.to_ascii_lowercase();
+        let now = self.now();
+
+        // Domain属性がある場合は、リクエストのホストと同じでも、そのドメインとサブドメインに送る。
+        // 他のドメインのCookieは設定できない
+        // "com"のような公開サフィックスは、ホストと同じ場合だけhost-onlyのCookieとして受け付ける
+        let (domain, host_only) = match parsed.domain {
+            Some(domain) if is_public_suffix(&domain) => {
+                if domain != host {
+                    return false;
+                }
+                (host.clone(), true)
+            }
+            Some(domain) => {
+                if !domain_match(&host, &domain) {
+                    return false;
+                }
+                (domain, false)
+            }
+            None => (host.clone(), true),
+        };
+
+        let path = match parsed.path {
+            Some(path) => path,
+            None => default_path(path),
+        };
+
+        if parsed.http_only && source == CookieSource::Script {
+            return false;
+        }
+
+        // Max-AgeはExpiresよりも優先される
+        let expires = match parsed.max_age {
+            Some(max_age) if max_age <= 0 => Some(0),
+            Some(max_age) => now.map(|now| now.saturating_add(max_age as u64)),
+            None => parsed.expires,
+        };
+
+        // 同じ名前・ドメイン・パスのCookieは置き換える。HttpOnlyのCookieはスクリプトから上書きできない
+        let existing = self.cookies.iter().position(|c| {
+            c.name == parsed.name
+                && c.domain == domain
+                && c.host_only == host_only
+                && c.path == path
+        });
+        let creation = match existing {
+            Some(index) => {
+                if self.cookies[index].http_only
+                    && source == CookieSource::Script
+                {
+                    return false;
+                }
+                self.cookies.remove(index).creation
+            }
+            None => {
+                self.next_creation += 1;
+                self.next_creation
+            }
+        };
+
+        let cookie = Cookie {
+            name: parsed.name,
+            value: parsed.value,
+            domain,
+            path,
+            expires,
+            secure: parsed.secure,
+            http_only: parsed.http_only,
+            host_only,
+            creation,
+        };
+
+        // 有効期限が過去のCookieは、既存のCookieを削除するためだけに使われる
+        if cookie.expires == Some(0) || cookie.is_expired(now) {
+            return false;
+        }
+        self.cookies.push(cookie);
+        true
+    }
+
+    /// hostのpathに送るCookieヘッダーの値を返す。送るCookieがない場合はNoneを返す
+    /// secure: HTTPSなどの安全な通信路かどうか
+    /// https://datatracker.ietf.org/doc/html/rfc6265#section-5.4
+    pub fn cookie_header(
+        &self,
+        host: &str,
+        path: &str,
+        secure: bool,
+        source: CookieSource,
+    ) -> Option<String> {
+        let host = host.to_ascii_lowercase();
+        let path = match path.find('?') {
+            Some(index) => &path[..index],
+            None => path,
+        };
+        let now = self.now();
+
+        let mut cookies: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|c| {
+                let domain_ok = if c.host_only {
+                    c.domain == host
+                } else {
+                    domain_match(&host, &c.domain)
+                };
+                domain_ok
+                    && path_match(path, &c.path)
+                    && (secure || !c.secure)
+                    && (source == CookieSource::Http || !c.http_only)
+                    && !c.is_expired(now)
+            })
+            .collect();
+        if cookies.is_empty() {
+            return None;
+        }
+
+        // パスが長いものを先に、同じ長さの場合は古いものを先に並べる
+        cookies.sort_by(|a, b| {
+            b.path.len().cmp(&a.path.len()).then(a.creation.cmp(&b.creation))
+        });
+
+        let pairs: Vec<String> =
+            cookies.iter().map(|c| format!("{}={}", c.name, c.value)).collect();
+        Some(pairs.join("; "))
+    }
+}
+
+// Set-Cookieの値を解釈する。Cookieとして扱えない場合はNoneを返す
+// https://datatracker.ietf.org/doc/html/rfc6265#section-5.2
+fn parse_set_cookie(set_cookie: &str) -> Option<SetCookie> {
+    let mut parts = set_cookie.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = SetCookie {
+        name: name.to_string(),
+        value: value.trim().to_string(),
+        domain: None,
+        path: None,
+        expires: None,
+        max_age: None,
+        secure: false,
+        http_only: false,
+    };
+
+    for attribute in parts {
+        let (name, value) = match attribute.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => (attribute.trim(), ""),
+        };
+
+        // 解釈できない属性や値は無視する
+        if name.eq_ignore_ascii_case("expires") {
+            if let Some(expires) = parse_date(value) {
+                cookie.expires = Some(expires);
+            }
+        } else if name.eq_ignore_ascii_case("max-age") {
+            let digits = value.strip_prefix('-').unwrap_or(value);
+            if !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit())
+            {
+                // 桁が多すぎる値は、最大値・最小値として扱う
+                cookie.max_age = Some(value.parse::<i64>().unwrap_or(
+                    if value.starts_with('-') { i64::MIN } else { i64::MAX },
+                ));
+            }
+        } else if name.eq_ignore_ascii_case("domain") {
+            let domain = value.strip_prefix('.').unwrap_or(value);
+            if !domain.is_empty() {
+                cookie.domain = Some(domain.to_ascii_lowercase());
+            }
+        } else if name.eq_ignore_ascii_case("path") {
+            if value.starts_with('/') {
+                cookie.path = Some(value.to_string());
+            } else {
+                cookie.path = None;
+            }
+        } else if name.eq_ignore_ascii_case("secure") {
+            cookie.secure = true;
+        } else if name.eq_ignore_ascii_case("httponly") {
+            cookie.http_only = true;
+        }
+    }
+
+    Some(cookie)
+}
+
+// hostがdomainと一致するか、domainのサブドメインかどうか
+// https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.3
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    // IPアドレスにはサブドメインがない
+    let is_ip_address = host.bytes().all(|c| c.is_ascii_digit() || c == b'.');
+    !is_ip_address
+        && host.len() > domain.len()
+        && host.ends_with(domain)
+        && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+}
+
+// 誰でもサブドメインを登録できるドメインかどうか
+// 公開サフィックスリストの全体は持たず、1つのラベルだけのドメインと、よく使われるものだけを扱う
+// https://publicsuffix.org/
+fn is_public_suffix(domain: &str) -> bool {
+    const SUFFIXES: [&str; 16] = [
+        "co.jp", "ne.jp", "or.jp", "ac.jp", "go.jp", "co.uk", "org.uk",
+        "ac.uk", "gov.uk", "com.au", "net.au", "org.au", "co.nz", "com.cn",
+        "com.br", "co.kr",
+    ];
+    !domain.contains('.') || SUFFIXES.contains(&domain)
+}
+
+// リクエストのパスが、Cookieのパスの下にあるかどうか
+// https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.4
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    if request_path == cookie_path {
+        return true;
+    }
+    request_path.starts_with(cookie_path)
+        && (cookie_path.ends_with('/')
+            || request_path.as_bytes()[cookie_path.len()] == b'/')
+}
+
+// Path属性がない場合のパス。リクエストのパスの最後の"/"より前の部分になる
+// https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.4
+fn default_path(request_path: &str) -> String {
+    let path = match request_path.find('?') {
+        Some(index) => &request_path[..index],
+        None => request_path,
+    };
+    if !path.starts_with('/') {
+        return "/".to_string();
+    }
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => path[..index].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn jar_with_clock(now: u64) -> (CookieJar, ManualClock) {
+        let clock = ManualClock::new(now);
+        let mut jar = CookieJar::new();
+        jar.set_clock(Rc::new(clock.clone()));
+        (jar, clock)
+    }
+
+    #[test]
+    fn test_parse_set_cookie() {
+        let cookie = parse_set_cookie(
+            "SID=31d4d96e407aad42; Path=/; Domain=.Example.com; Secure; HttpOnly; Unknown=1",
+        )
+        .expect("failed to parse set-cookie");
+        assert_eq!("SID", cookie.name);
+        assert_eq!("31d4d96e407aad42", cookie.value);
+        assert_eq!(Some("example.com".to_string()), cookie.domain);
+        assert_eq!(Some("/".to_string()), cookie.path);
+        assert!(cookie.secure);
+        assert!(cookie.http_only);
+
+        assert_eq!(None, parse_set_cookie("novalue"));
+        assert_eq!(None, parse_set_cookie("=value"));
+        assert_eq!(
+            Some(-1),
+            parse_set_cookie("a=b; Max-Age=-1").and_then(|c| c.max_age)
+        );
+        assert_eq!(
+            None,
+            parse_set_cookie("a=b; Max-Age=1x").and_then(|c| c.max_age)
+        );
+        // ASCII以外の文字を含む日付は無視する
+        assert_eq!(
+            None,
+            parse_set_cookie("a=b; Expires=x\u{fffd}\u{fffd}, 21 2015")
+                .and_then(|c| c.expires)
+        );
+    }
+
+    #[test]
+    fn test_domain_and_path_match() {
+        assert!(domain_match("example.com", "example.com"));
+        assert!(domain_match("www.example.com", "example.com"));
+        assert!(!domain_match("wwwexample.com", "example.com"));
+        assert!(!domain_match("example.com", "www.example.com"));
+        assert!(!domain_match("1.2.3.4", "2.3.4"));
+
+        assert!(path_match("/", "/"));
+        assert!(path_match("/a/b", "/a"));
+        assert!(path_match("/a/b", "/a/"));
+        assert!(!path_match("/ab", "/a"));
+        assert!(!path_match("/", "/a"));
+
+        assert_eq!("/", default_path(""));
+        assert_eq!("/", default_path("/index.html"));
+        assert_eq!("/a/b", default_path("/a/b/c?d=/e"));
+    }
+
+    #[test]
+    fn test_store_and_send() {
+        let mut jar = CookieJar::new();
+        assert!(jar.store("a=1", "example.com", "/", CookieSource::Http));
+        assert!(jar.store(
+            "b=2; Domain=example.com; Path=/docs",
+            "www.example.com",
+            "/",
+            CookieSource::Http
+        ));
+        assert!(!jar.store(
+            "c=3; Domain=other.com",
+            "example.com",
+            "/",
+            CookieSource::Http
+        ));
+
+        assert_eq!(
+            Some("a=1".to_string()),
+            jar.cookie_header(
+                "example.com",
+                "/index.html",
+                false,
+                CookieSource::Http
+            )
+        );
+        // host-onlyのCookieはサブドメインには送らない
+        assert_eq!(
+            Some("b=2".to_string()),
+            jar.cookie_header(
+                "sub.example.com",
+                "/docs/a?b",
+                false,
+                CookieSource::Http
+            )
+        );
+        // パスが長いものから順に並ぶ
+        assert_eq!(
+            Some("b=2; a=1".to_string()),
+            jar.cookie_header(
+                "example.com",
+                "/docs",
+                false,
+                CookieSource::Http
+            )
+        );
+        assert_eq!(
+            None,
+            jar.cookie_header("example.org", "/", false, CookieSource::Http)
+        );
+    }
+
+    #[test]
+    fn test_domain_same_as_host() {
+        let mut jar = CookieJar::new();
+        // リクエストのホストと同じDomain属性でも、host-onlyにはならない
+        assert!(jar.store(
+            "a=1; Domain=example.com",
+            "example.com",
+            "/",
+            CookieSource::Http
+        ));
+        assert_eq!(
+            Some("a=1".to_string()),
+            jar.cookie_header(
+                "sub.example.com",
+                "/",
+                false,
+                CookieSource::Http
+            )
+        );
+        assert_eq!(
+            Some("a=1".to_string()),
+            jar.cookie_header("example.com", "/", false, CookieSource::Http)
+        );
+    }
+
+    #[test]
+    fn test_public_suffix_domain() {
+        let mut jar = CookieJar::new();
+        // 公開サフィックスのCookieは、他のサイトに送られてしまうため保存しない
+        assert!(!jar.store(
+            "sid=1; Domain=com",
+            "example.com",
+            "/",
+            CookieSource::Http
+        ));
+        assert!(!jar.store(
+            "sid=2; Domain=co.jp",
+            "example.co.jp",
+            "/",
+            CookieSource::Http
+        ));
+        assert_eq!(
+            None,
+            jar.cookie_header("evil.com", "/", false, CookieSource::Http)
+        );
+        assert_eq!(
+            None,
+            jar.cookie_header("evil.co.jp", "/", false, CookieSource::Http)
+        );
+
+        // ホストと同じ場合は、host-onlyのCookieとして保存する
+        assert!(jar.store(
+            "a=1; Domain=localhost",
+            "localhost",
+            "/",
+            CookieSource::Http
+        ));
+        assert_eq!(
+            Some("a=1".to_string()),
+            jar.cookie_header("localhost", "/", false, CookieSource::Http)
+        );
+        assert_eq!(
+            None,
+            jar.cookie_header("sub.localhost", "/", false, CookieSource::Http)
+        );
+    }
+
+    #[test]
+    fn test_replace_and_delete() {
+        let mut jar = CookieJar::new();
+        jar.store("a=1", "example.com", "/", CookieSource::Http);
+        jar.store("b=2", "example.com", "/", CookieSource::Http);
+        jar.store("a=3", "example.com", "/", CookieSource::Http);
+        assert_eq!(2, jar.len());
+        // 置き換えても作成された順番は変わらない
+        assert_eq!(
+            Some("a=3; b=2".to_string()),
+            jar.cookie_header("example.com", "/", false, CookieSource::Http)
+        );
+
+        assert!(!jar.store(
+            "a=; Max-Age=0",
+            "example.com",
+            "/",
+            CookieSource::Http
+        ));
+        assert_eq!(
+            Some("b=2".to_string()),
+            jar.cookie_header("example.com", "/", false, CookieSource::Http)
+        );
+    }
+
+    #[test]
+    fn test_expiry() {
+        let (mut jar, clock) = jar_with_clock(1445412000);
+        jar.store("a=1; Max-Age=60", "example.com", "/", CookieSource::Http);
+        jar.store(
+            "b=2; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+            "example.com",
+            "/",
+            CookieSource::Http,
+        );
+        // Max-AgeはExpiresより優先される
+        jar.store(
+            "c=3; Max-Age=1000; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+            "example.com",
+            "/",
+            CookieSource::Http,
+        );
+        assert_eq!(3, jar.len());
+
+        clock.advance(60);
+        assert_eq!(
+            Some("b=2; c=3".to_string()),
+            jar.cookie_header("example.com", "/", false, CookieSource::Http)
+        );
+        clock.advance(420);
+        assert_eq!(
+            Some("c=3".to_string()),
+            jar.cookie_header("example.com", "/", false, CookieSource::Http)
+        );
+
+        // 有効期限が過去のCookieで削除する
+        jar.store(
+            "c=; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+            "example.com",
+            "/",
+            CookieSource::Http,
+        );
+        assert!(jar.is_empty());
+    }
+
+    #[test]
+    fn test_secure_and_http_only() {
+        let mut jar = CookieJar::new();
+        jar.store("s=1; Secure", "example.com", "/", CookieSource::Http);
+        jar.store("h=2; HttpOnly", "example.com", "/", CookieSource::Http);
+        jar.store("p=3", "example.com", "/", CookieSource::Http);
+
+        assert_eq!(
+            Some("h=2; p=3".to_string()),
+            jar.cookie_header("example.com", "/", false, CookieSource::Http)
+        );
+        assert_eq!(
+            Some("s=1; h=2; p=3".to_string()),
+            jar.cookie_header("example.com", "/", true, CookieSource::Http)
+        );
+        assert_eq!(
+            Some("p=3".to_string()),
+            jar.cookie_header("example.com", "/", false, CookieSource::Script)
+        );
+
+        // スクリプトからはHttpOnlyのCookieを設定・上書きできない
+        assert!(!jar.store(
+            "x=1; HttpOnly",
+            "example.com",
+            "/",
+            CookieSource::Script
+        ));
+        assert!(!jar.store(
+            "h=changed",
+            "example.com",
+            "/",
+            CookieSource::Script
+        ));
+        assert!(jar.store(
+            "p=changed",
+            "example.com",
+            "/",
+            CookieSource::Script
+        ));
+        assert_eq!(
+            Some("h=2; p=changed".to_string()),
+            jar.cookie_header("example.com", "/", false, CookieSource::Http)
+        );
+    }
+}
@@ -0,0 +1,168 @@
+// HTTPで使われる日付の解釈
+// https://datatracker.ietf.org/doc/html/rfc9110#section-5.6.7
+
+/// 日付を解釈し、UNIXエポックからの秒数を返す
+/// "Wed, 21 Oct 2015 07:28:00 GMT"のような形式のほか、区切り文字や順番が異なるものも受け付ける。
+/// CookieのExpires属性と、HTTPのDate, Expires, Last-Modifiedヘッダーの解釈に使用する。
+/// https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.1
+pub fn parse_date(date: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct",
+        "nov", "dec",
+    ];
+
+    let is_delimiter = |c: char| {
+        c == '\t'
+            || (' '..='/').contains(&c)
+            || (';'..='@').contains(&c)
+            || ('['..='`').contains(&c)
+            || ('{'..='~').contains(&c)
+    };
+
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+
+    for token in date.split(is_delimiter).filter(|t| !t.is_empty()) {
+        if time.is_none() {
+            if let Some(t) = parse_time(token) {
+                time = Some(t);
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some(d) = leading_digits(token, 1, 2) {
+                day = Some(d);
+                continue;
+            }
+        }
+        if month.is_none() {
+            // 先頭の3バイトが文字の境界で終わらないトークンは月ではない
+            let prefix = token.get(..3).map(|p| p.to_ascii_lowercase());
+            if let Some(m) =
+                MONTHS.iter().position(|m| Some(*m) == prefix.as_deref())
+            {
+                month = Some(m as u64 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some(y) = leading_digits(token, 2, 4) {
+                year = Some(y);
+                continue;
+            }
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let day = day?;
+    let month = month?;
+    let year = match year? {
+        y @ 70..=99 => y + 1900,
+        y @ 0..=69 => y + 2000,
+        y => y,
+    };
+
+    if year < 1601
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    // 1970年より前の日付は、すでに期限切れとして扱う
+    let days = days_from_epoch(year, month, day);
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+// "hh:mm:ss"を解釈する。各部分は1桁か2桁の数字
+fn parse_time(token: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = token.splitn(3, ':');
+    let hour = parts.next()?;
+    let minute = parts.next()?;
+    let second = parts.next()?;
+
+    let is_digits = |s: &str| {
+        (1..=2).contains(&s.len()) && s.bytes().all(|c| c.is_ascii_digit())
+    };
+    if !is_digits(hour) || !is_digits(minute) {
+        return None;
+    }
+    // 秒の後には、数字以外の文字が続いてもよい
+    let second = leading_digits(second, 1, 2)?;
+    Some((hour.parse().ok()?, minute.parse().ok()?, second))
+}
+
+// 先頭のmin-max桁の数字を読む。その後に数字以外の文字が続いてもよい
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<u64> {
+    let length = token.bytes().take_while(|c| c.is_ascii_digit()).count();
+    if length < min || length > max {
+        return None;
+    }
+    token[..length].parse().ok()
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// 1970年1月1日からの日数。1970年より前の場合は0を返す
+fn days_from_epoch(year: u64, month: u64, day: u64) -> u64 {
+    // 3月始まりの年として数えると、うるう日が年の最後になり計算が簡単になる
+    let (y, m) =
+        if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = y / 400;
+    let year_of_era = y % 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era;
+    // 0000-03-01から1970-01-01までの日数
+    days.saturating_sub(719468)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            Some(1445412480),
+            parse_date("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        // RFC 850形式とasctime形式
+        assert_eq!(
+            Some(1445412480),
+            parse_date("Wednesday, 21-Oct-15 07:28:00 GMT")
+        );
+        assert_eq!(
+            Some(1445412480),
+            parse_date("Wed Oct 21 07:28:00 2015")
+        );
+        assert_eq!(Some(951782400), parse_date("29 Feb 2000 00:00:00"));
+        assert_eq!(Some(0), parse_date("Thu, 01 Jan 1970 00:00:00 GMT"));
+        assert_eq!(Some(0), parse_date("Sat, 01 Jan 1601 00:00:00 GMT"));
+
+        assert_eq!(None, parse_date("29 Feb 2001 00:00:00"));
+        assert_eq!(None, parse_date("21 Oct 2015"));
+        assert_eq!(None, parse_date("21 Oct 2015 24:00:00"));
+        assert_eq!(None, parse_date("21 Foo 2015 07:28:00"));
+        // ASCII以外の文字を含むトークンでもパニックしない
+        assert_eq!(None, parse_date("x\u{fffd}\u{fffd} 2015 07:28:00"));
+        assert_eq!(None, parse_date("21 O\u{fffd} 2015 07:28:00"));
+        assert_eq!(
+            Some(1445412480),
+            parse_date("\u{fffd}, 21 Oct 2015 07:28:00 GMT")
+        );
+    }
+}
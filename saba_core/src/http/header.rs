@@ -0,0 +1,308 @@
+// HTTPヘッダーの保持と解釈
+// https://datatracker.ietf.org/doc/html/rfc9110#section-5
+
+use crate::error::Error;
+use crate::error::HttpError;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    name: String,
+    value: String,
+}
+
+impl Header {
+    pub fn new(name: String, value: String) -> Self {
+        Self { name, value }
+    }
+
+    /// "Name: value"の形式の1行を解釈する。コロンがない行や、名前が不正な行はエラーになる。
+    /// https://datatracker.ietf.org/doc/html/rfc9112#section-5
+    pub fn parse(line: &str) -> Result<Self, Error> {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name, value),
+            None => {
+                return Err(Error::Http(HttpError::InvalidHeader(
+                    line.to_string(),
+                )))
+            }
+        };
+
+        // 名前は空白を含まないトークンでなければならない。"Name : value"のような行も受け付けない
+        if name.is_empty() || !name.chars().all(is_token_char) {
+            return Err(Error::Http(HttpError::InvalidHeader(
+                line.to_string(),
+            )));
+        }
+
+        Ok(Self::new(name.to_string(), value.trim().to_string()))
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc9110#section-5.6.2
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+/// ヘッダーの集まり。名前は大文字・小文字を区別せずに扱い、同じ名前のヘッダーを複数保持できる。
+/// 受信した順番は保たれる。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeaderMap {
+    headers: Vec<Header>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self { headers: Vec::new() }
+    }
+
+    /// ヘッダーを追加する。同じ名前のヘッダーがあっても置き換えない。
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.headers.push(Header::new(name.to_string(), value.to_string()));
+    }
+
+    /// ヘッダーを設定する。同じ名前のヘッダーがある場合は、すべて置き換える。
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.remove(name);
+        self.append(name, value);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.headers.retain(|h| !h.name.eq_ignore_ascii_case(name));
+    }
+
+    /// nameという名前の最初のヘッダーの値を返す
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.clone())
+    }
+
+    /// nameという名前のヘッダーの値を、すべて受信した順に返す
+    pub fn get_all(&self, name: &str) -> Vec<String> {
+        self.headers
+            .iter()
+            .filter(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.clone())
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.headers.iter().any(|h| h.name.eq_ignore_ascii_case(name))
+    }
+
+    /// カンマ区切りの値を持つヘッダー(Connection, Transfer-Encodingなど)に、tokenが含まれるかどうか
+    pub fn has_token(&self, name: &str, token: &str) -> bool {
+        self.get_all(name).iter().any(|value| {
+            value.split(',').any(|v| v.trim().eq_ignore_ascii_case(token))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, Header> {
+        self.headers.iter()
+    }
+
+    pub fn content_type(&self) -> Option<ContentType> {
+        self.get("Content-Type").map(|value| ContentType::parse(&value))
+    }
+
+    /// Content-Lengthの値。数値でない場合や、複数の異なる値がある場合はエラーになる。
+    /// https://datatracker.ietf.org/doc/html/rfc9110#section-8.6
+    pub fn content_length(&self) -> Result<Option<usize>, Error> {
+        let mut length = None;
+        for value in self.get_all("Content-Length") {
+            // "Content-Length: 10, 10"のように同じ値が並んでいる場合は受け付ける
+            for v in value.split(',') {
+                let v = match v.trim().parse::<usize>() {
+                    Ok(v) => v,
+                    Err(_) => {
+                        return Err(Error::Http(
+                            HttpError::InvalidContentLength(value.clone()),
+                        ))
+                    }
+                };
+                match length {
+                    Some(l) if l != v => {
+                        return Err(Error::Http(
+                            HttpError::InvalidContentLength(value.clone()),
+                        ))
+                    }
+                    _ => length = Some(v),
+                }
+            }
+        }
+        Ok(length)
+    }
+
+    pub fn location(&self) -> Option<String> {
+        self.get("Location")
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = &'a Header;
+    type IntoIter = core::slice::Iter<'a, Header>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.headers.iter()
+    }
+}
+
+/// Content-Typeの値
+/// https://datatracker.ietf.org/doc/html/rfc9110#section-8.3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    // 小文字に揃えたメディアタイプ(example: "text/html")
+    mime_type: String,
+    charset: Option<String>,
+}
+
+impl ContentType {
+    pub fn parse(value: &str) -> Self {
+        let mut params = value.split(';');
+        let mime_type = params.next().unwrap_or("").trim().to_ascii_lowercase();
+
+        let mut charset = None;
+        for param in params {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("charset") {
+                    let value =
+                        value.trim().trim_matches('"').trim_matches('\'');
+                    charset = Some(value.to_string());
+                }
+            }
+        }
+
+        Self { mime_type, charset }
+    }
+
+    pub fn mime_type(&self) -> String {
+        self.mime_type.clone()
+    }
+
+    pub fn charset(&self) -> Option<String> {
+        self.charset.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let header = Header::parse("Content-Type:  text/html ")
+            .expect("failed to parse");
+        assert_eq!("Content-Type", header.name());
+        assert_eq!("text/html", header.value());
+
+        // 値にはコロンを含めることができる
+        let header = Header::parse("Location: http://example.com:8000/")
+            .expect("failed to parse");
+        assert_eq!("http://example.com:8000/", header.value());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(
+            Err(Error::Http(HttpError::InvalidHeader("invalid".to_string()))),
+            Header::parse("invalid")
+        );
+        assert!(Header::parse(": value").is_err());
+        assert!(Header::parse("Content-Type : text/html").is_err());
+        assert!(Header::parse(" folded: value").is_err());
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let mut headers = HeaderMap::new();
+        headers.append("location", "/a");
+        assert_eq!(Some("/a".to_string()), headers.get("Location"));
+        assert_eq!(Some("/a".to_string()), headers.location());
+        assert!(headers.contains("LOCATION"));
+        assert_eq!(None, headers.get("Content-Type"));
+    }
+
+    #[test]
+    fn test_multiple_values() {
+        let mut headers = HeaderMap::new();
+        headers.append("Set-Cookie", "a=1");
+        headers.append("Date", "xx");
+        headers.append("set-cookie", "b=2");
+        assert_eq!(3, headers.len());
+        assert_eq!(Some("a=1".to_string()), headers.get("Set-Cookie"));
+        assert_eq!(
+            ["a=1".to_string(), "b=2".to_string()].to_vec(),
+            headers.get_all("Set-Cookie")
+        );
+
+        headers.set("SET-COOKIE", "c=3");
+        assert_eq!(["c=3".to_string()].to_vec(), headers.get_all("set-cookie"));
+        assert_eq!(2, headers.len());
+
+        headers.remove("date");
+        assert_eq!(1, headers.len());
+    }
+
+    #[test]
+    fn test_has_token() {
+        let mut headers = HeaderMap::new();
+        headers.append("Transfer-Encoding", "gzip");
+        headers.append("Transfer-Encoding", "Chunked");
+        assert!(headers.has_token("transfer-encoding", "chunked"));
+        assert!(!headers.has_token("Connection", "close"));
+    }
+
+    #[test]
+    fn test_content_length() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Ok(None), headers.content_length());
+
+        headers.append("Content-Length", "10");
+        headers.append("content-length", "10, 10");
+        assert_eq!(Ok(Some(10)), headers.content_length());
+
+        headers.append("Content-Length", "11");
+        assert_eq!(
+            Err(Error::Http(HttpError::InvalidContentLength("11".to_string()))),
+            headers.content_length()
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.append("Content-Length", "-1");
+        assert!(headers.content_length().is_err());
+    }
+
+    #[test]
+    fn test_content_type() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(None, headers.content_type());
+
+        headers.append("Content-Type", "Text/HTML; Charset=\"Shift_JIS\"");
+        let content_type =
+            headers.content_type().expect("failed to get content type");
+        assert_eq!("text/html", content_type.mime_type());
+        assert_eq!(Some("Shift_JIS".to_string()), content_type.charset());
+
+        assert_eq!(None, ContentType::parse("image/png").charset());
+    }
+}
@@ -0,0 +1,553 @@
+// Content-Encoding: gzip / deflateで圧縮されたボディを復元する
+// DEFLATE: https://datatracker.ietf.org/doc/html/rfc1951
+// zlib: https://datatracker.ietf.org/doc/html/rfc1950
+// gzip: https://datatracker.ietf.org/doc/html/rfc1952
+
+use super::header::HeaderMap;
+use crate::error::Error;
+use crate::error::HttpError;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// ハフマン符号の最大のビット長
+const MAX_BITS: usize = 15;
+
+// 長さ符号(257..285)に対する、長さの基本値と追加ビット数
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59,
+    67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5,
+    5, 5, 5, 0,
+];
+
+// 距離符号(0..29)に対する、距離の基本値と追加ビット数
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513,
+    769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10,
+    11, 11, 12, 12, 13, 13,
+];
+
+// 動的ハフマンブロックで、符号長の符号長が並ぶ順番
+const CODE_LENGTH_ORDER: [usize; 19] =
+    [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn error(message: &str) -> Error {
+    Error::Http(HttpError::InvalidContentEncoding(message.to_string()))
+}
+
+// バイト列を、下位ビットから順に読み出す
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0, bit_buf: 0, bit_count: 0 }
+    }
+
+    fn bits(&mut self, need: u32) -> Result<u32, Error> {
+        let mut value = self.bit_buf;
+        while self.bit_count < need {
+            if self.pos >= self.data.len() {
+                return Err(error("unexpected end of deflate stream"));
+            }
+            value |= (self.data[self.pos] as u32) << self.bit_count;
+            self.pos += 1;
+            self.bit_count += 8;
+        }
+        self.bit_buf = value >> need;
+        self.bit_count -= need;
+        Ok(value & ((1 << need) - 1))
+    }
+
+    // 残りのビットを捨てて、次のバイトの先頭に進む
+    fn align(&mut self) {
+        self.bit_buf = 0;
+        self.bit_count = 0;
+    }
+}
+
+// 符号長の並びから作るハフマン符号表
+// counts[n]はビット長nの符号の数、symbolsは符号順に並べたシンボル
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, Error> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+
+        // 符号の数が多すぎる場合は、符号を割り当てられない
+        let mut left: i32 = 1;
+        for count in counts.iter().skip(1) {
+            left <<= 1;
+            left -= *count as i32;
+            if left < 0 {
+                return Err(error("over-subscribed huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    // 1ビットずつ読みながら、同じ長さの符号の範囲に入るかを調べる
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, Error> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(error("invalid huffman code"))
+    }
+}
+
+/// DEFLATE形式(ヘッダーなし)のデータを復元する
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, Error> {
+    inflate_with_length(data).map(|(out, _)| out)
+}
+
+// 復元したデータと、読み込んだバイト数を返す
+fn inflate_with_length(data: &[u8]) -> Result<(Vec<u8>, usize), Error> {
+    let mut reader = BitReader::new(data);
+    let mut out = Vec::new();
+
+    loop {
+        let last = reader.bits(1)?;
+        match reader.bits(2)? {
+            0 => stored_block(&mut reader, &mut out)?,
+            1 => {
+                let (literal, distance) = fixed_tables()?;
+                codes(&mut reader, &mut out, &literal, &distance)?;
+            }
+            2 => {
+                let (literal, distance) = dynamic_tables(&mut reader)?;
+                codes(&mut reader, &mut out, &literal, &distance)?;
+            }
+            _ => return Err(error("invalid deflate block type")),
+        }
+        if last == 1 {
+            break;
+        }
+    }
+
+    Ok((out, reader.pos))
+}
+
+// 無圧縮ブロック
+fn stored_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    reader.align();
+    let pos = reader.pos;
+    if reader.data.len() < pos + 4 {
+        return Err(error("unexpected end of stored block"));
+    }
+    let length = u16::from_le_bytes([reader.data[pos], reader.data[pos + 1]]);
+    let nlength =
+        u16::from_le_bytes([reader.data[pos + 2], reader.data[pos + 3]]);
+    if length != !nlength {
+        return Err(error("stored block length mismatch"));
+    }
+
+    let start = pos + 4;
+    let end = start + length as usize;
+    if reader.data.len() < end {
+        return Err(error("unexpected end of stored block"));
+    }
+    out.extend_from_slice(&reader.data[start..end]);
+    reader.pos = end;
+    Ok(())
+}
+
+// 固定ハフマン符号の符号表
+fn fixed_tables() -> Result<(Huffman, Huffman), Error> {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?))
+}
+
+// 動的ハフマン符号の符号表を、ブロックの先頭から読み込む
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), Error> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(error("too many length or distance codes"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_table = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length_table.decode(reader)?;
+        if symbol < 16 {
+            lengths[index] = symbol as u8;
+            index += 1;
+            continue;
+        }
+
+        let (length, repeat) = match symbol {
+            // 直前の符号長を3-6回繰り返す
+            16 => {
+                if index == 0 {
+                    return Err(error("repeat with no previous length"));
+                }
+                (lengths[index - 1], 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if index + repeat > lengths.len() {
+            return Err(error("too many code lengths"));
+        }
+        for _ in 0..repeat {
+            lengths[index] = length;
+            index += 1;
+        }
+    }
+
+    // ブロックの終わりを表す符号(256)がなければならない
+    if lengths[256] == 0 {
+        return Err(error("missing end-of-block code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+// リテラルと(長さ, 距離)の組を、ブロックの終わりまで復元する
+fn codes(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    literal: &Huffman,
+    distance: &Huffman,
+) -> Result<(), Error> {
+    loop {
+        let symbol = literal.decode(reader)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err(error("invalid length code"));
+        }
+        let length = LENGTH_BASE[symbol] as usize
+            + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+        let symbol = distance.decode(reader)? as usize;
+        if symbol >= DISTANCE_BASE.len() {
+            return Err(error("invalid distance code"));
+        }
+        let dist = DISTANCE_BASE[symbol] as usize
+            + reader.bits(DISTANCE_EXTRA[symbol] as u32)? as usize;
+        if dist > out.len() {
+            return Err(error("distance too far back"));
+        }
+
+        // 距離より長い長さの場合は、コピーしたデータをさらにコピーする
+        let start = out.len() - dist;
+        for i in 0..length {
+            out.push(out[start + i]);
+        }
+    }
+}
+
+/// zlib形式のデータを復元する
+pub fn zlib_decode(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if !is_zlib_header(data) {
+        return Err(error("invalid zlib header"));
+    }
+    // プリセット辞書には対応しない
+    if data[1] & 0x20 != 0 {
+        return Err(error("zlib preset dictionary is not supported"));
+    }
+
+    let (out, length) = inflate_with_length(&data[2..])?;
+    let trailer = &data[2 + length..];
+    if trailer.len() < 4 {
+        return Err(error("missing adler-32 checksum"));
+    }
+    let expected =
+        u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    if adler32(&out) != expected {
+        return Err(error("adler-32 checksum mismatch"));
+    }
+    Ok(out)
+}
+
+fn is_zlib_header(data: &[u8]) -> bool {
+    data.len() >= 2
+        && data[0] & 0x0f == 8
+        && (data[0] as u16 * 256 + data[1] as u16) % 31 == 0
+}
+
+/// gzip形式のデータを復元する。複数のメンバーが連結されている場合は、すべてを連結して返す
+pub fn gzip_decode(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        pos += gzip_member(&data[pos..], &mut out)?;
+    }
+    Ok(out)
+}
+
+// gzipのメンバーを1つ復元し、読み込んだバイト数を返す
+fn gzip_member(data: &[u8], out: &mut Vec<u8>) -> Result<usize, Error> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if data.len() < 10 || data[0] != 0x1f || data[1] != 0x8b || data[2] != 8 {
+        return Err(error("invalid gzip header"));
+    }
+    let flags = data[3];
+    let mut pos = 10;
+
+    if flags & FEXTRA != 0 {
+        if data.len() < pos + 2 {
+            return Err(error("invalid gzip header"));
+        }
+        pos += 2 + u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
+    }
+    // ファイル名とコメントは0で終わる文字列
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            match data.get(pos..).and_then(|d| d.iter().position(|&c| c == 0)) {
+                Some(end) => pos += end + 1,
+                None => return Err(error("invalid gzip header")),
+            }
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    if data.len() < pos {
+        return Err(error("invalid gzip header"));
+    }
+
+    let (member, length) = inflate_with_length(&data[pos..])?;
+    pos += length;
+
+    if data.len() < pos + 8 {
+        return Err(error("missing gzip trailer"));
+    }
+    let crc = u32::from_le_bytes([
+        data[pos],
+        data[pos + 1],
+        data[pos + 2],
+        data[pos + 3],
+    ]);
+    let size = u32::from_le_bytes([
+        data[pos + 4],
+        data[pos + 5],
+        data[pos + 6],
+        data[pos + 7],
+    ]);
+    if crc32(&member) != crc {
+        return Err(error("crc-32 checksum mismatch"));
+    }
+    if member.len() as u32 != size {
+        return Err(error("gzip size mismatch"));
+    }
+
+    out.extend_from_slice(&member);
+    Ok(pos + 8)
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for &byte in data {
+        a = (a + byte as u32) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffffffff;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// ヘッダーのContent-Encodingに従ってボディを復元する
+/// 複数の符号化が指定されている場合は、後に適用されたものから順に戻す。
+/// 対応していない符号化が含まれる場合は、それ以上は復元せずに返す。
+pub fn decode_content(
+    headers: &HeaderMap,
+    body: Vec<u8>,
+) -> Result<Vec<u8>, Error> {
+    let codings: Vec<String> = headers
+        .get_all("Content-Encoding")
+        .iter()
+        .flat_map(|value| {
+            value
+                .split(',')
+                .map(|v| v.trim().to_ascii_lowercase())
+                .collect::<Vec<String>>()
+        })
+        .collect();
+
+    let mut body = body;
+    for coding in codings.iter().rev() {
+        body = match coding.as_str() {
+            "gzip" | "x-gzip" => gzip_decode(&body)?,
+            // "deflate"はzlib形式だが、ヘッダーなしのDEFLATE形式を送るサーバーもある
+            "deflate" if is_zlib_header(&body) => zlib_decode(&body)?,
+            "deflate" => inflate(&body)?,
+            "identity" | "" => body,
+            _ => return Ok(body),
+        };
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTML: &[u8] = include_bytes!("testdata/index.html");
+
+    #[test]
+    fn test_stored_block() {
+        let data =
+            [0x01, 0x06, 0x00, 0xf9, 0xff, 0x73, 0x74, 0x6f, 0x72, 0x65, 0x64];
+        assert_eq!(Ok(b"stored".to_vec()), inflate(&data));
+    }
+
+    #[test]
+    fn test_fixed_huffman() {
+        // "hello, hello, hello!"を固定ハフマン符号で圧縮したデータ
+        let data = [
+            0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0xc8, 0x40, 0xa2, 0x14,
+            0x01,
+        ];
+        assert_eq!(Ok(b"hello, hello, hello!".to_vec()), inflate(&data));
+    }
+
+    #[test]
+    fn test_dynamic_huffman() {
+        let data = include_bytes!("testdata/index.html.deflate");
+        assert_eq!(Ok(HTML.to_vec()), inflate(data));
+    }
+
+    #[test]
+    fn test_zlib() {
+        let data = include_bytes!("testdata/index.html.zz");
+        assert_eq!(Ok(HTML.to_vec()), zlib_decode(data));
+
+        let mut broken = data.to_vec();
+        let last = broken.len() - 1;
+        broken[last] ^= 0xff;
+        assert!(zlib_decode(&broken).is_err());
+    }
+
+    #[test]
+    fn test_gzip() {
+        let data = include_bytes!("testdata/index.html.gz");
+        assert_eq!(Ok(HTML.to_vec()), gzip_decode(data));
+
+        // 連結された複数のメンバー
+        let mut members = data.to_vec();
+        members.extend_from_slice(data);
+        let mut expected = HTML.to_vec();
+        expected.extend_from_slice(HTML);
+        assert_eq!(Ok(expected), gzip_decode(&members));
+
+        // CRC-32が一致しない
+        let mut broken = data.to_vec();
+        let crc = broken.len() - 8;
+        broken[crc] ^= 0xff;
+        assert!(gzip_decode(&broken).is_err());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let data = include_bytes!("testdata/index.html.gz");
+        assert!(gzip_decode(&data[..data.len() / 2]).is_err());
+        assert!(inflate(&[]).is_err());
+    }
+
+    #[test]
+    fn test_decode_content() {
+        let mut headers = HeaderMap::new();
+        headers.append("Content-Encoding", "gzip");
+        let data = include_bytes!("testdata/index.html.gz").to_vec();
+        assert_eq!(Ok(HTML.to_vec()), decode_content(&headers, data));
+
+        let mut headers = HeaderMap::new();
+        headers.append("Content-Encoding", "Deflate");
+        let data = include_bytes!("testdata/index.html.deflate").to_vec();
+        assert_eq!(Ok(HTML.to_vec()), decode_content(&headers, data));
+
+        // 対応していない符号化はそのまま返す
+        let mut headers = HeaderMap::new();
+        headers.append("Content-Encoding", "br");
+        assert_eq!(
+            Ok(b"br".to_vec()),
+            decode_content(&headers, b"br".to_vec())
+        );
+    }
+}
@@ -0,0 +1,913 @@
+use crate::alloc::string::ToString;
+use crate::charset;
+use crate::clock::Clock;
+use crate::error::Error;
+use crate::transport::Connection;
+use crate::transport::Transport;
+use alloc::format;
+use alloc::string::String;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use cache::CacheLookup;
+use cache::HttpCache;
+use cookie::CookieJar;
+use cookie::CookieSource;
+use core::cell::RefCell;
+use header::HeaderMap;
+use netlog::NetLog;
+use netlog::NetLogEntry;
+use parser::ResponseParser;
+use pool::ConnectionPool;
+use request::HttpRequest;
+use request::Method;
+
+pub mod cache;
+pub mod cookie;
+pub mod date;
+pub mod header;
+pub mod inflate;
+pub mod netlog;
+pub mod parser;
+pub mod pool;
+pub mod redirect;
+pub mod request;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    version: String,
+    status_code: u32,
+    reason: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(raw_response: String) -> Result<Self, Error> {
+        Self::from_bytes(raw_response.as_bytes())
+    }
+
+    /// 受信したバイト列からレスポンスを作成する。ボディはデコードせずにバイト列のまま保持する。
+    pub fn from_bytes(raw_response: &[u8]) -> Result<Self, Error> {
+        let mut parser = ResponseParser::new();
+        parser.feed(raw_response)?;
+        parser.finish()
+    }
+
+    /// Content-Typeとボディだけを持つ200 OKのレスポンスを作成する
+    /// data:やfile:のように、HTTP以外のスキームで読み込んだ内容をレスポンスとして扱う時に使う。
+    pub fn from_body(content_type: &str, body: &[u8]) -> Result<Self, Error> {
+        let mut raw = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            content_type,
+            body.len()
+        )
+        .into_bytes();
+        raw.extend_from_slice(body);
+        Self::from_bytes(&raw)
+    }
+
+    pub fn version(&self) -> String {
+        self.version.clone()
+    }
+
+    pub fn status_code(&self) -> u32 {
+        self.status_code
+    }
+
+    pub fn reason(&self) -> String {
+        self.reason.clone()
+    }
+
+    pub fn headers(&self) -> HeaderMap {
+        self.headers.clone()
+    }
+
+    /// Content-Typeのcharset、BOM、<meta charset>から判定した文字エンコーディングでデコードしたボディ
+    pub fn body(&self) -> String {
+        let content_type = self.headers.get("Content-Type");
+        charset::decode(&self.body, content_type.as_deref())
+            .replace("\r\n", "\n")
+    }
+
+    /// デコード前のボディのバイト列
+    pub fn body_bytes(&self) -> Vec<u8> {
+        self.body.clone()
+    }
+
+    /// nameという名前の最初のヘッダーの値を返す。名前の大文字・小文字は区別しない。
+    pub fn header_value(&self, name: &str) -> Result<String, String> {
+        match self.headers.get(name) {
+            Some(value) => Ok(value),
+            None => Err(format!("failed to find {} in headers", name)),
+        }
+    }
+}
+
+/// Transportを使ってHTTPリクエストを送信するクライアント
+/// Transportを差し替えることで、noli, std::net, インメモリのいずれの環境でも動作する。
+/// HTTP/1.1のkeep-aliveに対応し、使い終わった接続はホストごとにプールして再利用する。
+pub struct HttpClient<T: Transport> {
+    transport: T,
+    pool: RefCell<ConnectionPool<T::Connection>>,
+    // アイドル接続の破棄に使用する。設定されていない場合は、時間による破棄は行わない
+    clock: Option<Rc<dyn Clock>>,
+    // 設定されている場合は、リクエストにCookieを付け、レスポンスのSet-Cookieを保存する
+    cookie_jar: Option<Rc<RefCell<CookieJar>>>,
+    // 設定されている場合は、レスポンスを保存し、有効期間内であればネットワークを使わずに返す
+    cache: Option<Rc<RefCell<HttpCache>>>,
+    // 設定されている場合は、送信したリクエストの結果とかかった時間を記録する
+    net_log: Option<Rc<RefCell<NetLog>>>,
+}
+
+impl<T: Transport> HttpClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            pool: RefCell::new(ConnectionPool::new()),
+            clock: None,
+            cookie_jar: None,
+            cache: None,
+            net_log: None,
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = Some(clock);
+    }
+
+    /// Cookieを保存する入れ物を設定する。ページのdocument.cookieと同じものを共有する
+    pub fn set_cookie_jar(&mut self, cookie_jar: Rc<RefCell<CookieJar>>) {
+        self.cookie_jar = Some(cookie_jar);
+    }
+
+    /// レスポンスを保存するキャッシュを設定する
+    /// Clockが設定されていない場合は有効期間を判断できないため、保存したレスポンスを毎回検証する
+    pub fn set_cache(&mut self, cache: Rc<RefCell<HttpCache>>) {
+        self.cache = Some(cache);
+    }
+
+    /// 送信したリクエストを記録するNetLogを設定する
+    /// かかった時間はClockで計測するため、Clockが設定されていない場合は時間なしで記録する。
+    pub fn set_net_log(&mut self, net_log: Rc<RefCell<NetLog>>) {
+        self.net_log = Some(net_log);
+    }
+
+    /// アイドル接続を破棄するまでの秒数を設定する
+    pub fn set_idle_timeout(&mut self, seconds: u64) {
+        self.pool.borrow_mut().set_idle_timeout(seconds);
+    }
+
+    /// host:portへの、再利用可能な接続の数
+    pub fn idle_connection_count(&self, host: &str, port: u16) -> usize {
+        self.pool.borrow().idle_count(host, port)
+    }
+
+    /// host:portにpathへのGETリクエストを送信する
+    /// path: パスとクエリ。先頭の"/"は省略できる(example: "index.html")
+    pub fn get(
+        &self,
+        host: String,
+        port: u16,
+        path: String,
+    ) -> Result<HttpResponse, Error> {
+        self.send(&HttpRequest::new(Method::Get, host, port, path))
+    }
+
+    pub fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+        let net_log = match &self.net_log {
+            Some(net_log) => net_log,
+            None => return self.send_with_cache(request).map(|(r, _)| r),
+        };
+
+        let start = self.clock.as_ref().map(|clock| clock.now_millis());
+        let result = self.send_with_cache(request);
+        let end = self.clock.as_ref().map(|clock| clock.now_millis());
+        let from_cache = matches!(result, Ok((_, true)));
+        let result = result.map(|(response, _)| response);
+        net_log.borrow_mut().record(NetLogEntry::new(
+            request, &result, from_cache, start, end,
+        ));
+        result
+    }
+
+    // キャッシュを使ってリクエストを送信する
+    // 返り値の2つ目は、ネットワークを使わずにキャッシュのレスポンスを返したかどうか
+    // Vary: Cookieを実際に送るリクエストと比較するため、Cookieヘッダーはキャッシュを探す前に追加する
+    fn send_with_cache(
+        &self,
+        request: &HttpRequest,
+    ) -> Result<(HttpResponse, bool), Error> {
+        let mut request = self.with_cookie_header(request);
+        let cache = match &self.cache {
+            // 条件付きリクエストが指定されている場合は、304をそのまま呼び出し元に返す
+            Some(cache)
+                if !request.headers().contains("If-None-Match")
+                    && !request.headers().contains("If-Modified-Since") =>
+            {
+                cache
+            }
+            _ => return Ok((self.send_with_cookies(&request)?, false)),
+        };
+
+        let now = self.clock.as_ref().map(|clock| clock.now());
+        let lookup = cache.borrow_mut().lookup(&request, now);
+        match lookup {
+            CacheLookup::Fresh(response) => return Ok((response, true)),
+            // 保存しているレスポンスが変更されていなければ、サーバーは304を返す
+            CacheLookup::Stale(conditions) => {
+                for header in &conditions {
+                    request =
+                        request.with_header(&header.name(), &header.value());
+                }
+            }
+            CacheLookup::Miss => {}
+        }
+
+        let response = self.send_with_cookies(&request)?;
+        Ok((cache.borrow_mut().store(&request, response, now), false))
+    }
+
+    // CookieJarに保存しているCookieを、Cookieヘッダーとしてリクエストに追加する
+    // Cookieヘッダーが指定されている場合は、そのまま送る
+    fn with_cookie_header(&self, request: &HttpRequest) -> HttpRequest {
+        let cookie_jar = match &self.cookie_jar {
+            Some(cookie_jar) => cookie_jar,
+            None => return request.clone(),
+        };
+        if request.headers().contains("Cookie") {
+            return request.clone();
+        }
+        match cookie_jar.borrow().cookie_header(
+            &request.host(),
+            &request.target(),
+            false,
+            CookieSource::Http,
+        ) {
+            Some(cookie) => request.clone().with_header("Cookie", &cookie),
+            None => request.clone(),
+        }
+    }
+
+    // リクエストを送信し、レスポンスのSet-CookieをCookieJarに保存する
+    fn send_with_cookies(
+        &self,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, Error> {
+        let cookie_jar = match &self.cookie_jar {
+            Some(cookie_jar) => cookie_jar,
+            None => return self.send_request(request),
+        };
+
+        let response = self.send_request(request)?;
+        for set_cookie in response.headers.get_all("Set-Cookie") {
+            cookie_jar.borrow_mut().store(
+                &set_cookie,
+                &request.host(),
+                &request.target(),
+                CookieSource::Http,
+            );
+        }
+        Ok(response)
+    }
+
+    fn send_request(
+        &self,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, Error> {
+        let host = request.host();
+        let port = request.port();
+        let bytes = request.to_bytes();
+        let now = self.clock.as_ref().map(|clock| clock.now());
+
+        // プールに接続があれば再利用する。サーバーがすでに接続を閉じている場合があるため、
+        // 失敗した場合は新しい接続でやり直す。
+        // 送り直すと結果が変わる可能性があるリクエストは、常に新しい接続で送る
+        if request.method().is_idempotent() {
+            let pooled = self.pool.borrow_mut().take(&host, port, now);
+            if let Some(mut connection) = pooled {
+                if let Ok((response, reusable)) =
+                    Self::exchange(&mut connection, request.method(), &bytes)
+                {
+                    if reusable {
+                        self.pool.borrow_mut().put(&host, port, connection, now);
+                    }
+                    return Ok(response);
+                }
+            }
+        }
+
+        let mut connection = self.transport.connect(&host, port)?;
+        let (response, reusable) =
+            Self::exchange(&mut connection, request.method(), &bytes)?;
+        if reusable {
+            self.pool.borrow_mut().put(&host, port, connection, now);
+        }
+        Ok(response)
+    }
+
+    // リクエストを送信してレスポンスを受信する。
+    // 返り値の2つ目は、この接続を次のリクエストに再利用できるかどうか
+    fn exchange(
+        connection: &mut T::Connection,
+        method: Method,
+        request: &[u8],
+    ) -> Result<(HttpResponse, bool), Error> {
+        if connection.write(request).is_err() {
+            return Err(Error::Network(
+                "Failed to send a request to TCP stream".to_string(),
+            ));
+        }
+
+        // レスポンスをチャンクごとに受信し、パーサーに渡す
+        let mut parser = ResponseParser::new();
+        if method == Method::Head {
+            parser.set_no_body();
+        }
+        loop {
+            let mut buf = [0u8; 4096];
+            let bytes_read = match connection.read(&mut buf) {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    return Err(Error::Network(
+                        "Failed to receive a request from TCP stream"
+                            .to_string(),
+                    ))
+                }
+            };
+
+            if bytes_read == 0 {
+                break;
+            }
+            parser.feed(&buf[..bytes_read])?;
+
+            // Content-Lengthやチャンクの終わりまで受信したら、接続が閉じられるのを待たない
+            if parser.is_complete() {
+                break;
+            }
+        }
+
+        // ボディの終わりが接続の切断で示される場合は、接続を再利用できない
+        let complete = parser.is_complete();
+        let response = parser.finish()?;
+        let reusable = complete && is_keep_alive(&response);
+        // 101 Switching Protocols以降は、HTTP以外のプロトコルでの通信になる
+        let reusable = reusable && response.status_code != 101;
+        Ok((response, reusable))
+    }
+}
+
+// レスポンスを受信した後も接続を維持するかどうか
+// https://datatracker.ietf.org/doc/html/rfc9112#section-9.3
+fn is_keep_alive(response: &HttpResponse) -> bool {
+    if response.headers.has_token("Connection", "close") {
+        return false;
+    }
+    // HTTP/1.0はkeep-aliveが明示された場合のみ接続を維持する
+    response.version != "HTTP/1.0"
+        || response.headers.has_token("Connection", "keep-alive")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::error::HttpError;
+    use crate::transport::MemoryTransport;
+
+    #[test]
+    fn test_invalid() {
+        let raw = "HTTP/1.1 200 OK".to_string();
+        assert!(HttpResponse::new(raw).is_err());
+    }
+
+    #[test]
+    fn test_status_line_only() {
+        let raw = "HTTP/1.1 200 OK\n\n".to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "OK");
+        assert_eq!(res.version(), "HTTP/1.1");
+    }
+
+    #[test]
+    fn test_one_header() {
+        let raw = "HTTP/1.1 200 OK\nDate: xx xx xx\n\n".to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.version(), "HTTP/1.1");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "OK");
+        assert_eq!(res.headers().len(), 1);
+
+        assert_eq!(res.header_value("Date"), Ok("xx xx xx".to_string()));
+    }
+
+    #[test]
+    fn test_two_headers_with_white_space() {
+        let raw = "HTTP/1.1 200 OK\nDate: yyyy-mm-dd \nContent-Length: 333\n\n"
+            .to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.version(), "HTTP/1.1");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "OK");
+        assert_eq!(res.headers().len(), 2);
+
+        assert_eq!(res.header_value("Date"), Ok("yyyy-mm-dd".to_string()));
+        assert_eq!(res.header_value("Content-Length"), Ok("333".to_string()));
+    }
+
+    #[test]
+    fn test_body() {
+        let raw = "HTTP/1.1 200 OK\r\nDate: xx xx xx\r\n\r\nbody message  "
+            .to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.version(), "HTTP/1.1");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "OK");
+        assert_eq!(res.headers().len(), 1);
+
+        assert_eq!(res.header_value("Date"), Ok("xx xx xx".to_string()));
+        assert_eq!(res.body(), "body message  ")
+    }
+
+    #[test]
+    fn test_reason_phrase() {
+        let raw = "HTTP/1.1 500 Internal Server Error\r\n\r\n".to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.status_code(), 500);
+        assert_eq!(res.reason(), "Internal Server Error");
+
+        let raw = "HTTP/1.1 200\r\n\r\n".to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "");
+    }
+
+    #[test]
+    fn test_invalid_status_code() {
+        let raw = "HTTP/1.1 abc OK\r\n\r\n".to_string();
+        assert_eq!(
+            Some(Error::Http(HttpError::InvalidStatusCode("abc".to_string()))),
+            HttpResponse::new(raw).err()
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_header() {
+        let raw = "HTTP/1.1 302 Found\r\nlocation: /next\r\nset-cookie: a=1\r\nSet-Cookie: b=2\r\n\r\n"
+            .to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.header_value("Location"), Ok("/next".to_string()));
+        assert_eq!(res.headers().location(), Some("/next".to_string()));
+        assert_eq!(
+            res.headers().get_all("Set-Cookie"),
+            ["a=1".to_string(), "b=2".to_string()].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_header_without_colon() {
+        let raw = "HTTP/1.1 200 OK\r\nDate xx\r\n\r\nbody".to_string();
+        assert!(HttpResponse::new(raw).is_err());
+    }
+
+    #[test]
+    fn test_body_without_headers() {
+        let raw = "HTTP/1.1 200 OK\n\nbody".to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.headers().len(), 0);
+        assert_eq!(res.body(), "body");
+    }
+
+    #[test]
+    fn test_content_length() {
+        let raw = "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbodyextra"
+            .to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.body(), "body");
+    }
+
+    #[test]
+    fn test_invalid_content_length() {
+        let raw = "HTTP/1.1 200 OK\r\nContent-Length: abc\r\n\r\nbody"
+            .to_string();
+        assert!(HttpResponse::new(raw).is_err());
+    }
+
+    #[test]
+    fn test_chunked_body() {
+        let raw = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                   5\r\n<html\r\nA\r\n>\r\n<p>a</p\r\n1\r\n>\r\n0\r\n\r\n"
+            .to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        // チャンクデータ中のCRLFはデコード後に改行コードの変換が行われる
+        assert_eq!(res.body(), "<html>\n<p>a</p>");
+    }
+
+    #[test]
+    fn test_chunked_body_with_extension_and_trailer() {
+        let raw = "HTTP/1.1 200 OK\r\ntransfer-encoding: gzip, chunked\r\n\r\n\
+                   4;name=value\r\nbody\r\n0\r\nExpires: never\r\n\r\n"
+            .to_string();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.body(), "body");
+        assert_eq!(res.headers().len(), 2);
+        assert_eq!(res.header_value("Expires"), Ok("never".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_chunked_body() {
+        let invalid_size = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                            zz\r\nbody\r\n0\r\n\r\n"
+            .to_string();
+        assert!(HttpResponse::new(invalid_size).is_err());
+
+        let too_short = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                         10\r\nbody\r\n"
+            .to_string();
+        assert!(HttpResponse::new(too_short).is_err());
+
+        let missing_last_chunk =
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+             4\r\nbody\r\n"
+                .to_string();
+        assert!(HttpResponse::new(missing_last_chunk).is_err());
+    }
+
+    #[test]
+    fn test_non_utf8_body() {
+        // "日本語"のShift_JIS表現
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=Shift_JIS\r\n\r\n\x93\xfa\x96\x7b\x8c\xea";
+        let res =
+            HttpResponse::from_bytes(raw).expect("failed to parse http response");
+        assert_eq!(res.body_bytes(), b"\x93\xfa\x96\x7b\x8c\xea".to_vec());
+        assert_eq!(res.body(), "日本語");
+    }
+
+    #[test]
+    fn test_gzip_body() {
+        let gzip = include_bytes!("testdata/index.html.gz");
+        let mut raw = format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n",
+            gzip.len()
+        )
+        .into_bytes();
+        raw.extend_from_slice(gzip);
+        raw.extend_from_slice(b"\r\n0\r\n\r\n");
+        let res =
+            HttpResponse::from_bytes(&raw).expect("failed to parse http response");
+        assert_eq!(
+            res.body_bytes(),
+            include_bytes!("testdata/index.html").to_vec()
+        );
+        assert!(res.body().contains("<title>"));
+
+        let mut broken = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\n\r\n".to_vec();
+        broken.extend_from_slice(&gzip[..gzip.len() / 2]);
+        assert!(HttpResponse::from_bytes(&broken).is_err());
+    }
+
+    #[test]
+    fn test_client_get() {
+        let mut transport = MemoryTransport::new();
+        transport.add_response(
+            "example.com",
+            8000,
+            "/index.html",
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<p>hello</p>",
+        );
+        let client = HttpClient::new(transport.clone());
+        let res = client
+            .get("example.com".to_string(), 8000, "index.html".to_string())
+            .expect("failed to get http response");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.body(), "<p>hello</p>");
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("GET /index.html HTTP/1.1\r\n"));
+        assert!(requests[0].contains("Host: example.com:8000\r\n"));
+    }
+
+    #[test]
+    fn test_client_get_non_utf8() {
+        let mut transport = MemoryTransport::new();
+        // "日本語"のEUC-JP表現
+        transport.add_response(
+            "example.com",
+            80,
+            "/",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 44\r\n\r\n<meta charset=\"euc-jp\"><p>\xc6\xfc\xcb\xdc\xb8\xec</p>",
+        );
+        let client = HttpClient::new(transport);
+        let res = client
+            .get("example.com".to_string(), 80, "".to_string())
+            .expect("failed to get http response");
+        assert_eq!(res.body(), "<meta charset=\"euc-jp\"><p>日本語</p>");
+    }
+
+    fn keep_alive_transport(raw_response: &[u8]) -> MemoryTransport {
+        let mut transport = MemoryTransport::new();
+        transport.add_response("example.com", 80, "/", raw_response);
+        transport
+    }
+
+    #[test]
+    fn test_client_keep_alive() {
+        let transport =
+            keep_alive_transport(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbody");
+        let client = HttpClient::new(transport.clone());
+        for _ in 0..3 {
+            let res = client
+                .get("example.com".to_string(), 80, "".to_string())
+                .expect("failed to get http response");
+            assert_eq!(res.body(), "body");
+        }
+        assert_eq!(1, transport.connection_count());
+        assert_eq!(1, client.idle_connection_count("example.com", 80));
+        assert!(transport.requests()[0].contains("Connection: keep-alive\r\n"));
+    }
+
+    #[test]
+    fn test_client_connection_close() {
+        let transport = keep_alive_transport(
+            b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 4\r\n\r\nbody",
+        );
+        let client = HttpClient::new(transport.clone());
+        for _ in 0..2 {
+            client
+                .get("example.com".to_string(), 80, "".to_string())
+                .expect("failed to get http response");
+        }
+        assert_eq!(2, transport.connection_count());
+        assert_eq!(0, client.idle_connection_count("example.com", 80));
+    }
+
+    #[test]
+    fn test_client_interim_response() {
+        let transport = keep_alive_transport(
+            b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbody",
+        );
+        let client = HttpClient::new(transport.clone());
+        for _ in 0..2 {
+            let res = client
+                .get("example.com".to_string(), 80, "".to_string())
+                .expect("failed to get http response");
+            assert_eq!(200, res.status_code());
+            assert_eq!(res.body(), "body");
+        }
+        assert_eq!(1, transport.connection_count());
+    }
+
+    #[test]
+    fn test_client_switching_protocols() {
+        let transport = keep_alive_transport(
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n",
+        );
+        let client = HttpClient::new(transport.clone());
+        let res = client
+            .get("example.com".to_string(), 80, "".to_string())
+            .expect("failed to get http response");
+        assert_eq!(101, res.status_code());
+        assert_eq!(0, client.idle_connection_count("example.com", 80));
+    }
+
+    #[test]
+    fn test_client_body_until_close() {
+        // ボディの長さがわからないレスポンスの後は、接続を再利用できない
+        let transport = keep_alive_transport(b"HTTP/1.1 200 OK\r\n\r\nbody");
+        let client = HttpClient::new(transport.clone());
+        let res = client
+            .get("example.com".to_string(), 80, "".to_string())
+            .expect("failed to get http response");
+        assert_eq!(res.body(), "body");
+        assert_eq!(0, client.idle_connection_count("example.com", 80));
+    }
+
+    #[test]
+    fn test_client_retry_closed_connection() {
+        let transport = keep_alive_transport(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nbody\r\n0\r\n\r\n",
+        );
+        let client = HttpClient::new(transport.clone());
+        client
+            .get("example.com".to_string(), 80, "".to_string())
+            .expect("failed to get http response");
+
+        // サーバーがアイドル接続を閉じても、新しい接続でやり直す
+        transport.close_connections();
+        let res = client
+            .get("example.com".to_string(), 80, "".to_string())
+            .expect("failed to get http response");
+        assert_eq!(res.body(), "body");
+        assert_eq!(2, transport.connection_count());
+    }
+
+    #[test]
+    fn test_client_idle_timeout() {
+        let transport =
+            keep_alive_transport(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbody");
+        let clock = ManualClock::new(1000);
+        let mut client = HttpClient::new(transport.clone());
+        client.set_clock(Rc::new(clock.clone()));
+        client.set_idle_timeout(10);
+
+        client
+            .get("example.com".to_string(), 80, "".to_string())
+            .expect("failed to get http response");
+        clock.advance(9);
+        client
+            .get("example.com".to_string(), 80, "".to_string())
+            .expect("failed to get http response");
+        assert_eq!(1, transport.connection_count());
+
+        clock.advance(10);
+        client
+            .get("example.com".to_string(), 80, "".to_string())
+            .expect("failed to get http response");
+        assert_eq!(2, transport.connection_count());
+    }
+
+    #[test]
+    fn test_client_send() {
+        let mut transport = MemoryTransport::new();
+        transport.add_response(
+            "example.com",
+            80,
+            "/form?a=b",
+            b"HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok",
+        );
+        let client = HttpClient::new(transport.clone());
+        let request = HttpRequest::new(
+            Method::Post,
+            "example.com".to_string(),
+            80,
+            "/form?a=b".to_string(),
+        )
+        .with_header("Content-Type", "text/plain")
+        .with_body(b"hello".to_vec());
+        let res = client.send(&request).expect("failed to get http response");
+        assert_eq!(res.status_code(), 201);
+        assert_eq!(res.body(), "ok");
+
+        let requests = transport.requests();
+        assert!(requests[0].starts_with("POST /form?a=b HTTP/1.1\r\n"));
+        assert!(requests[0].contains("Content-Type: text/plain\r\n"));
+        assert!(requests[0].ends_with("\r\n\r\nhello"));
+    }
+
+    #[test]
+    fn test_client_cookie() {
+        let mut transport = MemoryTransport::new();
+        transport.add_response(
+            "example.com",
+            80,
+            "/login",
+            b"HTTP/1.1 200 OK\r\nSet-Cookie: session=abc; HttpOnly\r\n\
+              Set-Cookie: theme=dark; Path=/app\r\nContent-Length: 0\r\n\r\n",
+        );
+        transport.add_response(
+            "example.com",
+            80,
+            "/app/index.html",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+        );
+        let cookie_jar = Rc::new(RefCell::new(CookieJar::new()));
+        let mut client = HttpClient::new(transport.clone());
+        client.set_cookie_jar(cookie_jar.clone());
+
+        client
+            .get("example.com".to_string(), 80, "/login".to_string())
+            .expect("failed to get http response");
+        assert_eq!(2, cookie_jar.borrow().len());
+        client
+            .get("example.com".to_string(), 80, "/app/index.html".to_string())
+            .expect("failed to get http response");
+
+        let requests = transport.requests();
+        assert!(!requests[0].contains("Cookie:"));
+        assert!(requests[1].contains("Cookie: theme=dark; session=abc\r\n"));
+    }
+
+    #[test]
+    fn test_client_cache() {
+        let mut transport = keep_alive_transport(
+            b"HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nETag: \"v1\"\r\n\
+              Content-Length: 4\r\n\r\nbody",
+        );
+        let clock = ManualClock::new(1000);
+        let cache = Rc::new(RefCell::new(HttpCache::new()));
+        let mut client = HttpClient::new(transport.clone());
+        client.set_clock(Rc::new(clock.clone()));
+        client.set_cache(cache.clone());
+
+        for _ in 0..2 {
+            let res = client
+                .get("example.com".to_string(), 80, "".to_string())
+                .expect("failed to get http response");
+            assert_eq!(res.body(), "body");
+        }
+        // 有効期間内は、ネットワークにリクエストを送らない
+        assert_eq!(1, transport.requests().len());
+
+        // 有効期間が切れた後は条件付きリクエストを送り、304なら保存したボディを使う
+        clock.advance(60);
+        transport.add_response(
+            "example.com",
+            80,
+            "/",
+            b"HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\n\r\n",
+        );
+        let res = client
+            .get("example.com".to_string(), 80, "".to_string())
+            .expect("failed to get http response");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.body(), "body");
+
+        let requests = transport.requests();
+        assert_eq!(2, requests.len());
+        assert!(requests[1].contains("If-None-Match: \"v1\"\r\n"));
+        assert_eq!(1, cache.borrow().len());
+    }
+
+    #[test]
+    fn test_client_cache_vary_cookie() {
+        let transport = keep_alive_transport(
+            b"HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nVary: Cookie\r\n\
+              Set-Cookie: session=abc\r\nContent-Length: 4\r\n\r\nbody",
+        );
+        let mut client = HttpClient::new(transport.clone());
+        client.set_clock(Rc::new(ManualClock::new(1000)));
+        client.set_cache(Rc::new(RefCell::new(HttpCache::new())));
+        client.set_cookie_jar(Rc::new(RefCell::new(CookieJar::new())));
+
+        for _ in 0..3 {
+            client
+                .get("example.com".to_string(), 80, "".to_string())
+                .expect("failed to get http response");
+        }
+        // Cookieなしで保存したレスポンスは、Cookieを送るリクエストには使わない
+        let requests = transport.requests();
+        assert_eq!(2, requests.len());
+        assert!(!requests[0].contains("Cookie:"));
+        assert!(requests[1].contains("Cookie: session=abc\r\n"));
+    }
+
+    #[test]
+    fn test_client_net_log() {
+        let transport = keep_alive_transport(
+            b"HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\n\
+              Content-Length: 4\r\n\r\nbody",
+        );
+        let net_log = Rc::new(RefCell::new(NetLog::new()));
+        let mut client = HttpClient::new(transport);
+        client.set_clock(Rc::new(ManualClock::new(1)));
+        client.set_cache(Rc::new(RefCell::new(HttpCache::new())));
+        client.set_net_log(net_log.clone());
+
+        for _ in 0..2 {
+            client
+                .get("example.com".to_string(), 80, "/".to_string())
+                .expect("failed to get http response");
+        }
+        assert!(client
+            .get("unknown.com".to_string(), 80, "/".to_string())
+            .is_err());
+
+        let entries = net_log.borrow().entries();
+        assert_eq!(3, entries.len());
+        assert_eq!("http://example.com/", entries[0].url());
+        assert_eq!(Some(200), entries[0].status_code());
+        assert!(!entries[0].from_cache());
+        assert!(entries[1].from_cache());
+        assert_eq!(Some(1000), entries[1].start());
+        assert_eq!(Some(0), entries[1].duration());
+        assert_eq!(None, entries[2].status_code());
+        assert!(entries[2].error().is_some());
+    }
+
+    #[test]
+    fn test_client_head() {
+        // HEADリクエストのレスポンスは、Content-Lengthがあってもボディを持たない
+        let transport = keep_alive_transport(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n",
+        );
+        let client = HttpClient::new(transport.clone());
+        let request = HttpRequest::new(
+            Method::Head,
+            "example.com".to_string(),
+            80,
+            "".to_string(),
+        );
+        let res = client.send(&request).expect("failed to get http response");
+        assert_eq!(res.body(), "");
+        assert_eq!(1, client.idle_connection_count("example.com", 80));
+    }
+
+    #[test]
+    fn test_client_connection_error() {
+        let client = HttpClient::new(MemoryTransport::new());
+        assert!(client
+            .get("example.com".to_string(), 80, "".to_string())
+            .is_err());
+    }
+}
@@ -0,0 +1,193 @@
+// HttpClientが送信したリクエストの記録
+// about:net-internalsで、最近のリクエストの結果とかかった時間を表示するために使用する。
+
+use super::request::HttpRequest;
+use super::request::Method;
+use super::HttpResponse;
+use crate::error::Error;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// 記録するリクエストの最大数のデフォルト値
+pub const DEFAULT_MAX_ENTRIES: usize = 100;
+
+/// 1つのリクエストの記録
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetLogEntry {
+    method: Method,
+    url: String,
+    // レスポンスを受信できなかった場合はNone
+    status_code: Option<u32>,
+    error: Option<String>,
+    // ネットワークを使わずに、キャッシュのレスポンスを返したかどうか
+    from_cache: bool,
+    // リクエストを開始した時刻と、かかった時間(ミリ秒)。Clockがない場合はNone
+    start: Option<u64>,
+    duration: Option<u64>,
+}
+
+impl NetLogEntry {
+    pub fn new(
+        request: &HttpRequest,
+        result: &Result<HttpResponse, Error>,
+        from_cache: bool,
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> Self {
+        let (status_code, error) = match result {
+            Ok(response) => (Some(response.status_code()), None),
+            Err(e) => (None, Some(format!("{:?}", e))),
+        };
+        let duration = match (start, end) {
+            (Some(start), Some(end)) => Some(end.saturating_sub(start)),
+            _ => None,
+        };
+        Self {
+            method: request.method(),
+            url: request.url(),
+            status_code,
+            error,
+            from_cache,
+            start,
+            duration,
+        }
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn status_code(&self) -> Option<u32> {
+        self.status_code
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+
+    pub fn from_cache(&self) -> bool {
+        self.from_cache
+    }
+
+    pub fn start(&self) -> Option<u64> {
+        self.start
+    }
+
+    pub fn duration(&self) -> Option<u64> {
+        self.duration
+    }
+}
+
+/// 最近のリクエストの記録。上限を超えると古いものから削除する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetLog {
+    entries: VecDeque<NetLogEntry>,
+    max_entries: usize,
+}
+
+impl NetLog {
+    pub fn new() -> Self {
+        Self::with_max_entries(DEFAULT_MAX_ENTRIES)
+    }
+
+    pub fn with_max_entries(max_entries: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            max_entries,
+        }
+    }
+
+    pub fn record(&mut self, entry: NetLogEntry) {
+        if self.max_entries == 0 {
+            return;
+        }
+        while self.entries.len() >= self.max_entries {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// 記録したリクエストを、古いものから順に返す
+    pub fn entries(&self) -> Vec<NetLogEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Default for NetLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn request(target: &str) -> HttpRequest {
+        HttpRequest::new(
+            Method::Get,
+            "example.com".to_string(),
+            80,
+            target.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_entry() {
+        let response =
+            HttpResponse::new("HTTP/1.1 404 Not Found\n\n".to_string());
+        let entry = NetLogEntry::new(
+            &request("/a"),
+            &response,
+            false,
+            Some(10),
+            Some(25),
+        );
+        assert_eq!(Method::Get, entry.method());
+        assert_eq!("http://example.com/a", entry.url());
+        assert_eq!(Some(404), entry.status_code());
+        assert_eq!(None, entry.error());
+        assert_eq!(Some(15), entry.duration());
+
+        let error = Err(Error::Network("failed".to_string()));
+        let entry = NetLogEntry::new(&request("/b"), &error, false, None, None);
+        assert_eq!(None, entry.status_code());
+        assert_eq!(Some("Network(\"failed\")".to_string()), entry.error());
+        assert_eq!(None, entry.duration());
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let mut log = NetLog::with_max_entries(2);
+        let ok = HttpResponse::new("HTTP/1.1 200 OK\n\n".to_string());
+        for target in ["/1", "/2", "/3"] {
+            log.record(NetLogEntry::new(
+                &request(target),
+                &ok,
+                true,
+                None,
+                None,
+            ));
+        }
+        let urls: Vec<String> =
+            log.entries().iter().map(|entry| entry.url()).collect();
+        assert_eq!(
+            ["http://example.com/2", "http://example.com/3"].to_vec(),
+            urls
+        );
+
+        log.clear();
+        assert!(log.entries().is_empty());
+    }
+}
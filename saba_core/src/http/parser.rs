@@ -0,0 +1,540 @@
+// 受信したバイト列を少しずつ受け取って、HTTPレスポンスを組み立てるプッシュ型のパーサー
+// ステータスラインとヘッダーは揃った時点で参照でき、ボディは受信した分から順に取り出せる。
+// https://datatracker.ietf.org/doc/html/rfc9112
+
+use super::header::Header;
+use super::header::HeaderMap;
+use super::inflate::decode_content;
+use super::HttpResponse;
+use crate::error::Error;
+use crate::error::HttpError;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    StatusLine,
+    Headers,
+    /// Content-Lengthで長さが決まっているボディ。値は残りのバイト数
+    Body(usize),
+    /// 接続が閉じられるまで続くボディ
+    BodyUntilClose,
+    ChunkSize,
+    /// チャンクデータ。値はチャンクの残りのバイト数
+    ChunkData(usize),
+    ChunkDataEnd,
+    Trailers,
+    Done,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResponseParser {
+    state: State,
+    // 改行で終わっていない、途中までの行
+    line: Vec<u8>,
+    version: String,
+    status_code: u32,
+    reason: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    // read_bodyでまだ取り出されていないボディの先頭位置
+    body_read_pos: usize,
+    // HEADリクエストに対するレスポンスのように、ヘッダーに関わらずボディを持たないかどうか
+    no_body: bool,
+}
+
+impl ResponseParser {
+    pub fn new() -> Self {
+        Self {
+            state: State::StatusLine,
+            line: Vec::new(),
+            version: String::new(),
+            status_code: 0,
+            reason: String::new(),
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+            body_read_pos: 0,
+            no_body: false,
+        }
+    }
+
+    /// ボディを持たないレスポンスとして扱う。HEADリクエストのレスポンスを受信する時に使う。
+    pub fn set_no_body(&mut self) {
+        self.no_body = true;
+    }
+
+    /// 受信したバイト列を渡す。レスポンスが完成した後のデータは無視する。
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), Error> {
+        let mut pos = 0;
+        while pos < data.len() {
+            match self.state {
+                State::Body(remaining) | State::ChunkData(remaining) => {
+                    let size = core::cmp::min(remaining, data.len() - pos);
+                    self.body.extend_from_slice(&data[pos..pos + size]);
+                    pos += size;
+                    self.state = match self.state {
+                        State::Body(_) if remaining == size => State::Done,
+                        State::Body(_) => State::Body(remaining - size),
+                        _ if remaining == size => State::ChunkDataEnd,
+                        _ => State::ChunkData(remaining - size),
+                    };
+                }
+                State::BodyUntilClose => {
+                    self.body.extend_from_slice(&data[pos..]);
+                    pos = data.len();
+                }
+                State::Done => return Ok(()),
+                _ => match data[pos..].iter().position(|&c| c == b'\n') {
+                    Some(i) => {
+                        self.line.extend_from_slice(&data[pos..pos + i]);
+                        pos += i + 1;
+                        let line = core::mem::take(&mut self.line);
+                        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+                        self.process_line(line)?;
+                    }
+                    None => {
+                        self.line.extend_from_slice(&data[pos..]);
+                        pos = data.len();
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // 1行単位で処理する状態(ステータスライン、ヘッダー、チャンクサイズ、トレイラー)の処理
+    fn process_line(&mut self, line: &[u8]) -> Result<(), Error> {
+        let line = String::from_utf8_lossy(line);
+        match self.state {
+            State::StatusLine => {
+                // ステータスラインの前の空行は無視する
+                if line.trim().is_empty() {
+                    return Ok(());
+                }
+                let (version, status_code, reason) =
+                    parse_status_line(line.trim_start())?;
+                self.version = version;
+                self.status_code = status_code;
+                self.reason = reason;
+                self.state = State::Headers;
+            }
+            State::Headers => {
+                if line.is_empty() {
+                    if is_interim(self.status_code) {
+                        // 100 Continueなどの中間レスポンスの後には、最終的なレスポンスが続く
+                        // https://datatracker.ietf.org/doc/html/rfc9110#section-15.2
+                        self.headers = HeaderMap::new();
+                        self.state = State::StatusLine;
+                    } else {
+                        self.state = self.body_state()?;
+                    }
+                } else {
+                    self.append_header(Header::parse(&line)?);
+                }
+            }
+            State::ChunkSize => {
+                // チャンク拡張(";"以降)は無視する
+                let size = line.split(';').next().unwrap_or("").trim();
+                let size = match usize::from_str_radix(size, 16) {
+                    Ok(size) => size,
+                    Err(_) => {
+                        return Err(Error::Http(HttpError::InvalidChunk(
+                            line.to_string(),
+                        )))
+                    }
+                };
+                self.state = if size == 0 {
+                    State::Trailers
+                } else {
+                    State::ChunkData(size)
+                };
+            }
+            State::ChunkDataEnd => {
+                // チャンクデータの直後は改行でなければならない
+                if !line.is_empty() {
+                    return Err(Error::Http(HttpError::InvalidChunk(
+                        line.to_string(),
+                    )));
+                }
+                self.state = State::ChunkSize;
+            }
+            State::Trailers => {
+                // 最後のチャンクの後に、空行までトレイラーが続く。トレイラーはヘッダーと同じように扱う
+                if line.is_empty() {
+                    self.state = State::Done;
+                } else {
+                    self.append_header(Header::parse(&line)?);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn append_header(&mut self, header: Header) {
+        self.headers.append(&header.name(), &header.value());
+    }
+
+    // ヘッダーからボディの長さの決め方を判断する
+    // https://datatracker.ietf.org/doc/html/rfc9112#section-6.3
+    fn body_state(&self) -> Result<State, Error> {
+        // HEADリクエストと、101, 204, 304のレスポンスはボディを持たない
+        if self.no_body
+            || (100..200).contains(&self.status_code)
+            || self.status_code == 204
+            || self.status_code == 304
+        {
+            return Ok(State::Done);
+        }
+        if self.headers.has_token("Transfer-Encoding", "chunked") {
+            return Ok(State::ChunkSize);
+        }
+        match self.headers.content_length()? {
+            Some(0) => Ok(State::Done),
+            Some(length) => Ok(State::Body(length)),
+            None => Ok(State::BodyUntilClose),
+        }
+    }
+
+    /// ステータスラインの受信が完了している場合、ステータスコードを返す
+    /// 中間レスポンスの後は、最終的なレスポンスのステータスラインを受信するまでNoneを返す。
+    pub fn status_code(&self) -> Option<u32> {
+        match self.state {
+            State::StatusLine => None,
+            _ => Some(self.status_code),
+        }
+    }
+
+    /// ヘッダーの受信が完了している場合、ヘッダーを返す
+    pub fn headers(&self) -> Option<HeaderMap> {
+        match self.state {
+            State::StatusLine | State::Headers => None,
+            _ => Some(self.headers.clone()),
+        }
+    }
+
+    /// 前回の呼び出し以降に受信したボディを返す。チャンクの区切りなどは取り除かれている。
+    /// Content-Encodingによる圧縮は、finish()で復元されるため、ここでは圧縮されたままになる。
+    pub fn read_body(&mut self) -> Vec<u8> {
+        let body = self.body[self.body_read_pos..].to_vec();
+        self.body_read_pos = self.body.len();
+        body
+    }
+
+    /// レスポンスの終わりまで受信したかどうか
+    /// trueの場合、接続が閉じられるのを待たずに読み込みをやめてよい。
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// 接続が閉じられた時に呼び出し、受信した内容からレスポンスを作成する
+    pub fn finish(mut self) -> Result<HttpResponse, Error> {
+        match self.state {
+            State::StatusLine => {
+                // 改行で終わっていないステータスラインは、途中で切れている可能性があるため受け付けない
+                if self.line.iter().all(|c| c.is_ascii_whitespace()) {
+                    return Err(Error::Http(HttpError::MissingStatusLine));
+                }
+                return Err(Error::Http(HttpError::MalformedStatusLine(
+                    String::from_utf8_lossy(&self.line).to_string(),
+                )));
+            }
+            State::Headers => {
+                // 空行で終わっていないヘッダーは、受信した分だけで完結しているものとして扱う
+                let line = core::mem::take(&mut self.line);
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end_matches('\r');
+                if !line.is_empty() {
+                    self.append_header(Header::parse(line)?);
+                }
+            }
+            // 最後のチャンク(サイズが0のチャンク)を受信する前に閉じられた
+            State::ChunkSize | State::ChunkData(_) | State::ChunkDataEnd => {
+                return Err(Error::Http(HttpError::InvalidChunk(
+                    String::from_utf8_lossy(&self.line).to_string(),
+                )))
+            }
+            // Content-Lengthより短いボディや、トレイラーの途中で閉じられた場合は、受信した分を使う
+            _ => {}
+        }
+
+        // 圧縮されたボディを復元する。ヘッダーは受信したままにしておく
+        let body = decode_content(&self.headers, self.body)?;
+
+        Ok(HttpResponse {
+            version: self.version,
+            status_code: self.status_code,
+            reason: self.reason,
+            headers: self.headers,
+            body,
+        })
+    }
+}
+
+impl Default for ResponseParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ステータスラインを解釈し、HTTPバージョン、ステータスコード、理由句を返す
+/// status-line = HTTP-version SP status-code SP [ reason-phrase ]
+/// 理由句がない場合は、ステータスコードの後のSPも省略されていてもよい。
+/// https://datatracker.ietf.org/doc/html/rfc9112#section-4
+pub fn parse_status_line(line: &str) -> Result<(String, u32, String), Error> {
+    let (version, rest) = match line.split_once(' ') {
+        Some((version, rest)) => (version, rest),
+        None => {
+            return Err(Error::Http(HttpError::MalformedStatusLine(
+                line.to_string(),
+            )))
+        }
+    };
+
+    // HTTP-version = "HTTP/" DIGIT "." DIGIT
+    let version_bytes = version.as_bytes();
+    if version_bytes.len() != 8
+        || !version.starts_with("HTTP/")
+        || !version_bytes[5].is_ascii_digit()
+        || version_bytes[6] != b'.'
+        || !version_bytes[7].is_ascii_digit()
+    {
+        return Err(Error::Http(HttpError::InvalidVersion(
+            version.to_string(),
+        )));
+    }
+
+    let (status_code, reason) = match rest.split_once(' ') {
+        Some((status_code, reason)) => (status_code, reason),
+        None => (rest, ""),
+    };
+
+    // status-code = 3DIGIT
+    if status_code.len() != 3
+        || !status_code.bytes().all(|c| c.is_ascii_digit())
+    {
+        return Err(Error::Http(HttpError::InvalidStatusCode(
+            status_code.to_string(),
+        )));
+    }
+    let status_code = match status_code.parse::<u32>() {
+        Ok(code) if (100..600).contains(&code) => code,
+        _ => {
+            return Err(Error::Http(HttpError::InvalidStatusCode(
+                status_code.to_string(),
+            )))
+        }
+    };
+
+    // reason-phrase = 1*( HTAB / SP / VCHAR / obs-text )
+    if reason.chars().any(|c| c.is_ascii_control() && c != '\t') {
+        return Err(Error::Http(HttpError::MalformedStatusLine(
+            line.to_string(),
+        )));
+    }
+
+    Ok((version.to_string(), status_code, reason.to_string()))
+}
+
+// 101以外の1xxのレスポンスは中間レスポンスで、ボディを持たず、最終的なレスポンスが続く
+fn is_interim(status_code: u32) -> bool {
+    (100..200).contains(&status_code) && status_code != 101
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_bytewise(parser: &mut ResponseParser, data: &[u8]) {
+        for b in data {
+            parser.feed(&[*b]).expect("failed to feed");
+        }
+    }
+
+    #[test]
+    fn test_headers_before_body() {
+        let mut parser = ResponseParser::new();
+        parser.feed(b"HTTP/1.1 200 OK\r\nContent-Le").expect("failed to feed");
+        assert_eq!(Some(200), parser.status_code());
+        assert!(parser.headers().is_none());
+
+        parser.feed(b"ngth: 10\r\n\r\n<p>").expect("failed to feed");
+        let headers = parser.headers().expect("headers should be complete");
+        assert_eq!(1, headers.len());
+        assert_eq!(b"<p>".to_vec(), parser.read_body());
+        assert!(!parser.is_complete());
+
+        parser.feed(b"a</p>").expect("failed to feed");
+        assert_eq!(b"a</p>".to_vec(), parser.read_body());
+        assert!(parser.read_body().is_empty());
+    }
+
+    #[test]
+    fn test_content_length() {
+        let mut parser = ResponseParser::new();
+        parser
+            .feed(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbodyextra")
+            .expect("failed to feed");
+        assert!(parser.is_complete());
+        let res = parser.finish().expect("failed to finish");
+        assert_eq!(b"body".to_vec(), res.body_bytes());
+    }
+
+    #[test]
+    fn test_until_close() {
+        let mut parser = ResponseParser::new();
+        feed_bytewise(&mut parser, b"HTTP/1.0 200 OK\nDate: xx\n\nbody");
+        assert!(!parser.is_complete());
+        let res = parser.finish().expect("failed to finish");
+        assert_eq!("HTTP/1.0", res.version());
+        assert_eq!("body", res.body());
+    }
+
+    #[test]
+    fn test_chunked() {
+        let mut parser = ResponseParser::new();
+        feed_bytewise(
+            &mut parser,
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+              4;ext\r\nbody\r\n2\r\n!!\r\n0\r\nExpires: never\r\n\r\n",
+        );
+        assert!(parser.is_complete());
+        assert_eq!(b"body!!".to_vec(), parser.read_body());
+        let res = parser.finish().expect("failed to finish");
+        assert_eq!(2, res.headers().len());
+        assert_eq!(Ok("never".to_string()), res.header_value("Expires"));
+    }
+
+    #[test]
+    fn test_incomplete_chunked() {
+        let mut parser = ResponseParser::new();
+        parser
+            .feed(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nbody")
+            .expect("failed to feed");
+        assert!(parser.finish().is_err());
+    }
+
+    #[test]
+    fn test_no_body_status() {
+        let mut parser = ResponseParser::new();
+        parser
+            .feed(b"HTTP/1.1 304 Not Modified\r\nContent-Length: 10\r\n\r\n")
+            .expect("failed to feed");
+        assert!(parser.is_complete());
+    }
+
+    #[test]
+    fn test_interim_response() {
+        let mut parser = ResponseParser::new();
+        feed_bytewise(
+            &mut parser,
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 103 Early Hints\r\nLink: </a.css>\r\n\r\n",
+        );
+        assert_eq!(None, parser.status_code());
+        assert!(!parser.is_complete());
+
+        parser
+            .feed(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbody")
+            .expect("failed to feed");
+        assert!(parser.is_complete());
+        let res = parser.finish().expect("failed to finish");
+        assert_eq!(200, res.status_code());
+        // 中間レスポンスのヘッダーは含まない
+        assert_eq!(1, res.headers().len());
+        assert_eq!("body", res.body());
+    }
+
+    #[test]
+    fn test_switching_protocols() {
+        let mut parser = ResponseParser::new();
+        parser
+            .feed(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n")
+            .expect("failed to feed");
+        assert!(parser.is_complete());
+        assert_eq!(Some(101), parser.status_code());
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut parser = ResponseParser::new();
+        assert!(parser.feed(b"HTTP/1.1 200 OK\r\ninvalid\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_status_line() {
+        assert_eq!(
+            Ok(("HTTP/1.1".to_string(), 200, "OK".to_string())),
+            parse_status_line("HTTP/1.1 200 OK")
+        );
+        assert_eq!(
+            Ok((
+                "HTTP/1.1".to_string(),
+                500,
+                "Internal Server Error".to_string()
+            )),
+            parse_status_line("HTTP/1.1 500 Internal Server Error")
+        );
+        // 理由句は省略できる
+        assert_eq!(
+            Ok(("HTTP/1.0".to_string(), 204, "".to_string())),
+            parse_status_line("HTTP/1.0 204")
+        );
+        assert_eq!(
+            Ok(("HTTP/1.1".to_string(), 404, "".to_string())),
+            parse_status_line("HTTP/1.1 404 ")
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_status_line() {
+        assert_eq!(
+            Err(Error::Http(HttpError::MalformedStatusLine(
+                "HTTP/1.1".to_string()
+            ))),
+            parse_status_line("HTTP/1.1")
+        );
+        assert_eq!(
+            Err(Error::Http(HttpError::InvalidVersion("HTTP/11".to_string()))),
+            parse_status_line("HTTP/11 200 OK")
+        );
+        assert_eq!(
+            Err(Error::Http(HttpError::InvalidVersion("ICY".to_string()))),
+            parse_status_line("ICY 200 OK")
+        );
+        assert_eq!(
+            Err(Error::Http(HttpError::InvalidStatusCode("2OO".to_string()))),
+            parse_status_line("HTTP/1.1 2OO OK")
+        );
+        assert_eq!(
+            Err(Error::Http(HttpError::InvalidStatusCode("99".to_string()))),
+            parse_status_line("HTTP/1.1 99 OK")
+        );
+        assert_eq!(
+            Err(Error::Http(HttpError::InvalidStatusCode("600".to_string()))),
+            parse_status_line("HTTP/1.1 600 OK")
+        );
+        assert_eq!(
+            Err(Error::Http(HttpError::InvalidStatusCode("".to_string()))),
+            parse_status_line("HTTP/1.1  OK")
+        );
+    }
+
+    #[test]
+    fn test_missing_status_line() {
+        let parser = ResponseParser::new();
+        assert_eq!(
+            Some(Error::Http(HttpError::MissingStatusLine)),
+            parser.finish().err()
+        );
+    }
+
+    #[test]
+    fn test_status_line_only() {
+        let mut parser = ResponseParser::new();
+        parser.feed(b"HTTP/1.1 200 OK").expect("failed to feed");
+        assert!(parser.status_code().is_none());
+        assert!(parser.finish().is_err());
+    }
+}
@@ -0,0 +1,144 @@
+// HTTP/1.1のkeep-aliveで使い回す接続を、(ホスト, ポート)ごとに保持するプール
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// 1つのホストに対して保持するアイドル接続の最大数
+pub const MAX_IDLE_CONNECTIONS_PER_HOST: usize = 6;
+/// アイドル接続を破棄するまでの秒数
+/// サーバー側のタイムアウト(Apacheのデフォルトは5秒)より先に破棄するため、短めにしている。
+pub const DEFAULT_IDLE_TIMEOUT: u64 = 5;
+
+#[derive(Debug)]
+struct IdleConnection<C> {
+    connection: C,
+    // プールに戻された時刻。Clockが設定されていない場合はNone
+    idle_since: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct ConnectionPool<C> {
+    idle: BTreeMap<(String, u16), Vec<IdleConnection<C>>>,
+    idle_timeout: u64,
+}
+
+impl<C> ConnectionPool<C> {
+    pub fn new() -> Self {
+        Self { idle: BTreeMap::new(), idle_timeout: DEFAULT_IDLE_TIMEOUT }
+    }
+
+    pub fn set_idle_timeout(&mut self, seconds: u64) {
+        self.idle_timeout = seconds;
+    }
+
+    /// host:portへのアイドル接続を取り出す。最後に使われた接続から順に再利用する。
+    /// nowには現在時刻を渡す。Noneの場合はアイドル時間による破棄を行わない。
+    pub fn take(
+        &mut self,
+        host: &str,
+        port: u16,
+        now: Option<u64>,
+    ) -> Option<C> {
+        if let Some(now) = now {
+            self.evict_idle(now);
+        }
+        let key = (host.to_string(), port);
+        let connections = self.idle.get_mut(&key)?;
+        let connection = connections.pop().map(|c| c.connection);
+        if connections.is_empty() {
+            self.idle.remove(&key);
+        }
+        connection
+    }
+
+    /// 使い終わった接続をプールに戻す。上限を超えた場合は、最も古い接続を破棄する。
+    pub fn put(
+        &mut self,
+        host: &str,
+        port: u16,
+        connection: C,
+        now: Option<u64>,
+    ) {
+        let connections =
+            self.idle.entry((host.to_string(), port)).or_default();
+        if connections.len() >= MAX_IDLE_CONNECTIONS_PER_HOST {
+            connections.remove(0);
+        }
+        connections.push(IdleConnection { connection, idle_since: now });
+    }
+
+    /// アイドル時間がタイムアウトを超えた接続を破棄する
+    pub fn evict_idle(&mut self, now: u64) {
+        let idle_timeout = self.idle_timeout;
+        for connections in self.idle.values_mut() {
+            connections.retain(|c| match c.idle_since {
+                Some(idle_since) => {
+                    now.saturating_sub(idle_since) < idle_timeout
+                }
+                None => true,
+            });
+        }
+        self.idle.retain(|_, connections| !connections.is_empty());
+    }
+
+    /// host:portへのアイドル接続の数
+    pub fn idle_count(&self, host: &str, port: u16) -> usize {
+        match self.idle.get(&(host.to_string(), port)) {
+            Some(connections) => connections.len(),
+            None => 0,
+        }
+    }
+}
+
+impl<C> Default for ConnectionPool<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_and_put() {
+        let mut pool = ConnectionPool::new();
+        assert_eq!(None, pool.take("example.com", 80, None));
+
+        pool.put("example.com", 80, 1, None);
+        pool.put("example.com", 80, 2, None);
+        pool.put("example.com", 8080, 3, None);
+        assert_eq!(2, pool.idle_count("example.com", 80));
+
+        // 最後に戻した接続から再利用する
+        assert_eq!(Some(2), pool.take("example.com", 80, None));
+        assert_eq!(Some(1), pool.take("example.com", 80, None));
+        assert_eq!(None, pool.take("example.com", 80, None));
+        assert_eq!(Some(3), pool.take("example.com", 8080, None));
+    }
+
+    #[test]
+    fn test_max_idle_connections() {
+        let mut pool = ConnectionPool::new();
+        for i in 0..MAX_IDLE_CONNECTIONS_PER_HOST + 1 {
+            pool.put("example.com", 80, i, None);
+        }
+        assert_eq!(
+            MAX_IDLE_CONNECTIONS_PER_HOST,
+            pool.idle_count("example.com", 80)
+        );
+    }
+
+    #[test]
+    fn test_evict_idle() {
+        let mut pool = ConnectionPool::new();
+        pool.set_idle_timeout(10);
+        pool.put("example.com", 80, 1, Some(100));
+        pool.put("example.com", 80, 2, Some(105));
+
+        assert_eq!(Some(2), pool.take("example.com", 80, Some(110)));
+        assert_eq!(0, pool.idle_count("example.com", 80));
+    }
+}
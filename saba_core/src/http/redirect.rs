@@ -0,0 +1,261 @@
+// リダイレクトのレスポンスを辿る処理
+// https://datatracker.ietf.org/doc/html/rfc9110#section-15.4
+
+use super::request::HttpRequest;
+use super::request::Method;
+use super::HttpResponse;
+use crate::error::Error;
+use crate::error::HttpError;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// 辿るリダイレクトの最大回数
+pub const MAX_REDIRECTS: usize = 20;
+
+/// リダイレクトのステータスコードの場合、リダイレクト先に送るリクエストのメソッドを返す
+/// 301と302は、ブラウザの慣習に従いPOSTをGETに変える。303はHEAD以外をGETに変える。
+/// 307と308はメソッドを変えない。
+pub fn redirect_method(status_code: u32, method: Method) -> Option<Method> {
+    match status_code {
+        301 | 302 if method == Method::Post => Some(Method::Get),
+        303 if method != Method::Head => Some(Method::Get),
+        301 | 302 | 303 | 307 | 308 => Some(method),
+        _ => None,
+    }
+}
+
+/// requestをsendで送信し、リダイレクトであればLocationのURLへのリクエストを送り直す
+/// Locationは、リダイレクトしたリクエストのURLを基準に解決する。
+/// リダイレクトではないレスポンスを受信した時の、最後のリクエストとレスポンスと、そのURLを返す。
+/// URLのフラグメントはサーバーに送られないため、Locationにフラグメントがあればそれを、
+/// なければ1つ前のURLのフラグメントを引き継ぐ。
+/// https://fetch.spec.whatwg.org/#http-redirect-fetch
+pub fn follow_redirects<F>(
+    request: HttpRequest,
+    mut send: F,
+) -> Result<(HttpRequest, HttpResponse, Url), Error>
+where
+    F: FnMut(&HttpRequest) -> Result<HttpResponse, Error>,
+{
+    let mut request = request;
+    let mut current = match Url::parse(&request.url()) {
+        Ok(current) => current,
+        Err(e) => return Err(Error::UnexpectedInput(e)),
+    };
+    // これまでに辿った(リクエストしたURL, Location)の組
+    let mut visited: Vec<(String, String)> = Vec::new();
+
+    loop {
+        let response = send(&request)?;
+        let method =
+            match redirect_method(response.status_code(), request.method()) {
+                Some(method) => method,
+                None => return Ok((request, response, current)),
+            };
+        // Locationがない場合は、リダイレクトのレスポンスをそのまま返す
+        let location = match response.headers().location() {
+            Some(location) => location,
+            None => return Ok((request, response, current)),
+        };
+
+        let url = request.url();
+        let mut next = match current.resolve(&location) {
+            Ok(next) => next,
+            Err(_) => {
+                return Err(Error::Http(HttpError::InvalidLocation(location)))
+            }
+        };
+        if let (None, Some(fragment)) = (next.fragment(), current.fragment()) {
+            if let Ok(url) = next.resolve(&format!("#{}", fragment)) {
+                next = url;
+            }
+        }
+
+        let edge = (url, next.href());
+        if visited.contains(&edge) {
+            return Err(Error::Http(HttpError::RedirectLoop(edge.0)));
+        }
+        if visited.len() >= MAX_REDIRECTS {
+            return Err(Error::Http(HttpError::TooManyRedirects(edge.0)));
+        }
+        visited.push(edge);
+
+        request = request.redirect(method, &next)?;
+        current = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn get(target: &str) -> HttpRequest {
+        HttpRequest::new(
+            Method::Get,
+            "example.com".to_string(),
+            80,
+            target.to_string(),
+        )
+    }
+
+    fn redirect(status_code: u32, location: &str) -> HttpResponse {
+        HttpResponse::new(format!(
+            "HTTP/1.1 {} Redirect\r\nLocation: {}\r\n\r\n",
+            status_code, location
+        ))
+        .expect("failed to parse http response")
+    }
+
+    fn ok() -> HttpResponse {
+        HttpResponse::new("HTTP/1.1 200 OK\r\n\r\nbody".to_string())
+            .expect("failed to parse http response")
+    }
+
+    #[test]
+    fn test_redirect_method() {
+        assert_eq!(Some(Method::Get), redirect_method(301, Method::Post));
+        assert_eq!(Some(Method::Get), redirect_method(302, Method::Post));
+        assert_eq!(Some(Method::Put), redirect_method(302, Method::Put));
+        assert_eq!(Some(Method::Get), redirect_method(303, Method::Delete));
+        assert_eq!(Some(Method::Head), redirect_method(303, Method::Head));
+        assert_eq!(Some(Method::Post), redirect_method(307, Method::Post));
+        assert_eq!(Some(Method::Post), redirect_method(308, Method::Post));
+        assert_eq!(None, redirect_method(304, Method::Get));
+        assert_eq!(None, redirect_method(200, Method::Get));
+    }
+
+    #[test]
+    fn test_relative_location() {
+        let mut targets = Vec::new();
+        let (request, response, url) =
+            follow_redirects(get("/a/b"), |request| {
+                targets.push(request.url());
+                Ok(match request.target().as_str() {
+                    "/a/b" => redirect(301, "c?x=1"),
+                    "/a/c?x=1" => redirect(308, "//other.com:8000/d"),
+                    _ => ok(),
+                })
+            })
+            .expect("failed to follow redirects");
+
+        assert_eq!(200, response.status_code());
+        assert_eq!("http://other.com:8000/d", request.url());
+        assert_eq!("http://other.com:8000/d", url.href());
+        assert_eq!(
+            [
+                "http://example.com/a/b",
+                "http://example.com/a/c?x=1",
+                "http://other.com:8000/d"
+            ]
+            .to_vec(),
+            targets
+        );
+    }
+
+    #[test]
+    fn test_location_fragment() {
+        // Locationのフラグメントは、その後のリダイレクト先にも引き継ぐ
+        let mut targets = Vec::new();
+        let (_, _, url) = follow_redirects(get("/a"), |request| {
+            targets.push(request.target());
+            Ok(match request.target().as_str() {
+                "/a" => redirect(302, "/b#sec"),
+                "/b" => redirect(302, "/c"),
+                "/c" => redirect(302, "/d#other"),
+                _ => ok(),
+            })
+        })
+        .expect("failed to follow redirects");
+        assert_eq!("http://example.com/d#other", url.href());
+        assert_eq!(["/a", "/b", "/c", "/d"].to_vec(), targets);
+
+        let (_, _, url) = follow_redirects(get("/a"), |request| {
+            Ok(match request.target().as_str() {
+                "/a" => redirect(302, "/b#sec"),
+                _ => ok(),
+            })
+        })
+        .expect("failed to follow redirects");
+        assert_eq!("http://example.com/b#sec", url.href());
+    }
+
+    #[test]
+    fn test_post_to_get() {
+        let post = HttpRequest::new(
+            Method::Post,
+            "example.com".to_string(),
+            80,
+            "/form".to_string(),
+        )
+        .with_body(b"a=b".to_vec());
+        let mut methods = Vec::new();
+        follow_redirects(post, |request| {
+            methods.push((request.method(), request.body().len()));
+            Ok(match request.target().as_str() {
+                "/form" => redirect(307, "/form2"),
+                "/form2" => redirect(303, "/done"),
+                _ => ok(),
+            })
+        })
+        .expect("failed to follow redirects");
+        assert_eq!(
+            [(Method::Post, 3), (Method::Post, 3), (Method::Get, 0)].to_vec(),
+            methods
+        );
+    }
+
+    #[test]
+    fn test_redirect_loop() {
+        let result = follow_redirects(get("/a"), |request| {
+            Ok(match request.target().as_str() {
+                "/a" => redirect(302, "/b"),
+                _ => redirect(302, "/a"),
+            })
+        });
+        assert_eq!(
+            Some(Error::Http(HttpError::RedirectLoop(
+                "http://example.com/a".to_string()
+            ))),
+            result.err()
+        );
+    }
+
+    #[test]
+    fn test_too_many_redirects() {
+        let mut count = 0;
+        let result = follow_redirects(get("/0"), |_| {
+            count += 1;
+            Ok(redirect(302, &format!("/{}", count)))
+        });
+        assert!(matches!(
+            result,
+            Err(Error::Http(HttpError::TooManyRedirects(_)))
+        ));
+        assert_eq!(MAX_REDIRECTS + 1, count);
+    }
+
+    #[test]
+    fn test_invalid_or_missing_location() {
+        let result =
+            follow_redirects(get("/"), |_| Ok(redirect(302, "http://[::1/")));
+        assert_eq!(
+            Some(Error::Http(HttpError::InvalidLocation(
+                "http://[::1/".to_string()
+            ))),
+            result.err()
+        );
+        // HTTP以外のスキームへはリダイレクトできない
+        let result =
+            follow_redirects(get("/"), |_| Ok(redirect(302, "https://a.com/")));
+        assert!(matches!(result, Err(Error::UnexpectedInput(_))));
+
+        let (_, response, _) = follow_redirects(get("/"), |_| {
+            HttpResponse::new("HTTP/1.1 302 Found\r\n\r\n".to_string())
+        })
+        .expect("failed to follow redirects");
+        assert_eq!(302, response.status_code());
+    }
+}
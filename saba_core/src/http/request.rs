@@ -0,0 +1,366 @@
+// HTTPリクエストの組み立てとシリアライズ
+// https://datatracker.ietf.org/doc/html/rfc9112#section-3
+
+use super::header::HeaderMap;
+use crate::error::Error;
+use crate::url::Scheme;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+        }
+    }
+
+    /// 同じリクエストを何度送っても結果が変わらないメソッドかどうか
+    /// 冪等なリクエストは、接続が切れた時に送り直すことができる。
+    /// https://datatracker.ietf.org/doc/html/rfc9110#section-9.2.2
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(self, Method::Post)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    method: Method,
+    host: String,
+    port: u16,
+    // origin-formのリクエストターゲット(example: "/index.html?a=b")
+    target: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// target: パスとクエリ。先頭の"/"は省略できる(example: "index.html?a=b")
+    pub fn new(
+        method: Method,
+        host: String,
+        port: u16,
+        target: String,
+    ) -> Self {
+        let target = if target.starts_with('/') {
+            target
+        } else {
+            format!("/{}", target)
+        };
+        Self {
+            method,
+            host,
+            port,
+            target,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// URLのホスト、ポート、パス、クエリからリクエストを作成する
+    /// フラグメントはサーバーに送らない。
+    pub fn from_url(method: Method, url: &Url) -> Result<Self, Error> {
+        if url.scheme() != Scheme::Http {
+            return Err(Error::UnexpectedInput(format!(
+                "Only HTTP scheme is supported: {}",
+                url.href()
+            )));
+        }
+        let port = url.port_or_default().unwrap_or(80);
+        let mut target = url.path();
+        if let Some(query) = url.query() {
+            target.push('?');
+            target.push_str(&query);
+        }
+        Ok(Self::new(method, url.host(), port, target))
+    }
+
+    /// ヘッダーを追加する。同じ名前のヘッダーがある場合は置き換える。
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.set(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    pub fn host(&self) -> String {
+        self.host.clone()
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn target(&self) -> String {
+        self.target.clone()
+    }
+
+    pub fn headers(&self) -> HeaderMap {
+        self.headers.clone()
+    }
+
+    pub fn body(&self) -> Vec<u8> {
+        self.body.clone()
+    }
+
+    /// リクエスト先のURL(example: "http://example.com:8000/index.html?a=b")
+    pub fn url(&self) -> String {
+        if self.port == 80 {
+            format!("http://{}{}", self.host, self.target)
+        } else {
+            format!("http://{}:{}{}", self.host, self.port, self.target)
+        }
+    }
+
+    /// urlへリダイレクトする時の、次のリクエストを作成する
+    /// メソッドがGETに変わる場合はボディとボディに関するヘッダーを、
+    /// 別のホストに移る場合は認証情報のヘッダーを引き継がない。
+    pub fn redirect(&self, method: Method, url: &Url) -> Result<Self, Error> {
+        let mut request = Self::from_url(method, url)?;
+        let drop_body = method != self.method;
+        let cross_origin = request.host != self.host || request.port != self.port;
+        for header in &self.headers {
+            let name = header.name();
+            let is_body_header = name.eq_ignore_ascii_case("Content-Type")
+                || name.eq_ignore_ascii_case("Content-Length")
+                || name.eq_ignore_ascii_case("Content-Encoding");
+            let is_credential = name.eq_ignore_ascii_case("Cookie")
+                || name.eq_ignore_ascii_case("Authorization");
+            if name.eq_ignore_ascii_case("Host")
+                || (drop_body && is_body_header)
+                || (cross_origin && is_credential)
+            {
+                continue;
+            }
+            request.headers.append(&name, &header.value());
+        }
+        if !drop_body {
+            request.body = self.body.clone();
+        }
+        Ok(request)
+    }
+
+    /// 送信するバイト列に変換する。Host, Accept, Accept-Encoding, Connection,
+    /// Content-Lengthは、指定されていない場合にデフォルトの値を追加する。
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut request =
+            format!("{} {} HTTP/1.1\r\n", self.method.as_str(), self.target);
+
+        if !self.headers.contains("Host") {
+            if self.port == 80 {
+                request.push_str(&format!("Host: {}\r\n", self.host));
+            } else {
+                request.push_str(&format!(
+                    "Host: {}:{}\r\n",
+                    self.host, self.port
+                ));
+            }
+        }
+        if !self.headers.contains("Accept") {
+            request.push_str("Accept: text/html\r\n");
+        }
+        // 復元できる圧縮形式を伝える
+        if !self.headers.contains("Accept-Encoding") {
+            request.push_str("Accept-Encoding: gzip, deflate\r\n");
+        }
+        if !self.headers.contains("Connection") {
+            request.push_str("Connection: keep-alive\r\n");
+        }
+        // ボディを送るメソッドは、空でも長さを明示する
+        if !self.headers.contains("Content-Length")
+            && (!self.body.is_empty()
+                || matches!(self.method, Method::Post | Method::Put))
+        {
+            request
+                .push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+
+        for header in &self.headers {
+            request.push_str(&format!(
+                "{}: {}\r\n",
+                header.name(),
+                header.value()
+            ));
+        }
+        request.push_str("\r\n");
+
+        let mut bytes = request.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_get() {
+        let request = HttpRequest::new(
+            Method::Get,
+            "example.com".to_string(),
+            80,
+            "index.html".to_string(),
+        );
+        assert_eq!(
+            b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nAccept: text/html\r\nAccept-Encoding: gzip, deflate\r\nConnection: keep-alive\r\n\r\n".to_vec(),
+            request.to_bytes()
+        );
+    }
+
+    #[test]
+    fn test_target() {
+        let request = HttpRequest::new(
+            Method::Get,
+            "example.com".to_string(),
+            80,
+            "/a/b?c=d".to_string(),
+        );
+        assert_eq!("/a/b?c=d", request.target());
+
+        let request = HttpRequest::new(
+            Method::Head,
+            "example.com".to_string(),
+            80,
+            "".to_string(),
+        );
+        assert_eq!("/", request.target());
+    }
+
+    #[test]
+    fn test_from_url() {
+        let url = Url::parse("http://example.com:8888/index.html?a=b#top")
+            .expect("failed to parse url");
+        let request = HttpRequest::from_url(Method::Get, &url)
+            .expect("failed to create request");
+        assert_eq!("example.com", request.host());
+        assert_eq!(8888, request.port());
+        assert_eq!("/index.html?a=b", request.target());
+        assert!(String::from_utf8_lossy(&request.to_bytes())
+            .contains("Host: example.com:8888\r\n"));
+
+        let url = Url::parse("https://example.com/").expect("failed to parse url");
+        assert!(HttpRequest::from_url(Method::Get, &url).is_err());
+    }
+
+    #[test]
+    fn test_post_with_headers_and_body() {
+        let request = HttpRequest::new(
+            Method::Post,
+            "example.com".to_string(),
+            80,
+            "/form".to_string(),
+        )
+        .with_header("Content-Type", "application/x-www-form-urlencoded")
+        .with_header("accept", "*/*")
+        .with_header("Accept", "text/plain")
+        .with_body(b"a=b".to_vec());
+        assert_eq!(
+            b"POST /form HTTP/1.1\r\nHost: example.com\r\nAccept-Encoding: gzip, deflate\r\nConnection: keep-alive\r\nContent-Length: 3\r\nContent-Type: application/x-www-form-urlencoded\r\nAccept: text/plain\r\n\r\na=b".to_vec(),
+            request.to_bytes()
+        );
+    }
+
+    #[test]
+    fn test_empty_post() {
+        let request = HttpRequest::new(
+            Method::Post,
+            "example.com".to_string(),
+            80,
+            "/".to_string(),
+        );
+        assert!(String::from_utf8_lossy(&request.to_bytes())
+            .contains("Content-Length: 0\r\n"));
+
+        let request = HttpRequest::new(
+            Method::Delete,
+            "example.com".to_string(),
+            80,
+            "/".to_string(),
+        );
+        assert!(!String::from_utf8_lossy(&request.to_bytes())
+            .contains("Content-Length"));
+    }
+
+    #[test]
+    fn test_idempotent() {
+        assert!(Method::Get.is_idempotent());
+        assert!(Method::Put.is_idempotent());
+        assert!(!Method::Post.is_idempotent());
+    }
+
+    #[test]
+    fn test_url() {
+        let request = HttpRequest::new(
+            Method::Get,
+            "example.com".to_string(),
+            80,
+            "a?b=c".to_string(),
+        );
+        assert_eq!("http://example.com/a?b=c", request.url());
+        let request = HttpRequest::new(
+            Method::Get,
+            "localhost".to_string(),
+            8000,
+            "".to_string(),
+        );
+        assert_eq!("http://localhost:8000/", request.url());
+    }
+
+    #[test]
+    fn test_redirect() {
+        let request = HttpRequest::new(
+            Method::Post,
+            "example.com".to_string(),
+            80,
+            "/form".to_string(),
+        )
+        .with_header("Content-Type", "text/plain")
+        .with_header("Cookie", "a=b")
+        .with_header("X-Custom", "1")
+        .with_body(b"hello".to_vec());
+
+        let url =
+            Url::parse("http://example.com/next").expect("failed to parse url");
+        let same_method = request
+            .redirect(Method::Post, &url)
+            .expect("failed to create a request");
+        assert_eq!("/next", same_method.target());
+        assert_eq!(b"hello".to_vec(), same_method.body());
+        assert_eq!(request.headers(), same_method.headers());
+
+        let url = Url::parse("http://other.com:8000/next")
+            .expect("failed to parse url");
+        let get = request
+            .redirect(Method::Get, &url)
+            .expect("failed to create a request");
+        assert_eq!(Method::Get, get.method());
+        assert_eq!(("other.com".to_string(), 8000), (get.host(), get.port()));
+        assert!(get.body().is_empty());
+        assert!(!get.headers().contains("Content-Type"));
+        assert!(!get.headers().contains("Cookie"));
+        assert_eq!(Some("1".to_string()), get.headers().get("X-Custom"));
+    }
+}
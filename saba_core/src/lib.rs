@@ -3,11 +3,18 @@
 
 extern crate alloc;
 
+pub mod about;
 pub mod browser;
+pub mod charset;
+pub mod clock;
 pub mod constants;
+pub mod data_url;
 pub mod display_item;
 pub mod error;
+pub mod fetcher;
 pub mod http;
+pub mod omnibox;
 pub mod renderer;
+pub mod transport;
 pub mod url;
 pub mod utils;
@@ -0,0 +1,210 @@
+// アドレスバーに入力された文字列を、移動先のURLに変換する処理
+// スキームを省略したホスト名はhttp:のURLとして扱い、URLとして解釈できない入力は検索語として扱う。
+
+use crate::url::Host;
+use crate::url::Scheme;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+
+/// 検索に使うURLのデフォルト値。"%s"が検索語に置き換えられる
+pub const DEFAULT_SEARCH_URL: &str = "http://www.google.com/search?q=%s";
+
+/// アドレスバーの入力を解釈する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Omnibox {
+    // 検索に使うURLのテンプレート(example: "http://example.com/search?q=%s")
+    search_url: String,
+}
+
+impl Omnibox {
+    pub fn new() -> Self {
+        Self {
+            search_url: DEFAULT_SEARCH_URL.to_string(),
+        }
+    }
+
+    /// 検索に使うURLのテンプレートを設定する。"%s"が検索語に置き換えられる
+    pub fn set_search_url(&mut self, search_url: String) {
+        self.search_url = search_url;
+    }
+
+    pub fn search_url(&self) -> String {
+        self.search_url.clone()
+    }
+
+    /// 入力を移動先のURLに変換する。前後の空白は取り除く
+    /// 1. ブラウザが読み込めるスキームを持つURLは、そのまま使う
+    /// 2. "example.com", "localhost:8000/a"のようなホスト名で始まる入力は、http:のURLにする
+    /// 3. それ以外は、検索語として検索用のURLに埋め込む
+    ///
+    /// 空の入力の場合はNoneを返す。
+    pub fn navigation_url(&self, input: &str) -> Option<String> {
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+
+        if let Ok(url) = Url::parse(input) {
+            if is_navigable_scheme(&url.scheme()) {
+                return Some(url.href());
+            }
+        }
+
+        // 空白を含む入力は、URLではなく検索語として扱う
+        if !input.contains(char::is_whitespace) {
+            if let Some(url) = infer_http_url(input) {
+                return Some(url.href());
+            }
+        }
+
+        Some(self.search_url.replace("%s", &encode_search_terms(input)))
+    }
+}
+
+impl Default for Omnibox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// アドレスバーから直接移動できるスキームかどうか
+// "localhost:8000"のような入力は"localhost"というスキームとして解釈されるため、
+// 知らないスキームはホスト名として解釈し直す。
+fn is_navigable_scheme(scheme: &Scheme) -> bool {
+    matches!(
+        scheme,
+        Scheme::Http
+            | Scheme::Https
+            | Scheme::File
+            | Scheme::Data
+            | Scheme::About
+    )
+}
+
+// スキームを省略した入力を、http:のURLとして解釈する
+// ホストが"localhost"、IPアドレス、"."を含むドメイン名、またはポート番号が指定されている場合だけURLとみなす。
+fn infer_http_url(input: &str) -> Option<Url> {
+    let url = Url::parse(&format!("http://{}", input)).ok()?;
+    if !url.username().is_empty() || !url.password().is_empty() {
+        return None;
+    }
+    if url.port().is_some() {
+        return Some(url);
+    }
+
+    match url.host_kind()? {
+        Host::Ipv4(_) | Host::Ipv6(_) => Some(url),
+        Host::Domain(domain) if domain == "localhost" => Some(url),
+        Host::Domain(domain) => {
+            // 最後のラベル(トップレベルドメイン)は2文字以上の英字
+            let (name, tld) = domain.trim_end_matches('.').rsplit_once('.')?;
+            if !name.is_empty()
+                && tld.len() >= 2
+                && tld.chars().all(|c| c.is_ascii_alphabetic())
+            {
+                Some(url)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// 検索語をapplication/x-www-form-urlencodedの形式でエンコードする
+// https://url.spec.whatwg.org/#concept-urlencoded-byte-serializer
+fn encode_search_terms(input: &str) -> String {
+    let mut output = String::new();
+    for c in input.bytes() {
+        match c {
+            b' ' => output.push('+'),
+            b'*' | b'-' | b'.' | b'_' => output.push(c as char),
+            _ if c.is_ascii_alphanumeric() => output.push(c as char),
+            _ => output.push_str(&format!("%{:02X}", c)),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn navigate(input: &str) -> Option<String> {
+        Omnibox::new().navigation_url(input)
+    }
+
+    fn search(terms: &str) -> Option<String> {
+        Some(format!("http://www.google.com/search?q={}", terms))
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            Some("http://example.com/index.html".to_string()),
+            navigate("  http://example.com/index.html\n")
+        );
+        assert_eq!(Some("about:blank".to_string()), navigate("about:blank"));
+        assert_eq!(
+            Some("file:///tmp/a.html".to_string()),
+            navigate("file:///tmp/a.html")
+        );
+        assert_eq!(None, navigate("   "));
+    }
+
+    #[test]
+    fn test_infer_scheme() {
+        assert_eq!(
+            Some("http://example.com/".to_string()),
+            navigate("example.com")
+        );
+        assert_eq!(
+            Some("http://www.example.co.jp/a?b=c#d".to_string()),
+            navigate("www.example.co.jp/a?b=c#d")
+        );
+        assert_eq!(
+            Some("http://localhost:8000/test.html".to_string()),
+            navigate("localhost:8000/test.html")
+        );
+        assert_eq!(
+            Some("http://localhost/".to_string()),
+            navigate("localhost")
+        );
+        assert_eq!(
+            Some("http://myhost:8080/".to_string()),
+            navigate("myhost:8080")
+        );
+        assert_eq!(
+            Some("http://127.0.0.1/".to_string()),
+            navigate("127.0.0.1")
+        );
+        assert_eq!(
+            Some("http://[::1]:8000/".to_string()),
+            navigate("[::1]:8000")
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        assert_eq!(search("rust"), navigate("rust"));
+        assert_eq!(search("rust+browser"), navigate(" rust browser "));
+        assert_eq!(search("example.com+is"), navigate("example.com is"));
+        assert_eq!(search("a%26b%3Dc%3F"), navigate("a&b=c?"));
+        assert_eq!(search("%E3%81%82"), navigate("あ"));
+        assert_eq!(search("1.x"), navigate("1.x"));
+        assert_eq!(search("user%40example.com"), navigate("user@example.com"));
+    }
+
+    #[test]
+    fn test_search_url() {
+        let mut omnibox = Omnibox::new();
+        omnibox
+            .set_search_url("http://localhost:8000/?q=%s&lang=ja".to_string());
+        assert_eq!(
+            Some("http://localhost:8000/?q=a+b&lang=ja".to_string()),
+            omnibox.navigation_url("a b")
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use alloc::{string::String, vec::Vec};
 
 use super::token::CssToken;
+use crate::renderer::diagnostics::Span;
 
 // セレクター
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,12 +18,21 @@ pub enum Selector {
 
 // 宣言ノード
 // https://www.w3.org/TR/css-syntax-3/#declaration
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Declaration {
     // font-colorなどを入れる
     pub property: String,
     // 20pxなどの値を入れる
     pub value: ComponentValue,
+    // プロパティ名から値までのソース上の範囲
+    pub span: Option<Span>,
+}
+
+// ソース上の範囲は比較しない
+impl PartialEq for Declaration {
+    fn eq(&self, other: &Self) -> bool {
+        self.property == other.property && self.value == other.value
+    }
 }
 
 impl Declaration {
@@ -30,6 +40,7 @@ impl Declaration {
         Self {
             property: String::new(),
             value: ComponentValue::Ident(String::new()),
+            span: None,
         }
     }
 
@@ -40,6 +51,10 @@ impl Declaration {
     pub fn set_value(&mut self, value: ComponentValue) {
         self.value = value;
     }
+
+    pub fn set_span(&mut self, span: Span) {
+        self.span = Some(span);
+    }
 }
 
 // コンポーネント値ノード
@@ -47,11 +62,21 @@ impl Declaration {
 pub type ComponentValue = CssToken;
 
 // cssの一つのルール
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct QualifiedRule {
     // 公式では、セレクターは1つのルールで複数指定できますが、今回は一つのみとする。（eg. div, #id...)
     pub selector: Selector,
     pub declarations: Vec<Declaration>,
+    // セレクターから"}"までのソース上の範囲
+    pub span: Option<Span>,
+}
+
+// ソース上の範囲は比較しない
+impl PartialEq for QualifiedRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.selector == other.selector
+            && self.declarations == other.declarations
+    }
 }
 
 impl QualifiedRule {
@@ -59,6 +84,7 @@ impl QualifiedRule {
         Self {
             selector: Selector::TypeSelector(String::new()),
             declarations: Vec::new(),
+            span: None,
         }
     }
 
@@ -69,6 +95,10 @@ impl QualifiedRule {
     pub fn set_declarations(&mut self, declarations: Vec<Declaration>) {
         self.declarations = declarations;
     }
+
+    pub fn set_span(&mut self, span: Span) {
+        self.span = Some(span);
+    }
 }
 
 // CSSOMのルート
@@ -2,19 +2,53 @@ use super::cssom::{ComponentValue, Declaration};
 use crate::renderer::css::cssom::{QualifiedRule, Selector, StyleSheet};
 use crate::renderer::css::token::CssToken;
 use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::diagnostics::sort_errors;
+use crate::renderer::diagnostics::ParseError;
+use crate::renderer::diagnostics::SourceKind;
+use crate::renderer::diagnostics::Span;
+use crate::renderer::diagnostics::TokenStream;
+use alloc::format;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
-use core::iter::Peekable;
 
 #[derive(Debug, Clone)]
 pub struct CssParser {
-    t: Peekable<CssTokenizer>,
+    t: TokenStream<CssTokenizer>,
+    // 構文解析中に見つかったエラー
+    errors: Vec<ParseError>,
 }
 
 impl CssParser {
     pub fn new(t: CssTokenizer) -> Self {
-        Self { t: t.peekable() }
+        Self {
+            t: TokenStream::new(t),
+            errors: Vec::new(),
+        }
+    }
+
+    /// 字句解析と構文解析で見つかったエラーを、ソース上の位置の順に返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        let mut errors = self.t.inner().errors();
+        errors.extend(self.errors.iter().cloned());
+        sort_errors(&mut errors);
+        errors
+    }
+
+    // エラーを記録する。CSSのエラーは、そのルールや宣言を無視して解析を続ける
+    // https://www.w3.org/TR/css-syntax-3/#error-handling
+    fn parse_error(&mut self, message: String, span: Span) {
+        self.errors
+            .push(ParseError::new(SourceKind::Css, message, span));
+    }
+
+    // 正しいがサポートしていない構文を無視したことを記録する
+    fn unsupported(&mut self, message: String, span: Span) {
+        self.errors.push(ParseError::unsupported(
+            SourceKind::Css,
+            message,
+            span,
+        ));
     }
 
     pub fn parse_stylesheet(&mut self) -> StyleSheet {
@@ -38,8 +72,8 @@ impl CssParser {
             match token {
                 // AtKeyword トークンが出てきた場合、ほかのCSSのインポートする@import, @mediaなどを表す
                 CssToken::AtKeyword(_keyword) => {
-                    // 今回は、Wから始まるルールはサポートしない
-                    let _rule = self.consume_qualified_rule();
+                    // 今回は、@から始まるルールはサポートしない
+                    self.consume_at_rule();
                 }
 
                 _ => {
@@ -54,13 +88,48 @@ impl CssParser {
         }
     }
 
+    // @ルールを読み飛ばす。"{"で始まるブロックは、入れ子の"{}"も含めて読み飛ばす
+    // https://www.w3.org/TR/css-syntax-3/#consume-an-at-rule
+    fn consume_at_rule(&mut self) {
+        let keyword = match self.t.next() {
+            Some(CssToken::AtKeyword(keyword)) => keyword,
+            _ => return,
+        };
+        let span = self.t.span();
+        self.unsupported(format!("unsupported at-rule @{}", keyword), span);
+
+        let mut depth = 0;
+        for token in self.t.by_ref() {
+            match token {
+                CssToken::SemiColon if depth == 0 => return,
+                CssToken::OpenCurly => depth += 1,
+                CssToken::CloseCurly => {
+                    depth -= 1;
+                    if depth <= 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
     fn consume_qualified_rule(&mut self) -> Option<QualifiedRule> {
         let mut rule = QualifiedRule::new();
+        let start = self.t.peek_span();
 
         loop {
             let token = match self.t.peek() {
                 Some(t) => t,
-                None => return None,
+                None => {
+                    // 宣言ブロックがないまま入力が終わった
+                    let span = self.t.peek_span();
+                    self.parse_error(
+                        "unexpected end of input before '{'".to_string(),
+                        span,
+                    );
+                    return None;
+                }
             };
 
             match token {
@@ -68,6 +137,7 @@ impl CssParser {
                 CssToken::OpenCurly => {
                     assert_eq!(self.t.next(), Some(CssToken::OpenCurly));
                     rule.set_declarations(self.consume_list_of_declarations());
+                    rule.set_span(start.to(self.t.span()));
                     return Some(rule);
                 }
                 _ => {
@@ -81,7 +151,7 @@ impl CssParser {
     fn consume_selector(&mut self) -> Selector {
         let token = match self.t.next() {
             Some(t) => t,
-            None => panic!("should have a token but got None"),
+            None => return Selector::UnknownSelector,
         };
 
         match token {
@@ -89,43 +159,71 @@ impl CssParser {
             CssToken::HashToken(value) => {
                 Selector::IdSelector(value[1..].to_string())
             }
-            CssToken::Delim(delim) => {
-                if delim == '.' {
-                    return Selector::ClassSelector(self.consume_ident());
+            CssToken::Delim('.') => match self.consume_ident() {
+                Some(ident) => Selector::ClassSelector(ident),
+                None => {
+                    self.skip_to_block();
+                    Selector::UnknownSelector
                 }
-                panic!("Parse error: {:?} is an expected token.", token);
-            }
+            },
             CssToken::Ident(ident) => {
                 // a:hoverのようなセレクタはタイプセレクタとして扱う
                 // コロンが出てきた場合は宣言ブロックの直前までトークンを進める
                 // a:hoverは、aとして扱う
                 if self.t.peek() == Some(&CssToken::Colon) {
-                    while self.t.peek() != Some(&CssToken::OpenCurly) {
-                        self.t.next();
-                    }
+                    self.skip_to_block();
                 }
                 Selector::TypeSelector(ident.to_string())
             }
             CssToken::AtKeyword(_keyword) => {
-                while self.t.peek() != Some(&CssToken::OpenCurly) {
-                    self.t.next();
-                }
+                self.skip_to_block();
                 Selector::UnknownSelector
             }
             _ => {
-                self.t.next();
+                // "*"や"h1, h2"、"a > b"のようなサポートしていないセレクタのルールは、どの要素にも適用しない
+                let span = self.t.span();
+                self.unsupported(
+                    format!("unsupported token {:?} in selector", token),
+                    span,
+                );
+                self.skip_to_block();
                 Selector::UnknownSelector
             }
         }
     }
 
+    // 宣言ブロックの"{"の直前まで、トークンを読み飛ばす
+    fn skip_to_block(&mut self) {
+        while !matches!(self.t.peek(), Some(CssToken::OpenCurly) | None) {
+            self.t.next();
+        }
+    }
+
+    // 宣言の終わりの";"か"}"の直前まで、トークンを読み飛ばす
+    fn skip_to_end_of_declaration(&mut self) {
+        while !matches!(
+            self.t.peek(),
+            Some(CssToken::SemiColon) | Some(CssToken::CloseCurly) | None
+        ) {
+            self.t.next();
+        }
+    }
+
     fn consume_list_of_declarations(&mut self) -> Vec<Declaration> {
         let mut declarations = Vec::new();
 
         loop {
             let token = match self.t.peek() {
                 Some(t) => t,
-                None => return declarations,
+                None => {
+                    let span = self.t.peek_span();
+                    self.parse_error(
+                        "unexpected end of input in declaration block"
+                            .to_string(),
+                        span,
+                    );
+                    return declarations;
+                }
             };
 
             match token {
@@ -143,49 +241,68 @@ impl CssParser {
                     }
                 }
                 _ => {
-                    self.t.next();
+                    let message =
+                        format!("unexpected token {:?} in declaration", token);
+                    let span = self.t.peek_span();
+                    self.parse_error(message, span);
+                    self.skip_to_end_of_declaration();
                 }
             }
         }
     }
 
     fn consume_declaration(&mut self) -> Option<Declaration> {
-        if self.t.peek().is_none() {
-            return None;
-        }
+        self.t.peek()?;
 
+        let start = self.t.peek_span();
         let mut declaration = Declaration::new();
         // 識別子を設定する。 font: xxx; の時のfontの部分
-        declaration.set_property(self.consume_ident());
-        // もし次のトークンが転んでない場合、パースエラーなのでNoneを返す。
-        match self.t.next() {
-            Some(token) => match token {
-                CssToken::Colon => {}
-                _ => return None,
-            },
-            None => return None,
+        declaration.set_property(self.consume_ident()?);
+        // もし次のトークンがコロンでない場合、パースエラーなのでNoneを返す。
+        if self.t.peek() != Some(&CssToken::Colon) {
+            let span = self.t.peek_span();
+            self.parse_error(
+                format!("expected ':' after {}", declaration.property),
+                span,
+            );
+            self.skip_to_end_of_declaration();
+            return None;
         }
-        declaration.set_value(self.consume_component_value());
+        assert_eq!(self.t.next(), Some(CssToken::Colon));
+
+        declaration.set_value(self.consume_component_value()?);
+        declaration.set_span(start.to(self.t.span()));
+        // 値の2つ目以降のコンポーネント値(10pxのpxなど)はサポートしないため、読み飛ばす
+        self.skip_to_end_of_declaration();
         Some(declaration)
     }
 
-    fn consume_ident(&mut self) -> String {
-        let token = match self.t.next() {
-            Some(t) => t,
-            None => panic!("should have a token but got None"),
+    fn consume_ident(&mut self) -> Option<String> {
+        if let Some(CssToken::Ident(ident)) = self.t.peek() {
+            let ident = ident.to_string();
+            self.t.next();
+            return Some(ident);
+        }
+
+        let message = match self.t.peek() {
+            Some(token) => format!("expected identifier but got {:?}", token),
+            None => "expected identifier but got end of input".to_string(),
         };
+        let span = self.t.peek_span();
+        self.parse_error(message, span);
+        None
+    }
 
-        match token {
-            CssToken::Ident(ref ident) => ident.to_string(),
-            _ => {
-                panic!("Parse Error: {:?} is an unexpected token", token);
+    fn consume_component_value(&mut self) -> Option<ComponentValue> {
+        match self.t.peek() {
+            Some(CssToken::SemiColon) | Some(CssToken::CloseCurly) | None => {
+                let span = self.t.peek_span();
+                self.parse_error("missing value".to_string(), span);
+                None
             }
+            _ => self.t.next(),
         }
     }
-
-    fn consume_component_value(&mut self) -> ComponentValue {
-        self.t.next().expect("should have a consume_component_value")
-    }
 }
 
 #[cfg(test)]
@@ -291,4 +408,97 @@ mod tests {
             assert_eq!(rule, &expected[index]);
         }
     }
+
+    #[test]
+    fn test_parse_errors() {
+        let mut parser = CssParser::new(CssTokenizer::new(
+            "div > p {color: red;}\nh1 {color blue; font-size: ; margin: 10px;}"
+                .to_string(),
+        ));
+        let cssom = parser.parse_stylesheet();
+
+        // サポートしていないセレクタのルールも、後続のルールの解析は続ける
+        assert_eq!(2, cssom.rules.len());
+        assert_eq!(Selector::UnknownSelector, cssom.rules[0].selector);
+        assert_eq!(
+            Selector::TypeSelector("h1".to_string()),
+            cssom.rules[1].selector
+        );
+
+        let mut margin = Declaration::new();
+        margin.set_property("margin".to_string());
+        margin.set_value(ComponentValue::Number(10.0));
+        assert_eq!(vec![margin], cssom.rules[1].declarations);
+
+        let errors: Vec<(String, usize, usize, bool)> = parser
+            .errors()
+            .iter()
+            .map(|e| {
+                (
+                    e.message(),
+                    e.span().start().line(),
+                    e.span().start().column(),
+                    e.is_unsupported(),
+                )
+            })
+            .collect();
+        // 正しいがサポートしていないセレクタは、構文エラーとは区別する
+        assert_eq!(
+            vec![
+                (
+                    "unsupported token Delim('>') in selector".to_string(),
+                    1,
+                    5,
+                    true
+                ),
+                ("expected ':' after color".to_string(), 2, 11, false),
+                ("missing value".to_string(), 2, 28, false),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_unsupported_syntax() {
+        let mut parser = CssParser::new(CssTokenizer::new(
+            "*{margin:0}\nh1, h2 {}\n@media screen { p { color: red; } }\n\
+             @import \"a.css\";\np {color: red;}"
+                .to_string(),
+        ));
+        let cssom = parser.parse_stylesheet();
+
+        // @ルールのブロックは、入れ子のブロックも含めて読み飛ばす
+        assert_eq!(3, cssom.rules.len());
+        assert_eq!(
+            Selector::TypeSelector("p".to_string()),
+            cssom.rules[2].selector
+        );
+        assert_eq!(1, cssom.rules[2].declarations.len());
+
+        let errors = parser.errors();
+        assert_eq!(4, errors.len());
+        assert!(errors.iter().all(|e| e.is_unsupported()));
+        assert_eq!("unsupported at-rule @media", errors[2].message());
+    }
+
+    #[test]
+    fn test_span() {
+        let cssom = create_stylesheet("p {}\nh1 { margin: 10px; }".to_string());
+        let position = |span: Option<Span>| {
+            span.map(|span| {
+                (
+                    (span.start().line(), span.start().column()),
+                    (span.end().line(), span.end().column()),
+                )
+            })
+        };
+
+        assert_eq!(Some(((1, 1), (1, 5))), position(cssom.rules[0].span));
+        assert_eq!(Some(((2, 1), (2, 21))), position(cssom.rules[1].span));
+        // 宣言の範囲は、サポートしている最初のコンポーネント値まで
+        assert_eq!(
+            Some(((2, 6), (2, 16))),
+            position(cssom.rules[1].declarations[0].span)
+        );
+    }
 }
@@ -1,3 +1,9 @@
+use crate::renderer::diagnostics::LineMap;
+use crate::renderer::diagnostics::ParseError;
+use crate::renderer::diagnostics::SourceKind;
+use crate::renderer::diagnostics::Span;
+use crate::renderer::diagnostics::Spanned;
+use alloc::string::ToString;
 use alloc::{string::String, vec::Vec};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -21,34 +27,84 @@ pub enum CssToken {
 pub struct CssTokenizer {
     pos: usize,
     input: Vec<char>,
+    // 入力の位置を行と列に変換するための表
+    line_map: LineMap,
+    // 最後に返したトークンのソース上の範囲
+    latest_span: Span,
+    // 字句解析中に見つかったエラー
+    errors: Vec<ParseError>,
 }
 
 impl CssTokenizer {
     pub fn new(css: String) -> Self {
+        let input: Vec<char> = css.chars().collect();
+        let line_map = LineMap::new(&input);
         Self {
             pos: 0,
-            input: css.chars().collect(),
+            input,
+            latest_span: line_map.span(0, 0),
+            line_map,
+            errors: Vec::new(),
         }
     }
 
+    /// 字句解析中に見つかったエラーを返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        self.errors.clone()
+    }
+
+    fn parse_error(&mut self, message: &str, start: usize) {
+        self.errors.push(ParseError::new(
+            SourceKind::Css,
+            message.to_string(),
+            self.line_map.span(start, self.pos),
+        ));
+    }
+
+    // 開始の引用符と同じ引用符までを、文字列として消費する
+    // https://www.w3.org/TR/css-syntax-3/#consume-string-token
     fn consume_string_token(&mut self) -> String {
+        let start = self.pos;
+        let quote = self.input[self.pos];
         let mut s = String::new();
 
         loop {
+            self.pos += 1;
             if self.pos >= self.input.len() {
+                // 閉じる引用符がないまま入力が終わった
+                self.parse_error("unterminated string", start);
+                self.pos -= 1;
                 return s;
             }
 
-            self.pos += 1;
             let c = self.input[self.pos];
-            match c {
-                '"' | '\'' => break,
-                _ => s.push(c),
+            if c == quote {
+                break;
             }
+            s.push(c);
         }
         s
     }
 
+    // "/*"から"*/"までのコメントを読み飛ばす
+    // https://www.w3.org/TR/css-syntax-3/#consume-comments
+    fn consume_comment(&mut self) {
+        let start = self.pos;
+        self.pos += 2;
+        loop {
+            if self.pos + 1 >= self.input.len() {
+                self.pos = self.input.len();
+                self.parse_error("unterminated comment", start);
+                return;
+            }
+            if self.input[self.pos] == '*' && self.input[self.pos + 1] == '/' {
+                self.pos += 2;
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+
     fn consume_numeric_token(&mut self) -> f64 {
         let mut num = 0f64;
         let mut floating = false;
@@ -86,10 +142,10 @@ impl CssTokenizer {
         let mut s = String::new();
         s.push(self.input[self.pos]);
         loop {
+            self.pos += 1;
             if self.pos >= self.input.len() {
                 return s;
             }
-            self.pos += 1;
             let c = self.input[self.pos];
             match c {
                 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => {
@@ -108,8 +164,10 @@ impl Iterator for CssTokenizer {
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             if self.pos >= self.input.len() {
+                self.latest_span = self.line_map.span(self.pos, self.pos);
                 return None;
             }
+            let start = self.pos;
 
             let c = self.input[self.pos];
 
@@ -122,10 +180,14 @@ impl Iterator for CssTokenizer {
                 ';' => CssToken::SemiColon,
                 '{' => CssToken::OpenCurly,
                 '}' => CssToken::CloseCurly,
-                ' ' | '\n' => {
+                c if c.is_whitespace() => {
                     self.pos += 1;
                     continue;
                 }
+                '/' if self.input.get(self.pos + 1) == Some(&'*') => {
+                    self.consume_comment();
+                    continue;
+                }
                 '"' | '\'' => {
                     let value = self.consume_string_token();
                     CssToken::StringToken(value)
@@ -149,7 +211,7 @@ impl Iterator for CssTokenizer {
                 }
                 '@' => {
                     // 次の3文字が識別子として有効な文字の場合、<at-keyword-token>
-                    if self.input.len() >= self.pos + 3
+                    if self.input.len() > self.pos + 3
                         && self.input[self.pos + 1].is_ascii_alphabetic()
                         && self.input[self.pos + 2].is_ascii_alphabetic()
                         && self.input[self.pos + 3].is_ascii_alphabetic()
@@ -168,15 +230,24 @@ impl Iterator for CssTokenizer {
                     self.pos -= 1;
                     t
                 }
-                _ => unimplemented!("char {} is not supported yet", c),
+                // それ以外の文字は、区切り文字トークンとして返す
+                // https://www.w3.org/TR/css-syntax-3/#consume-token
+                _ => CssToken::Delim(c),
             };
 
             self.pos += 1;
+            self.latest_span = self.line_map.span(start, self.pos);
             return Some(token);
         }
     }
 }
 
+impl Spanned for CssTokenizer {
+    fn latest_span(&self) -> Span {
+        self.latest_span
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -311,4 +382,55 @@ mod tests {
         }
         assert!(t.next().is_none());
     }
+
+    #[test]
+    fn test_delim_and_comment() {
+        let style = "div > p /* comment */ *{}".to_string();
+        let mut t = CssTokenizer::new(style);
+        let expected = [
+            CssToken::Ident("div".to_string()),
+            CssToken::Delim('>'),
+            CssToken::Ident("p".to_string()),
+            CssToken::Delim('*'),
+            CssToken::OpenCurly,
+            CssToken::CloseCurly,
+        ];
+        for e in expected {
+            assert_eq!(Some(e), t.next());
+        }
+        assert!(t.next().is_none());
+        assert!(t.errors().is_empty());
+    }
+
+    #[test]
+    fn test_span_and_errors() {
+        let style = "div {}\na { content: \"x }".to_string();
+        let mut t = CssTokenizer::new(style);
+        let mut tokens = Vec::new();
+        while let Some(token) = t.next() {
+            let span = t.latest_span();
+            tokens.push((
+                token,
+                (span.start().line(), span.start().column()),
+                (span.end().line(), span.end().column()),
+            ));
+        }
+        assert_eq!(
+            (CssToken::Ident("div".to_string()), (1, 1), (1, 4)),
+            tokens[0]
+        );
+        assert_eq!((CssToken::OpenCurly, (2, 3), (2, 4)), tokens[4]);
+        // 閉じる引用符がない文字列は、入力の終わりまでになる
+        assert_eq!(
+            (CssToken::StringToken("x }".to_string()), (2, 14), (2, 18)),
+            tokens[7]
+        );
+        assert_eq!(8, tokens.len());
+
+        let errors = t.errors();
+        assert_eq!(1, errors.len());
+        assert_eq!("unterminated string", errors[0].message());
+        assert_eq!(2, errors[0].span().start().line());
+        assert_eq!(14, errors[0].span().start().column());
+    }
 }
@@ -0,0 +1,269 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// ソース上の位置。行と列は1から数え、列は文字単位で数える
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SourcePosition {
+    line: usize,
+    column: usize,
+}
+
+impl SourcePosition {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// <style>や<script>の中身のように、baseの位置から埋め込まれたソースでの位置を、
+    /// 外側のソースでの位置に変換する
+    pub fn offset_by(&self, base: SourcePosition) -> Self {
+        if self.line == 1 {
+            Self::new(base.line, base.column + self.column - 1)
+        } else {
+            Self::new(base.line + self.line - 1, self.column)
+        }
+    }
+}
+
+/// ソース上の範囲。endは範囲の最後の文字の次の位置を指す
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    start: SourcePosition,
+    end: SourcePosition,
+}
+
+impl Span {
+    pub fn new(start: SourcePosition, end: SourcePosition) -> Self {
+        Self { start, end }
+    }
+
+    pub fn start(&self) -> SourcePosition {
+        self.start
+    }
+
+    pub fn end(&self) -> SourcePosition {
+        self.end
+    }
+
+    /// 2つの範囲を含む範囲を返す
+    pub fn to(&self, other: Span) -> Self {
+        Self::new(self.start, other.end)
+    }
+
+    pub fn offset_by(&self, base: SourcePosition) -> Self {
+        Self::new(self.start.offset_by(base), self.end.offset_by(base))
+    }
+}
+
+/// 入力の何文字目かを、行と列に変換するための表
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMap {
+    // 各行の先頭の文字の位置
+    line_starts: Vec<usize>,
+}
+
+impl LineMap {
+    pub fn new(input: &[char]) -> Self {
+        let mut line_starts = Vec::new();
+        line_starts.push(0);
+        for (i, c) in input.iter().enumerate() {
+            if *c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        Self { line_starts }
+    }
+
+    pub fn position(&self, offset: usize) -> SourcePosition {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        SourcePosition::new(line + 1, offset - self.line_starts[line] + 1)
+    }
+
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.position(start), self.position(end))
+    }
+}
+
+/// エラーが見つかったソースの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Html,
+    Css,
+    Js,
+}
+
+/// パース中に見つかったエラー
+/// パースは中断せずに続けるため、ページの作者がどこを間違えたかを知るために使う。
+/// HTMLのエラーには、"missing-doctype"のような仕様で定義された形式のエラーコードを使う。
+/// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: SourceKind,
+    message: String,
+    span: Span,
+    // 構文としては正しいが、このブラウザがサポートしていないために無視した場合はtrue
+    unsupported: bool,
+}
+
+impl ParseError {
+    pub fn new(kind: SourceKind, message: String, span: Span) -> Self {
+        Self {
+            kind,
+            message,
+            span,
+            unsupported: false,
+        }
+    }
+
+    /// セレクタの結合子や@mediaのように、正しいがサポートしていない構文を無視したことを表す
+    pub fn unsupported(kind: SourceKind, message: String, span: Span) -> Self {
+        Self {
+            unsupported: true,
+            ..Self::new(kind, message, span)
+        }
+    }
+
+    pub fn kind(&self) -> SourceKind {
+        self.kind
+    }
+
+    pub fn is_unsupported(&self) -> bool {
+        self.unsupported
+    }
+
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn offset_by(&self, base: SourcePosition) -> Self {
+        Self {
+            span: self.span.offset_by(base),
+            ..self.clone()
+        }
+    }
+}
+
+/// 最後に返したトークンの、ソース上の範囲を返せる字句解析器
+pub trait Spanned {
+    fn latest_span(&self) -> Span;
+}
+
+/// 字句解析器のトークンを1つ先読みできるようにする
+/// core::iter::Peekableと違い、消費したトークンの範囲と、元の字句解析器を参照できる。
+#[derive(Debug, Clone)]
+pub struct TokenStream<T: Iterator + Spanned> {
+    t: T,
+    peeked: Option<Option<(T::Item, Span)>>,
+    span: Span,
+}
+
+impl<T: Iterator + Spanned> TokenStream<T> {
+    pub fn new(t: T) -> Self {
+        let span = t.latest_span();
+        Self {
+            t,
+            peeked: None,
+            span,
+        }
+    }
+
+    fn fill(&mut self) {
+        if self.peeked.is_none() {
+            let token = self.t.next();
+            let span = self.t.latest_span();
+            self.peeked = Some(token.map(|token| (token, span)));
+        }
+    }
+
+    pub fn peek(&mut self) -> Option<&T::Item> {
+        self.fill();
+        match &self.peeked {
+            Some(Some((token, _))) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// 最後に消費したトークンの範囲
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// 次のトークンの範囲。トークンが残っていない場合は、入力の終わりを指す
+    pub fn peek_span(&mut self) -> Span {
+        self.fill();
+        match &self.peeked {
+            Some(Some((_, span))) => *span,
+            _ => self.t.latest_span(),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.t
+    }
+}
+
+impl<T: Iterator + Spanned> Iterator for TokenStream<T> {
+    type Item = T::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fill();
+        match self.peeked.take() {
+            Some(Some((token, span))) => {
+                self.span = span;
+                Some(token)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// エラーを、ソース上の位置の順に並べる
+pub fn sort_errors(errors: &mut [ParseError]) {
+    errors.sort_by_key(|e| e.span().start());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_map() {
+        let input: Vec<char> = "ab\ncd\n\ne".chars().collect();
+        let map = LineMap::new(&input);
+        assert_eq!(SourcePosition::new(1, 1), map.position(0));
+        assert_eq!(SourcePosition::new(1, 3), map.position(2));
+        assert_eq!(SourcePosition::new(2, 1), map.position(3));
+        assert_eq!(SourcePosition::new(2, 2), map.position(4));
+        assert_eq!(SourcePosition::new(3, 1), map.position(6));
+        assert_eq!(SourcePosition::new(4, 1), map.position(7));
+        // 入力の終わり
+        assert_eq!(SourcePosition::new(4, 2), map.position(8));
+    }
+
+    #[test]
+    fn test_offset_by() {
+        let base = SourcePosition::new(3, 8);
+        assert_eq!(
+            SourcePosition::new(3, 10),
+            SourcePosition::new(1, 3).offset_by(base)
+        );
+        assert_eq!(
+            SourcePosition::new(5, 3),
+            SourcePosition::new(3, 3).offset_by(base)
+        );
+    }
+}
@@ -1,11 +1,9 @@
 use core::cell::RefCell;
 
 use alloc::string::String;
-use alloc::vec::Vec;
 use alloc::{rc::Rc, string::ToString};
 
 use super::node::Node;
-use crate::renderer::dom::element::Element;
 use crate::renderer::dom::element::ElementKind;
 use crate::renderer::dom::node::NodeKind;
 
@@ -15,12 +13,8 @@ pub fn get_target_element_node(
 ) -> Option<Rc<RefCell<Node>>> {
     match node {
         Some(n) => {
-            if n.borrow().kind()
-                == NodeKind::Element(Element::new(
-                    &element_kind.to_string(),
-                    Vec::new(),
-                ))
-            {
+            // 属性の有無に関わらず、要素の種類で比較する
+            if n.borrow().element_kind() == Some(element_kind) {
                 return Some(n.clone());
             }
             // 深さ優先探索で探す
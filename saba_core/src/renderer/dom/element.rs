@@ -1,37 +1,137 @@
 use crate::renderer::html::attribute::Attribute;
 use alloc::format;
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::fmt::{Display, Formatter};
 use core::str::FromStr;
 
+/// 要素
+/// kind: サポートしているHTMLの要素の場合の種類。タグ名で比較せずに済むようにするために使う。それ以外の要素はNone
+/// local_name: タグ名。<div>の場合は"div"
+/// namespace: 要素の名前空間
+/// https://dom.spec.whatwg.org/#concept-element
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Element {
-    pub(crate) kind: ElementKind,
+    pub(crate) kind: Option<ElementKind>,
+    local_name: String,
+    namespace: Namespace,
     attributes: Vec<Attribute>,
 }
 
 impl Element {
+    /// HTMLの名前空間の要素を作る
     pub fn new(element_name: &str, attributes: Vec<Attribute>) -> Self {
+        Self::new_with_namespace(Namespace::Html, element_name, attributes)
+    }
+
+    pub fn new_with_namespace(
+        namespace: Namespace,
+        local_name: &str,
+        attributes: Vec<Attribute>,
+    ) -> Self {
+        // SVGの<a>などは、HTMLの<a>とは別の要素として扱う
+        let kind = match namespace {
+            Namespace::Html => ElementKind::from_str(local_name).ok(),
+            _ => None,
+        };
         Self {
-            kind: ElementKind::from_str(element_name)
-                .expect("failed to convert string to ElementKind"),
+            kind,
+            local_name: local_name.to_string(),
+            namespace,
             attributes,
         }
     }
 
-    pub fn kind(&self) -> ElementKind {
+    pub fn kind(&self) -> Option<ElementKind> {
         self.kind
     }
 
+    pub fn local_name(&self) -> String {
+        self.local_name.clone()
+    }
+
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    /// デフォルトでdisplay: blockになる要素かどうか
+    /// https://html.spec.whatwg.org/multipage/rendering.html#flow-content-3
     pub fn is_block_element(&self) -> bool {
-        match self.kind() {
-            ElementKind::Body
-            | ElementKind::H1
-            | ElementKind::H2
-            | ElementKind::P => true,
-            _ => false,
+        if self.namespace != Namespace::Html {
+            return false;
+        }
+        matches!(
+            self.local_name.as_str(),
+            "address"
+                | "article"
+                | "aside"
+                | "blockquote"
+                | "body"
+                | "center"
+                | "dd"
+                | "details"
+                | "dialog"
+                | "dir"
+                | "div"
+                | "dl"
+                | "dt"
+                | "fieldset"
+                | "figcaption"
+                | "figure"
+                | "footer"
+                | "form"
+                | "h1"
+                | "h2"
+                | "h3"
+                | "h4"
+                | "h5"
+                | "h6"
+                | "header"
+                | "hgroup"
+                | "hr"
+                | "legend"
+                | "li"
+                | "listing"
+                | "main"
+                | "menu"
+                | "nav"
+                | "ol"
+                | "p"
+                | "plaintext"
+                | "pre"
+                | "search"
+                | "section"
+                | "summary"
+                | "ul"
+                | "xmp"
+        )
+    }
+
+    /// 描画しない要素かどうか
+    /// https://html.spec.whatwg.org/multipage/rendering.html#hidden-elements
+    pub fn is_hidden_element(&self) -> bool {
+        if self.namespace != Namespace::Html {
+            return false;
         }
+        matches!(
+            self.local_name.as_str(),
+            "area"
+                | "base"
+                | "basefont"
+                | "datalist"
+                | "head"
+                | "link"
+                | "meta"
+                | "noembed"
+                | "noframes"
+                | "param"
+                | "rp"
+                | "script"
+                | "style"
+                | "template"
+                | "title"
+        )
     }
 
     pub fn attributes(&self) -> Vec<Attribute> {
@@ -48,10 +148,31 @@ impl Element {
     }
 }
 
+/// 要素の名前空間
+/// https://infra.spec.whatwg.org/#namespaces
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+}
+
+impl Namespace {
+    pub fn url(&self) -> &'static str {
+        match self {
+            Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
+        }
+    }
+}
+
+/// サポートしているHTMLの要素の種類
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ElementKind {
     Html,
     Head,
+    Base,
     Style,
     Script,
     Body,
@@ -59,6 +180,12 @@ pub enum ElementKind {
     H1,
     H2,
     A,
+    Title,
+    Textarea,
+    Xmp,
+    Iframe,
+    Noembed,
+    Noframes,
 }
 
 impl FromStr for ElementKind {
@@ -68,6 +195,7 @@ impl FromStr for ElementKind {
         match s {
             "html" => Ok(ElementKind::Html),
             "head" => Ok(ElementKind::Head),
+            "base" => Ok(ElementKind::Base),
             "style" => Ok(ElementKind::Style),
             "script" => Ok(ElementKind::Script),
             "body" => Ok(ElementKind::Body),
@@ -75,6 +203,12 @@ impl FromStr for ElementKind {
             "h1" => Ok(ElementKind::H1),
             "h2" => Ok(ElementKind::H2),
             "a" => Ok(ElementKind::A),
+            "title" => Ok(ElementKind::Title),
+            "textarea" => Ok(ElementKind::Textarea),
+            "xmp" => Ok(ElementKind::Xmp),
+            "iframe" => Ok(ElementKind::Iframe),
+            "noembed" => Ok(ElementKind::Noembed),
+            "noframes" => Ok(ElementKind::Noframes),
             _ => Err(format!("unimplemented element name {:?}", s)),
         }
     }
@@ -85,6 +219,7 @@ impl Display for ElementKind {
         let s = match self {
             ElementKind::Html => "html",
             ElementKind::Head => "head",
+            ElementKind::Base => "base",
             ElementKind::Style => "style",
             ElementKind::Script => "script",
             ElementKind::Body => "body",
@@ -92,6 +227,12 @@ impl Display for ElementKind {
             ElementKind::H2 => "h2",
             ElementKind::P => "p",
             ElementKind::A => "a",
+            ElementKind::Title => "title",
+            ElementKind::Textarea => "textarea",
+            ElementKind::Xmp => "xmp",
+            ElementKind::Iframe => "iframe",
+            ElementKind::Noembed => "noembed",
+            ElementKind::Noframes => "noframes",
         };
         write!(f, "{}", s)
     }
@@ -2,6 +2,7 @@ use alloc::rc::{Rc, Weak};
 use alloc::string::String;
 use core::cell::RefCell;
 
+use crate::renderer::diagnostics::Span;
 use crate::renderer::dom::element::Element;
 use crate::renderer::dom::element::ElementKind;
 use crate::renderer::dom::window::Window;
@@ -12,11 +13,19 @@ use crate::renderer::dom::window::Window;
 /// Element: DOMツリーないの要素ノードとして動作する。Elementインタフェースでは、タグ名や、クラス名などの属性を取得する
 /// 関数を定義する。
 /// Text: 要素ないのテキストコンテンツを表す。
+/// Comment: <!-- -->で囲まれたコメントを表す。
+/// DocumentType: <!DOCTYPE html>のような文書型宣言を表す。
 #[derive(Debug, Clone)]
 pub enum NodeKind {
     Document,
     Element(Element),
     Text(String),
+    Comment(String),
+    DocumentType {
+        name: String,
+        public_id: String,
+        system_id: String,
+    },
 }
 
 impl PartialEq for NodeKind {
@@ -24,10 +33,17 @@ impl PartialEq for NodeKind {
         match &self {
             NodeKind::Document => matches!(other, NodeKind::Document),
             NodeKind::Element(e1) => match &other {
-                NodeKind::Element(e2) => e1.kind == e2.kind,
+                NodeKind::Element(e2) => {
+                    e1.namespace() == e2.namespace()
+                        && e1.local_name() == e2.local_name()
+                }
                 _ => false,
             },
             NodeKind::Text(_) => matches!(other, NodeKind::Text(_)),
+            NodeKind::Comment(_) => matches!(other, NodeKind::Comment(_)),
+            NodeKind::DocumentType { .. } => {
+                matches!(other, NodeKind::DocumentType { .. })
+            }
         }
     }
 }
@@ -40,6 +56,7 @@ impl PartialEq for NodeKind {
 /// last_child: ノードの最後の子ノード
 /// previous_sibling: ノードの前の兄弟のノード
 /// next_sibling: ノードの次の兄弟ノード(強い参照)
+/// source_span: ノードを作ったHTMLのソース上の範囲。要素の場合は開始タグの範囲。暗黙に作られたノードはNone
 /// RCの説明 https://doc.rust-jp.rs/book-ja/ch15-04-rc.html
 /// 強い参照は、所有権を持つ
 #[derive(Debug, Clone)]
@@ -51,6 +68,7 @@ pub struct Node {
     last_child: Weak<RefCell<Node>>,
     previous_sibling: Weak<RefCell<Node>>,
     next_sibling: Option<Rc<RefCell<Node>>>,
+    source_span: Option<Span>,
 }
 
 impl Node {
@@ -63,6 +81,7 @@ impl Node {
             last_child: Weak::new(),
             previous_sibling: Weak::new(),
             next_sibling: None,
+            source_span: None,
         }
     }
 
@@ -113,21 +132,29 @@ impl Node {
         self.next_sibling.as_ref().cloned()
     }
 
+    pub fn set_source_span(&mut self, source_span: Option<Span>) {
+        self.source_span = source_span;
+    }
+
+    pub fn source_span(&self) -> Option<Span> {
+        self.source_span
+    }
+
     pub fn kind(&self) -> NodeKind {
         self.kind.clone()
     }
 
     pub fn get_element(&self) -> Option<Element> {
         match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
             NodeKind::Element(ref e) => Some(e.clone()),
+            _ => None,
         }
     }
 
     pub fn element_kind(&self) -> Option<ElementKind> {
         match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
-            NodeKind::Element(ref e) => Some(e.kind()),
+            NodeKind::Element(ref e) => e.kind(),
+            _ => None,
         }
     }
 }
@@ -1,12 +1,27 @@
+use crate::renderer::dom::element::ElementKind;
 use crate::renderer::dom::node::Node;
 use crate::renderer::dom::node::NodeKind;
+use crate::url::Url;
 use alloc::rc::Rc;
+use alloc::string::String;
 use core::cell::RefCell;
 
+/// ドキュメントのモード。DOCTYPEによって決まり、古いブラウザとの互換性のための挙動を切り替える
+/// https://dom.spec.whatwg.org/#concept-document-mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuirksMode {
+    NoQuirks,
+    Quirks,
+    LimitedQuirks,
+}
+
 /// DOMツリーのルートを持ち、1つのWebページに対して1つのインスタンスが存在する。
 #[derive(Debug, Clone)]
 pub struct Window {
     document: Rc<RefCell<Node>>,
+    // ドキュメントを取得したURL
+    url: Option<Url>,
+    quirks_mode: QuirksMode,
 }
 
 impl Window {
@@ -14,6 +29,8 @@ impl Window {
         /// DOMツリーのルートノード(ElementKind::Document)を持つように実装を行う。
         let window = Self {
             document: Rc::new(RefCell::new(Node::new(NodeKind::Document))),
+            url: None,
+            quirks_mode: QuirksMode::NoQuirks,
         };
         // node.windowに自分の弱い参照を持つようにする。
         window
@@ -26,4 +43,56 @@ impl Window {
     pub fn document(&self) -> Rc<RefCell<Node>> {
         self.document.clone()
     }
+
+    pub fn set_url(&mut self, url: Url) {
+        self.url = Some(url);
+    }
+
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    pub fn set_quirks_mode(&mut self, quirks_mode: QuirksMode) {
+        self.quirks_mode = quirks_mode;
+    }
+
+    pub fn quirks_mode(&self) -> QuirksMode {
+        self.quirks_mode
+    }
+
+    /// 相対URLの基準になるURL。最初の<base href>をドキュメントのURLで解決したもので、
+    /// <base href>がない場合はドキュメントのURL
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#document-base-url
+    pub fn base_url(&self) -> Option<Url> {
+        let href = get_base_href(Some(self.document.clone()));
+
+        match (href, &self.url) {
+            (Some(href), Some(url)) => url.resolve(&href).ok().or(self.url()),
+            (Some(href), None) => Url::parse(&href).ok(),
+            (None, _) => self.url(),
+        }
+    }
+
+    /// リンクなどのURLを、ベースURLを基準にして解決する
+    pub fn resolve_url(&self, url: &str) -> Result<Url, String> {
+        match self.base_url() {
+            Some(base) => base.resolve(url),
+            None => Url::parse(url),
+        }
+    }
+}
+
+// href属性を持つ最初の<base>のhrefを、深さ優先探索で探す
+// <base target>のようにhrefを持たない<base>は無視する
+fn get_base_href(node: Option<Rc<RefCell<Node>>>) -> Option<String> {
+    let n = node?;
+    if n.borrow().element_kind() == Some(ElementKind::Base) {
+        let href = n.borrow().get_element()?.get_attribute("href");
+        if href.is_some() {
+            return href;
+        }
+    }
+    let first_child = n.borrow().first_child();
+    let next_sibling = n.borrow().next_sibling();
+    get_base_href(first_child).or_else(|| get_base_href(next_sibling))
 }
@@ -1,3 +1,4 @@
 pub mod attribute;
+pub mod entities;
 pub mod parser;
 pub mod token;
@@ -1,11 +1,18 @@
+use crate::renderer::diagnostics::sort_errors;
+use crate::renderer::diagnostics::ParseError;
+use crate::renderer::diagnostics::SourceKind;
+use crate::renderer::diagnostics::Spanned;
 use crate::renderer::dom::element::Element;
 use crate::renderer::dom::element::ElementKind;
+use crate::renderer::dom::element::Namespace;
 use crate::renderer::dom::node::Node;
 use crate::renderer::dom::node::NodeKind;
+use crate::renderer::dom::window::QuirksMode;
 use crate::renderer::dom::window::Window;
 use crate::renderer::html::attribute::Attribute;
 use crate::renderer::html::token::HtmlToken;
 use crate::renderer::html::token::HtmlTokenizer;
+use crate::renderer::html::token::State;
 
 use alloc::rc::Rc;
 use alloc::string::String;
@@ -33,6 +40,8 @@ pub struct HtmlParser {
     original_insertion_mode: InsertionMode,
     stack_of_open_elements: Vec<Rc<RefCell<Node>>>,
     t: HtmlTokenizer,
+    // 木構築中に見つかったエラー
+    errors: Vec<ParseError>,
 }
 
 impl HtmlParser {
@@ -43,9 +52,29 @@ impl HtmlParser {
             original_insertion_mode: InsertionMode::Initial,
             stack_of_open_elements: Vec::new(),
             t,
+            errors: Vec::new(),
         }
     }
 
+    /// 字句解析と木構築で見つかったエラーを、ソース上の位置の順に返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        let mut errors = self.t.errors();
+        errors.extend(self.errors.iter().cloned());
+        sort_errors(&mut errors);
+        errors
+    }
+
+    /// 処理中のトークンの位置でエラーを記録する
+    /// 木構築のエラーは仕様にコードがないため、字句解析のエラーコードと同じ形式の名前を使う
+    /// https://html.spec.whatwg.org/multipage/parsing.html#tree-construction
+    fn parse_error(&mut self, code: &str) {
+        self.errors.push(ParseError::new(
+            SourceKind::Html,
+            String::from(code),
+            self.t.latest_span(),
+        ));
+    }
+
     fn contain_in_stack(&mut self, element_kind: ElementKind) -> bool {
         for i in 0..self.stack_of_open_elements.len() {
             if self.stack_of_open_elements[i].borrow().element_kind()
@@ -101,10 +130,17 @@ impl HtmlParser {
             None => return,
         };
 
-        if let NodeKind::Text(ref mut s) = current.borrow_mut().kind {
-            // 今の一番最深のノードがテキストノードの場合はそちらに文字を追加する
-            s.push(c);
-            return;
+        {
+            let mut node = current.borrow_mut();
+            if let NodeKind::Text(ref mut s) = node.kind {
+                // 今の一番最深のノードがテキストノードの場合はそちらに文字を追加し、範囲を広げる
+                s.push(c);
+                let span = node
+                    .source_span()
+                    .map(|span| span.to(self.t.latest_span()));
+                node.set_source_span(span);
+                return;
+            }
         }
 
         if c == '\n' || c == ' ' {
@@ -114,27 +150,121 @@ impl HtmlParser {
         // stack_of_open_elementsの最後がテキストノードでない場合は、新しいテキストノードを作成し、
         // そのノードをcurrentの子ノードとして追加する。
         let new_text_node = Rc::new(RefCell::new(self.create_char(c)));
-        if current.borrow().first_child().is_some() {
-            current
-                .borrow()
-                .first_child()
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(new_text_node.clone()));
-        } else {
-            current.borrow_mut().set_first_child(Some(new_text_node.clone()));
-        }
-        current.borrow_mut().set_last_child(Rc::downgrade(&new_text_node));
-        new_text_node.borrow_mut().set_parent(Rc::downgrade(&current));
+        new_text_node
+            .borrow_mut()
+            .set_source_span(Some(self.t.latest_span()));
+        append_child(&current, &new_text_node);
 
         self.stack_of_open_elements.push(new_text_node);
     }
 
-    fn create_element(&self, tag: &str, attributes: Vec<Attribute>) -> Node {
-        Node::new(NodeKind::Element(Element::new(tag, attributes)))
+    /// <title>や<style>のように、中身をテキストとして扱う要素を挿入する
+    /// 字句解析の状態を切り替え、終了タグまでの中身を文字トークンとして受け取る。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-elements-that-contain-only-text
+    fn insert_text_element(&mut self, tag: &str, attributes: Vec<Attribute>) {
+        let state = match tag {
+            // RCDATAでは文字参照を解釈する
+            "title" | "textarea" => State::Rcdata,
+            "script" => State::ScriptData,
+            _ => State::Rawtext,
+        };
+        self.insert_element(tag, attributes);
+        self.t.switch_to(state);
+        self.original_insertion_mode = self.mode;
+        self.mode = InsertionMode::Text;
+    }
+
+    /// コメントノードを挿入する
+    /// parentがNoneの場合は、現在のノード(スタックが空の場合はドキュメント)の最後の子にする。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-comment
+    fn insert_comment(
+        &mut self,
+        data: &str,
+        parent: Option<Rc<RefCell<Node>>>,
+    ) {
+        let parent = match parent {
+            Some(parent) => parent,
+            None => {
+                // テキストノードはスタックから取り除き、コメントの後の文字は新しいテキストノードにする
+                let is_text = match self.stack_of_open_elements.last() {
+                    Some(n) => matches!(n.borrow().kind, NodeKind::Text(_)),
+                    None => false,
+                };
+                if is_text {
+                    self.stack_of_open_elements.pop();
+                }
+                match self.stack_of_open_elements.last() {
+                    Some(n) => n.clone(),
+                    None => self.window.borrow().document(),
+                }
+            }
+        };
+
+        let comment =
+            Rc::new(RefCell::new(Node::new(NodeKind::Comment(data.into()))));
+        comment
+            .borrow_mut()
+            .set_source_span(Some(self.t.latest_span()));
+        append_child(&parent, &comment);
+    }
+
+    /// DOCTYPEトークンから文書型宣言のノードを作ってドキュメントに追加し、ドキュメントのモードを決める
+    fn insert_doctype(
+        &mut self,
+        name: &Option<String>,
+        public_id: &Option<String>,
+        system_id: &Option<String>,
+        force_quirks: bool,
+    ) {
+        let document = self.window.borrow().document();
+        let doctype =
+            Rc::new(RefCell::new(Node::new(NodeKind::DocumentType {
+                name: name.clone().unwrap_or_default(),
+                public_id: public_id.clone().unwrap_or_default(),
+                system_id: system_id.clone().unwrap_or_default(),
+            })));
+        doctype
+            .borrow_mut()
+            .set_source_span(Some(self.t.latest_span()));
+        append_child(&document, &doctype);
+
+        let quirks_mode =
+            quirks_mode_from_doctype(name, public_id, system_id, force_quirks);
+        self.window.borrow_mut().set_quirks_mode(quirks_mode);
+    }
+
+    fn create_element(
+        &self,
+        namespace: Namespace,
+        tag: &str,
+        attributes: Vec<Attribute>,
+    ) -> Node {
+        Node::new(NodeKind::Element(Element::new_with_namespace(
+            namespace, tag, attributes,
+        )))
     }
 
     fn insert_element(&mut self, tag: &str, attributes: Vec<Attribute>) {
+        self.insert_element_with_namespace(Namespace::Html, tag, attributes);
+    }
+
+    /// <svg>や<math>とその中の要素のように、HTML以外の名前空間の要素を挿入する
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-foreign-element
+    fn insert_element_with_namespace(
+        &mut self,
+        namespace: Namespace,
+        tag: &str,
+        attributes: Vec<Attribute>,
+    ) {
+        // テキストノードはスタックから取り除き、要素はテキストノードの兄弟にする
+        let is_text = match self.stack_of_open_elements.last() {
+            Some(n) => matches!(n.borrow().kind, NodeKind::Text(_)),
+            None => false,
+        };
+        if is_text {
+            self.stack_of_open_elements.pop();
+        }
+
         let window = self.window.borrow();
 
         // currentノードを取得する。この時、スタックにないということは、ルートノード(window.document)を取る
@@ -143,43 +273,135 @@ impl HtmlParser {
             None => window.document(),
         };
 
-        let new_elem_node =
-            Rc::new(RefCell::new(self.create_element(tag, attributes)));
+        let new_elem_node = Rc::new(RefCell::new(
+            self.create_element(namespace, tag, attributes),
+        ));
+        new_elem_node
+            .borrow_mut()
+            .set_source_span(Some(self.t.latest_span()));
+        append_child(&current, &new_elem_node);
+        self.stack_of_open_elements.push(new_elem_node);
+    }
 
-        if current.borrow().first_child().is_some() {
-            // 子要素がある場合
-            // 子要素の兄弟を辿っていき、末っ子まで移動する。
-            let mut last_sibling = current.borrow().first_child();
-            loop {
-                last_sibling = match last_sibling {
-                    Some(ref node) => {
-                        // 子要素の兄弟がある場合は、それをlast_siblingとしてさらに、その兄弟を探す。
-                        if node.borrow().next_sibling().is_some() {
-                            node.borrow().next_sibling()
-                        } else {
-                            break;
-                        }
-                    }
-                    None => unimplemented!("last_sibling should be Some"),
+    /// <br>や<img>のように子を持たない要素を挿入し、すぐにスタックから取り除く
+    /// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+    fn insert_void_element(&mut self, tag: &str, attributes: Vec<Attribute>) {
+        self.insert_element(tag, attributes);
+        self.stack_of_open_elements.pop();
+    }
+
+    /// 終了タグと同じ名前の、開いている要素までをスタックから取り除く
+    /// 間に特殊な要素がある場合は、終了タグを無視する。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody の"Any other end tag"
+    fn close_element(&mut self, tag: &str) {
+        for i in (0..self.stack_of_open_elements.len()).rev() {
+            // テキストノードは読み飛ばす
+            let element =
+                match self.stack_of_open_elements[i].borrow().get_element() {
+                    Some(e) => e,
+                    None => continue,
                 };
+            if element.local_name() == tag {
+                self.stack_of_open_elements.truncate(i);
+                return;
             }
+            if is_special_element(&element.local_name()) {
+                break;
+            }
+        }
+        self.parse_error("end-tag-without-matching-open-element");
+    }
 
-            // 一番最後に見つかった末っ子の下に、兄弟として追加する。
-            last_sibling
-                .as_ref()
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(new_elem_node.clone()));
-            new_elem_node.borrow_mut().set_previous_sibling(Rc::downgrade(
-                &last_sibling.expect("last_sibling should be Some"),
-            ))
-        } else {
-            // currentに子ノードが一つつもない場合は、新しいノードをfirst_childとしてセットする。
-            current.borrow_mut().set_first_child(Some(new_elem_node.clone()));
+    // 現在の要素がSVGやMathMLの要素の場合、その名前空間を返す
+    // <foreignObject>のようなHTMLの統合ポイントの中はHTMLとして扱うため、Noneを返す
+    // https://html.spec.whatwg.org/multipage/parsing.html#html-integration-point
+    fn current_foreign_namespace(&self) -> Option<Namespace> {
+        let element = self
+            .stack_of_open_elements
+            .iter()
+            .rev()
+            .find_map(|n| n.borrow().get_element())?;
+        match element.namespace() {
+            Namespace::Html => None,
+            Namespace::Svg
+                if matches!(
+                    element.local_name().as_str(),
+                    "foreignobject" | "desc" | "title"
+                ) =>
+            {
+                None
+            }
+            namespace => Some(namespace),
+        }
+    }
+
+    /// SVGやMathMLの要素の中のトークンを処理する
+    /// 処理した場合はtrueを返す。falseの場合は、HTMLの要素と同じように処理する。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+    fn process_foreign_content(&mut self, token: &HtmlToken) -> bool {
+        let namespace = match self.current_foreign_namespace() {
+            Some(namespace) => namespace,
+            None => return false,
+        };
+
+        match token {
+            HtmlToken::StartTag {
+                tag,
+                self_closing,
+                attributes,
+            } => {
+                // <p>などのHTMLの要素が出てきた場合は、外部コンテンツを閉じる
+                if is_breakout_element(tag) {
+                    self.parse_error(
+                        "unexpected-html-element-in-foreign-content",
+                    );
+                    while self.current_foreign_namespace().is_some() {
+                        self.stack_of_open_elements.pop();
+                    }
+                    return false;
+                }
+                self.insert_element_with_namespace(
+                    namespace,
+                    tag,
+                    attributes.to_vec(),
+                );
+                // 外部コンテンツでは、"/>"で閉じた要素は子を持たない
+                if *self_closing {
+                    self.stack_of_open_elements.pop();
+                }
+                true
+            }
+            HtmlToken::EndTag { tag } => {
+                for i in (0..self.stack_of_open_elements.len()).rev() {
+                    let element = match self.stack_of_open_elements[i]
+                        .borrow()
+                        .get_element()
+                    {
+                        Some(e) => e,
+                        None => continue,
+                    };
+                    // HTMLの要素まで戻った場合は、HTMLの終了タグとして処理する
+                    if element.namespace() == Namespace::Html {
+                        return false;
+                    }
+                    if element.local_name() == *tag {
+                        self.stack_of_open_elements.truncate(i);
+                        return true;
+                    }
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// 開始タグがないまま、暗黙に作られる要素を挿入する
+    /// ソース上に対応する範囲はないため、範囲は持たない。
+    fn insert_implied_element(&mut self, tag: &str) {
+        self.insert_element(tag, Vec::new());
+        if let Some(node) = self.stack_of_open_elements.last() {
+            node.borrow_mut().set_source_span(None);
         }
-        current.borrow_mut().set_last_child(Rc::downgrade(&new_elem_node));
-        new_elem_node.borrow_mut().set_parent(Rc::downgrade(&current));
-        self.stack_of_open_elements.push(new_elem_node);
     }
 
     /// HTMLのパースを行い、DOMツリーを構築する
@@ -188,14 +410,43 @@ impl HtmlParser {
 
         while token.is_some() {
             match self.mode {
+                // https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
                 InsertionMode::Initial => {
-                    // DOCTYPEトークンをサポートしていないため、<!doctype html>のようなトークンは文字トークンとして扱う
-                    // この状態での文字トークンは無視する。
-                    if let Some(HtmlToken::Char(_)) = token {
-                        token = self.t.next();
-                        continue;
+                    match token {
+                        Some(HtmlToken::Char(c)) if is_whitespace(c) => {
+                            token = self.t.next();
+                            continue;
+                        }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let document = self.window.borrow().document();
+                            self.insert_comment(data, Some(document));
+                            token = self.t.next();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype {
+                            ref name,
+                            ref public_id,
+                            ref system_id,
+                            force_quirks,
+                        }) => {
+                            self.insert_doctype(
+                                name,
+                                public_id,
+                                system_id,
+                                force_quirks,
+                            );
+                            self.mode = InsertionMode::BeforeHtml;
+                            token = self.t.next();
+                            continue;
+                        }
+                        _ => {}
                     }
 
+                    // DOCTYPEがない場合は、quirksモードにする
+                    self.parse_error("missing-doctype");
+                    self.window
+                        .borrow_mut()
+                        .set_quirks_mode(QuirksMode::Quirks);
                     self.mode = InsertionMode::BeforeHtml;
                     continue;
                 }
@@ -223,22 +474,37 @@ impl HtmlParser {
                             }
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
-                            if tag != "head"
-                                || tag != "body"
-                                || tag != "html"
-                                || tag != "br"
-                            {
+                            // </head>, </body>, </html>, </br>は、それ以外のトークンと同じように処理する
+                            if !matches!(
+                                tag.as_str(),
+                                "head" | "body" | "html" | "br"
+                            ) {
+                                self.parse_error(
+                                    "end-tag-without-matching-open-element",
+                                );
                                 token = self.t.next();
                                 continue;
                             }
                         }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let document = self.window.borrow().document();
+                            self.insert_comment(data, Some(document));
+                            token = self.t.next();
+                            continue;
+                        }
+                        // 2つ目以降のDOCTYPEは無視する
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.parse_error("misplaced-doctype");
+                            token = self.t.next();
+                            continue;
+                        }
                         Some(HtmlToken::Eof) | None => {
                             return self.window.clone();
                         }
                     }
                     // charで、空白や開業以外の時は、htmlを追加する
                     // starttagが、html以外の時は、htmlタグを追加する
-                    self.insert_element("html", Vec::new());
+                    self.insert_implied_element("html");
                     self.mode = InsertionMode::BeforeHead;
                     continue;
                 }
@@ -263,12 +529,22 @@ impl HtmlParser {
                                 continue;
                             }
                         }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data, None);
+                            token = self.t.next();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.parse_error("misplaced-doctype");
+                            token = self.t.next();
+                            continue;
+                        }
                         Some(HtmlToken::Eof) | None => {
                             return self.window.clone();
                         }
                         _ => {}
                     }
-                    self.insert_element("head", Vec::new());
+                    self.insert_implied_element("head");
                     self.mode = InsertionMode::InHead;
                     continue;
                 }
@@ -287,25 +563,47 @@ impl HtmlParser {
                             self_closing: _,
                             ref attributes,
                         }) => {
-                            if tag == "style" || tag == "script" {
-                                self.insert_element(tag, attributes.to_vec());
-                                self.original_insertion_mode = self.mode;
-                                self.mode = InsertionMode::Text;
+                            // <base>や<meta>は子を持たないため、すぐにスタックから取り除く
+                            if matches!(
+                                tag.as_str(),
+                                "base"
+                                    | "basefont"
+                                    | "bgsound"
+                                    | "link"
+                                    | "meta"
+                            ) {
+                                self.insert_void_element(
+                                    tag,
+                                    attributes.to_vec(),
+                                );
                                 token = self.t.next();
                                 continue;
                             }
 
-                            if tag == "body" {
-                                self.pop_until(ElementKind::Head);
-                                self.mode = InsertionMode::AfterHead;
+                            if matches!(
+                                tag.as_str(),
+                                "title" | "style" | "noframes" | "script"
+                            ) {
+                                self.insert_text_element(
+                                    tag,
+                                    attributes.to_vec(),
+                                );
+                                token = self.t.next();
                                 continue;
                             }
-                            if let Ok(_element_kind) = ElementKind::from_str(tag)
-                            {
-                                self.pop_until(ElementKind::Head);
-                                self.mode = InsertionMode::AfterHead;
+
+                            // 2つ目の<head>は無視する
+                            if tag == "head" {
+                                self.parse_error(
+                                    "misplaced-start-tag-for-head-element",
+                                );
+                                token = self.t.next();
                                 continue;
                             }
+                            // それ以外の開始タグは<head>を閉じてから処理する
+                            self.pop_until(ElementKind::Head);
+                            self.mode = InsertionMode::AfterHead;
+                            continue;
                         }
 
                         Some(HtmlToken::EndTag { ref tag }) => {
@@ -315,12 +613,27 @@ impl HtmlParser {
                                 self.pop_until(ElementKind::Head);
                                 continue;
                             }
+                            if !matches!(tag.as_str(), "body" | "html" | "br") {
+                                self.parse_error(
+                                    "end-tag-without-matching-open-element",
+                                );
+                            }
+                        }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data, None);
+                            token = self.t.next();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.parse_error("misplaced-doctype");
+                            token = self.t.next();
+                            continue;
                         }
                         Some(HtmlToken::Eof) | None => {
                             return self.window.clone()
                         }
                     }
-                    // <meta>や<title>などのサポートしていないタグは無視する。
+                    // それ以外の終了タグは無視する。
                     token = self.t.next();
                     continue;
                 }
@@ -347,21 +660,38 @@ impl HtmlParser {
                                 continue;
                             }
                         }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data, None);
+                            token = self.t.next();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.parse_error("misplaced-doctype");
+                            token = self.t.next();
+                            continue;
+                        }
                         Some(HtmlToken::Eof) | None => {
                             return self.window.clone();
                         }
                         _ => {}
                     }
-                    self.insert_element("body", Vec::new());
+                    self.insert_implied_element("body");
                     self.mode = InsertionMode::InBody;
                     continue;
                 }
 
                 InsertionMode::InBody => {
+                    if let Some(ref t) = token {
+                        if self.process_foreign_content(t) {
+                            token = self.t.next();
+                            continue;
+                        }
+                    }
+
                     match token {
                         Some(HtmlToken::StartTag {
                             ref tag,
-                            self_closing: _,
+                            self_closing,
                             ref attributes,
                         }) => match tag.as_str() {
                             "p" | "h1" | "h2" | "a" => {
@@ -369,20 +699,82 @@ impl HtmlParser {
                                 token = self.t.next();
                                 continue;
                             }
+                            // <xmp>は開いている<p>を閉じる
+                            "xmp" => {
+                                if self.contain_in_stack(ElementKind::P) {
+                                    self.pop_until(ElementKind::P);
+                                }
+                                self.insert_text_element(
+                                    tag,
+                                    attributes.to_vec(),
+                                );
+                                token = self.t.next();
+                                continue;
+                            }
+                            "title" | "style" | "noframes" | "script"
+                            | "textarea" | "iframe" | "noembed" => {
+                                self.insert_text_element(
+                                    tag,
+                                    attributes.to_vec(),
+                                );
+                                token = self.t.next();
+                                continue;
+                            }
+                            "svg" | "math" => {
+                                let namespace = if tag == "svg" {
+                                    Namespace::Svg
+                                } else {
+                                    Namespace::MathMl
+                                };
+                                self.insert_element_with_namespace(
+                                    namespace,
+                                    tag,
+                                    attributes.to_vec(),
+                                );
+                                if self_closing {
+                                    self.stack_of_open_elements.pop();
+                                }
+                                token = self.t.next();
+                                continue;
+                            }
+                            // <html>や<body>は既にあるため、無視する
+                            "html" | "head" | "body" => {
+                                self.parse_error("unexpected-start-tag");
+                                token = self.t.next();
+                                continue;
+                            }
+                            "area" | "base" | "basefont" | "bgsound" | "br"
+                            | "embed" | "hr" | "img" | "input" | "keygen"
+                            | "link" | "meta" | "param" | "source"
+                            | "track" | "wbr" => {
+                                self.insert_void_element(
+                                    tag,
+                                    attributes.to_vec(),
+                                );
+                                token = self.t.next();
+                                continue;
+                            }
+                            // それ以外の要素は、タグ名のまま挿入する
                             _ => {
+                                self.insert_element(tag, attributes.to_vec());
                                 token = self.t.next();
+                                continue;
                             }
                         },
                         Some(HtmlToken::EndTag { ref tag }) => {
                             match tag.as_str() {
                                 "body" => {
-                                    self.mode = InsertionMode::AfterBody;
-                                    token = self.t.next();
                                     if !self.contain_in_stack(ElementKind::Body)
                                     {
                                         // パースの失敗。トークンを無視する
+                                        self.parse_error(
+                                            "end-tag-without-matching-open-element",
+                                        );
+                                        token = self.t.next();
                                         continue;
                                     }
+                                    self.mode = InsertionMode::AfterBody;
+                                    token = self.t.next();
                                     self.pop_until(ElementKind::Body);
                                     continue;
                                 }
@@ -393,6 +785,9 @@ impl HtmlParser {
                                             ElementKind::Html
                                         ));
                                     } else {
+                                        self.parse_error(
+                                            "end-tag-without-matching-open-element",
+                                        );
                                         token = self.t.next();
                                     }
                                     continue;
@@ -405,11 +800,20 @@ impl HtmlParser {
                                     .expect(
                                         "faled to convert string to ElementKind",
                                     );
+                                    // 対応する開始タグがない終了タグは無視する
+                                    if !self.contain_in_stack(element_kind) {
+                                        self.parse_error(
+                                            "end-tag-without-matching-open-element",
+                                        );
+                                        token = self.t.next();
+                                        continue;
+                                    }
                                     token = self.t.next();
                                     self.pop_until(element_kind);
                                     continue;
                                 }
                                 _ => {
+                                    self.close_element(tag);
                                     token = self.t.next();
                                 }
                             }
@@ -422,20 +826,33 @@ impl HtmlParser {
                             token = self.t.next();
                             continue;
                         }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data, None);
+                            token = self.t.next();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.parse_error("misplaced-doctype");
+                            token = self.t.next();
+                            continue;
+                        }
                     }
                 }
 
                 InsertionMode::Text => {
                     match token {
                         Some(HtmlToken::Eof) | None => {
+                            self.parse_error(
+                                "eof-in-element-that-can-contain-only-text",
+                            );
                             return self.window.clone();
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
-                            if tag == "style" {
-                                self.pop_until(ElementKind::Style);
-                            }
-                            if tag == "script" {
-                                self.pop_until(ElementKind::Script);
+                            if let Ok(element_kind) = ElementKind::from_str(tag)
+                            {
+                                if self.contain_in_stack(element_kind) {
+                                    self.pop_until(element_kind);
+                                }
                             }
                             self.mode = self.original_insertion_mode;
                             token = self.t.next();
@@ -453,7 +870,20 @@ impl HtmlParser {
 
                 InsertionMode::AfterBody => {
                     match token {
-                        Some(HtmlToken::Char(_)) => {
+                        Some(HtmlToken::Char(c)) => {
+                            if !is_whitespace(c) {
+                                self.parse_error(
+                                    "unexpected-character-after-body",
+                                );
+                            }
+                            token = self.t.next();
+                            continue;
+                        }
+                        // </body>の後のコメントは、<html>の最後の子にする
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let html =
+                                self.stack_of_open_elements.first().cloned();
+                            self.insert_comment(data, html);
                             token = self.t.next();
                             continue;
                         }
@@ -469,12 +899,25 @@ impl HtmlParser {
                         }
                         _ => {}
                     }
+                    self.parse_error("unexpected-token-after-body");
                     self.mode = InsertionMode::InBody;
                 }
 
                 InsertionMode::AfterAfterBody => {
                     match token {
-                        Some(HtmlToken::Char(_)) => {
+                        Some(HtmlToken::Char(c)) => {
+                            if !is_whitespace(c) {
+                                self.parse_error(
+                                    "unexpected-character-after-html",
+                                );
+                            }
+                            token = self.t.next();
+                            continue;
+                        }
+                        // </html>の後のコメントは、ドキュメントの最後の子にする
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let document = self.window.borrow().document();
+                            self.insert_comment(data, Some(document));
                             token = self.t.next();
                             continue;
                         }
@@ -485,6 +928,7 @@ impl HtmlParser {
                     }
 
                     // failuer to parse
+                    self.parse_error("unexpected-token-after-html");
                     self.mode = InsertionMode::InBody;
                 }
             }
@@ -493,6 +937,299 @@ impl HtmlParser {
     }
 }
 
+/// SVGやMathMLの中に現れた場合に、外部コンテンツを閉じてHTMLの要素として扱う要素
+/// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+fn is_breakout_element(local_name: &str) -> bool {
+    matches!(
+        local_name,
+        "b" | "big"
+            | "blockquote"
+            | "body"
+            | "br"
+            | "center"
+            | "code"
+            | "dd"
+            | "div"
+            | "dl"
+            | "dt"
+            | "em"
+            | "embed"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "head"
+            | "hr"
+            | "i"
+            | "img"
+            | "li"
+            | "listing"
+            | "menu"
+            | "meta"
+            | "nobr"
+            | "ol"
+            | "p"
+            | "pre"
+            | "ruby"
+            | "s"
+            | "small"
+            | "span"
+            | "strong"
+            | "strike"
+            | "sub"
+            | "sup"
+            | "table"
+            | "tt"
+            | "u"
+            | "ul"
+            | "var"
+    )
+}
+
+/// 終了タグで閉じる要素を探すときに、それより外側を探さない要素
+/// https://html.spec.whatwg.org/multipage/parsing.html#special
+fn is_special_element(local_name: &str) -> bool {
+    matches!(
+        local_name,
+        "address"
+            | "applet"
+            | "area"
+            | "article"
+            | "aside"
+            | "base"
+            | "basefont"
+            | "bgsound"
+            | "blockquote"
+            | "body"
+            | "br"
+            | "button"
+            | "caption"
+            | "center"
+            | "col"
+            | "colgroup"
+            | "dd"
+            | "details"
+            | "dir"
+            | "div"
+            | "dl"
+            | "dt"
+            | "embed"
+            | "fieldset"
+            | "figcaption"
+            | "figure"
+            | "footer"
+            | "form"
+            | "frame"
+            | "frameset"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "head"
+            | "header"
+            | "hgroup"
+            | "hr"
+            | "html"
+            | "iframe"
+            | "img"
+            | "input"
+            | "keygen"
+            | "li"
+            | "link"
+            | "listing"
+            | "main"
+            | "marquee"
+            | "menu"
+            | "meta"
+            | "nav"
+            | "noembed"
+            | "noframes"
+            | "noscript"
+            | "object"
+            | "ol"
+            | "p"
+            | "param"
+            | "plaintext"
+            | "pre"
+            | "script"
+            | "search"
+            | "section"
+            | "select"
+            | "source"
+            | "style"
+            | "summary"
+            | "table"
+            | "tbody"
+            | "td"
+            | "template"
+            | "textarea"
+            | "tfoot"
+            | "th"
+            | "thead"
+            | "title"
+            | "tr"
+            | "track"
+            | "ul"
+            | "wbr"
+            | "xmp"
+    )
+}
+
+/// parentの最後の子としてchildを追加する
+fn append_child(parent: &Rc<RefCell<Node>>, child: &Rc<RefCell<Node>>) {
+    let first_child = parent.borrow().first_child();
+    match first_child {
+        Some(first_child) => {
+            // 子ノードがある場合は、兄弟を辿って末っ子まで移動し、その次の兄弟として追加する
+            let mut last_sibling = first_child;
+            loop {
+                let next = last_sibling.borrow().next_sibling();
+                match next {
+                    Some(next) => last_sibling = next,
+                    None => break,
+                }
+            }
+            last_sibling
+                .borrow_mut()
+                .set_next_sibling(Some(child.clone()));
+            child
+                .borrow_mut()
+                .set_previous_sibling(Rc::downgrade(&last_sibling));
+        }
+        None => {
+            // parentに子ノードが一つもない場合は、新しいノードをfirst_childとしてセットする。
+            parent.borrow_mut().set_first_child(Some(child.clone()));
+        }
+    }
+    parent.borrow_mut().set_last_child(Rc::downgrade(child));
+    child.borrow_mut().set_parent(Rc::downgrade(parent));
+}
+
+// 空白文字(タブ、改行、フォームフィード、復帰、スペース)かどうか
+fn is_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0c' | '\r' | ' ')
+}
+
+// DOCTYPEの公開識別子が、これらで始まる場合はquirksモードにする
+// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+const QUIRKS_PUBLIC_ID_PREFIXES: [&str; 55] = [
+    "+//Silmaril//dtd html Pro v0r11 19970101//",
+    "-//AS//DTD HTML 3.0 asWedit + extensions//",
+    "-//AdvaSoft Ltd//DTD HTML 3.0 asWedit + extensions//",
+    "-//IETF//DTD HTML 2.0 Level 1//",
+    "-//IETF//DTD HTML 2.0 Level 2//",
+    "-//IETF//DTD HTML 2.0 Strict Level 1//",
+    "-//IETF//DTD HTML 2.0 Strict Level 2//",
+    "-//IETF//DTD HTML 2.0 Strict//",
+    "-//IETF//DTD HTML 2.0//",
+    "-//IETF//DTD HTML 2.1E//",
+    "-//IETF//DTD HTML 3.0//",
+    "-//IETF//DTD HTML 3.2 Final//",
+    "-//IETF//DTD HTML 3.2//",
+    "-//IETF//DTD HTML 3//",
+    "-//IETF//DTD HTML Level 0//",
+    "-//IETF//DTD HTML Level 1//",
+    "-//IETF//DTD HTML Level 2//",
+    "-//IETF//DTD HTML Level 3//",
+    "-//IETF//DTD HTML Strict Level 0//",
+    "-//IETF//DTD HTML Strict Level 1//",
+    "-//IETF//DTD HTML Strict Level 2//",
+    "-//IETF//DTD HTML Strict Level 3//",
+    "-//IETF//DTD HTML Strict//",
+    "-//IETF//DTD HTML//",
+    "-//Metrius//DTD Metrius Presentational//",
+    "-//Microsoft//DTD Internet Explorer 2.0 HTML Strict//",
+    "-//Microsoft//DTD Internet Explorer 2.0 HTML//",
+    "-//Microsoft//DTD Internet Explorer 2.0 Tables//",
+    "-//Microsoft//DTD Internet Explorer 3.0 HTML Strict//",
+    "-//Microsoft//DTD Internet Explorer 3.0 HTML//",
+    "-//Microsoft//DTD Internet Explorer 3.0 Tables//",
+    "-//Netscape Comm. Corp.//DTD HTML//",
+    "-//Netscape Comm. Corp.//DTD Strict HTML//",
+    "-//O'Reilly and Associates//DTD HTML 2.0//",
+    "-//O'Reilly and Associates//DTD HTML Extended 1.0//",
+    "-//O'Reilly and Associates//DTD HTML Extended Relaxed 1.0//",
+    "-//SQ//DTD HTML 2.0 HoTMetaL + extensions//",
+    "-//SoftQuad Software//DTD HoTMetaL PRO 6.0::19990601::extensions to HTML 4.0//",
+    "-//SoftQuad//DTD HoTMetaL PRO 4.0::19971010::extensions to HTML 4.0//",
+    "-//Spyglass//DTD HTML 2.0 Extended//",
+    "-//Sun Microsystems Corp.//DTD HotJava HTML//",
+    "-//Sun Microsystems Corp.//DTD HotJava Strict HTML//",
+    "-//W3C//DTD HTML 3 1995-03-24//",
+    "-//W3C//DTD HTML 3.2 Draft//",
+    "-//W3C//DTD HTML 3.2 Final//",
+    "-//W3C//DTD HTML 3.2//",
+    "-//W3C//DTD HTML 3.2S Draft//",
+    "-//W3C//DTD HTML 4.0 Frameset//",
+    "-//W3C//DTD HTML 4.0 Transitional//",
+    "-//W3C//DTD HTML Experimental 19960712//",
+    "-//W3C//DTD HTML Experimental 970421//",
+    "-//W3C//DTD W3 HTML//",
+    "-//W3O//DTD W3 HTML 3.0//",
+    "-//WebTechs//DTD Mozilla HTML 2.0//",
+    "-//WebTechs//DTD Mozilla HTML//",
+];
+
+/// DOCTYPEトークンから、ドキュメントのモードを決める
+/// 識別子は、大文字と小文字を区別せずに比較する。
+/// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+fn quirks_mode_from_doctype(
+    name: &Option<String>,
+    public_id: &Option<String>,
+    system_id: &Option<String>,
+    force_quirks: bool,
+) -> QuirksMode {
+    let starts_with = |id: &str, prefix: &str| {
+        id.len() >= prefix.len()
+            && id.as_bytes()[..prefix.len()]
+                .eq_ignore_ascii_case(prefix.as_bytes())
+    };
+    let public = public_id.as_deref().unwrap_or("");
+    let system = system_id.as_deref().unwrap_or("");
+    let html401 = [
+        "-//W3C//DTD HTML 4.01 Frameset//",
+        "-//W3C//DTD HTML 4.01 Transitional//",
+    ];
+    let xhtml10 = [
+        "-//W3C//DTD XHTML 1.0 Frameset//",
+        "-//W3C//DTD XHTML 1.0 Transitional//",
+    ];
+
+    if force_quirks
+        || name.as_deref() != Some("html")
+        || [
+            "-//W3O//DTD W3 HTML Strict 3.0//EN//",
+            "-/W3C/DTD HTML 4.0 Transitional/EN",
+            "HTML",
+        ]
+        .iter()
+        .any(|id| public.eq_ignore_ascii_case(id))
+        || system.eq_ignore_ascii_case(
+            "http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd",
+        )
+        || QUIRKS_PUBLIC_ID_PREFIXES
+            .iter()
+            .any(|p| starts_with(public, p))
+        || (system_id.is_none()
+            && html401.iter().any(|p| starts_with(public, p)))
+    {
+        return QuirksMode::Quirks;
+    }
+
+    if xhtml10.iter().any(|p| starts_with(public, p))
+        || (system_id.is_some()
+            && html401.iter().any(|p| starts_with(public, p)))
+    {
+        return QuirksMode::LimitedQuirks;
+    }
+
+    QuirksMode::NoQuirks
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -796,4 +1533,454 @@ mod tests {
             body
         );
     }
+
+    fn parse(html: &str) -> Rc<RefCell<Window>> {
+        HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree()
+    }
+
+    fn is_comment(node: &Rc<RefCell<Node>>, data: &str) -> bool {
+        matches!(node.borrow().kind(), NodeKind::Comment(ref d) if d == data)
+    }
+
+    fn is_text(node: &Rc<RefCell<Node>>, text: &str) -> bool {
+        matches!(node.borrow().kind(), NodeKind::Text(ref t) if t == text)
+    }
+
+    #[test]
+    fn test_comment() {
+        let window = parse(
+            "<!--a--><html><body>x<!--b-->y</body><!--c--></html><!--d-->",
+        );
+        let document = window.borrow().document();
+
+        let comment_a = document
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of document");
+        assert!(is_comment(&comment_a, "a"));
+        let html = comment_a
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of the comment");
+        let comment_d = html
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of html");
+        assert!(is_comment(&comment_d, "d"));
+
+        let body = html
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of html")
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of head");
+        let x = body.borrow().first_child().expect("failed to get x");
+        let b = x.borrow().next_sibling().expect("failed to get a comment");
+        let y = b.borrow().next_sibling().expect("failed to get y");
+        assert!(is_text(&x, "x"));
+        assert!(is_comment(&b, "b"));
+        assert!(is_text(&y, "y"));
+
+        // </body>の後のコメントは、<html>の最後の子になる
+        let comment_c = html
+            .borrow()
+            .last_child()
+            .upgrade()
+            .expect("failed to get a last child of html");
+        assert!(is_comment(&comment_c, "c"));
+    }
+
+    #[test]
+    fn test_doctype() {
+        let window = parse("<!DOCTYPE html><html><body></body></html>");
+        let document = window.borrow().document();
+        let doctype = document
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of document");
+        assert!(matches!(
+            doctype.borrow().kind(),
+            NodeKind::DocumentType {
+                ref name,
+                ref public_id,
+                ref system_id,
+            } if name == "html" && public_id.is_empty() && system_id.is_empty()
+        ));
+        let html = doctype
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of doctype");
+        assert_eq!(Some(ElementKind::Html), html.borrow().element_kind());
+        assert_eq!(QuirksMode::NoQuirks, window.borrow().quirks_mode());
+    }
+
+    #[test]
+    fn test_quirks_mode() {
+        let mode = |html: &str| parse(html).borrow().quirks_mode();
+        assert_eq!(QuirksMode::NoQuirks, mode("<!DOCTYPE html>"));
+        assert_eq!(QuirksMode::NoQuirks, mode("  <!doctype html>"));
+        assert_eq!(
+            QuirksMode::NoQuirks,
+            mode("<!DOCTYPE html SYSTEM \"about:legacy-compat\">")
+        );
+        assert_eq!(
+            QuirksMode::NoQuirks,
+            mode(
+                "<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \
+                 \"http://www.w3.org/TR/html4/strict.dtd\">"
+            )
+        );
+
+        // DOCTYPEがない、または古いDOCTYPEの場合はquirksモード
+        assert_eq!(QuirksMode::Quirks, mode("<html></html>"));
+        assert_eq!(QuirksMode::Quirks, mode("<!DOCTYPE>"));
+        assert_eq!(QuirksMode::Quirks, mode("<!DOCTYPE svg>"));
+        assert_eq!(
+            QuirksMode::Quirks,
+            mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 3.2 Final//EN\">")
+        );
+        assert_eq!(
+            QuirksMode::Quirks,
+            mode(
+                "<!DOCTYPE html PUBLIC \
+                 \"-//w3c//dtd html 4.01 transitional//en\">"
+            )
+        );
+        assert_eq!(QuirksMode::Quirks, mode("<!DOCTYPE html PUBLIC \"html\">"));
+
+        assert_eq!(
+            QuirksMode::LimitedQuirks,
+            mode(
+                "<!DOCTYPE html PUBLIC \
+                 \"-//W3C//DTD HTML 4.01 Transitional//EN\" \
+                 \"http://www.w3.org/TR/html4/loose.dtd\">"
+            )
+        );
+        assert_eq!(
+            QuirksMode::LimitedQuirks,
+            mode(
+                "<!DOCTYPE html PUBLIC \
+                 \"-//W3C//DTD XHTML 1.0 Transitional//EN\">"
+            )
+        );
+    }
+
+    // 要素の最初の子のテキスト
+    fn text_of(node: &Rc<RefCell<Node>>) -> String {
+        match node.borrow().first_child() {
+            Some(child) => match child.borrow().kind() {
+                NodeKind::Text(t) => t,
+                _ => String::new(),
+            },
+            None => String::new(),
+        }
+    }
+
+    #[test]
+    fn test_text_elements_in_head() {
+        let window = parse(
+            "<html><head><title>a<b>&amp;</title>\
+             <style>p > a { color: red; }</style></head><body></body></html>",
+        );
+        let document = window.borrow().document();
+        let head = document
+            .borrow()
+            .first_child()
+            .expect("failed to get html")
+            .borrow()
+            .first_child()
+            .expect("failed to get head");
+
+        let title = head.borrow().first_child().expect("failed to get title");
+        assert_eq!(Some(ElementKind::Title), title.borrow().element_kind());
+        assert_eq!("a<b>&", text_of(&title));
+
+        let style = title.borrow().next_sibling().expect("failed to get style");
+        assert_eq!(Some(ElementKind::Style), style.borrow().element_kind());
+        assert_eq!("p > a { color: red; }", text_of(&style));
+    }
+
+    #[test]
+    fn test_text_elements_in_body() {
+        let window = parse(
+            "<html><head></head><body><textarea><p>&lt;</textarea>\
+             <p>a<xmp><b></xmp><iframe><p></iframe></body></html>",
+        );
+        let document = window.borrow().document();
+        let body = document
+            .borrow()
+            .first_child()
+            .expect("failed to get html")
+            .borrow()
+            .first_child()
+            .expect("failed to get head")
+            .borrow()
+            .next_sibling()
+            .expect("failed to get body");
+
+        let textarea =
+            body.borrow().first_child().expect("failed to get textarea");
+        assert_eq!(
+            Some(ElementKind::Textarea),
+            textarea.borrow().element_kind()
+        );
+        assert_eq!("<p><", text_of(&textarea));
+
+        // <xmp>は<p>を閉じるため、<p>の兄弟になる
+        let p = textarea.borrow().next_sibling().expect("failed to get p");
+        assert_eq!(Some(ElementKind::P), p.borrow().element_kind());
+        let xmp = p.borrow().next_sibling().expect("failed to get xmp");
+        assert_eq!(Some(ElementKind::Xmp), xmp.borrow().element_kind());
+        assert_eq!("<b>", text_of(&xmp));
+
+        let iframe = xmp.borrow().next_sibling().expect("failed to get iframe");
+        assert_eq!(Some(ElementKind::Iframe), iframe.borrow().element_kind());
+        assert_eq!("<p>", text_of(&iframe));
+    }
+
+    fn span_of(
+        node: &Rc<RefCell<Node>>,
+    ) -> Option<((usize, usize), (usize, usize))> {
+        node.borrow().source_span().map(|span| {
+            (
+                (span.start().line(), span.start().column()),
+                (span.end().line(), span.end().column()),
+            )
+        })
+    }
+
+    #[test]
+    fn test_source_span() {
+        let window = parse("<html>\n<body><p>ab\ncd</p></body></html>");
+        let document = window.borrow().document();
+        let html = document.borrow().first_child().expect("failed to get html");
+        assert_eq!(Some(((1, 1), (1, 7))), span_of(&html));
+
+        // 省略された<head>はソース上に存在しない
+        let head = html.borrow().first_child().expect("failed to get head");
+        assert_eq!(None, span_of(&head));
+
+        let body = head.borrow().next_sibling().expect("failed to get body");
+        assert_eq!(Some(((2, 1), (2, 7))), span_of(&body));
+        let p = body.borrow().first_child().expect("failed to get p");
+        assert_eq!(Some(((2, 7), (2, 10))), span_of(&p));
+
+        // テキストノードの範囲は、含まれる全ての文字にわたる
+        let text = p.borrow().first_child().expect("failed to get text");
+        assert_eq!(Some(((2, 10), (3, 3))), span_of(&text));
+    }
+
+    #[test]
+    fn test_tree_construction_errors() {
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(
+            "<p>a</h1></p></p>\n<!DOCTYPE html>".to_string(),
+        ));
+        let window = parser.construct_tree();
+
+        let errors: Vec<(String, usize, usize)> = parser
+            .errors()
+            .iter()
+            .map(|e| {
+                (
+                    e.message(),
+                    e.span().start().line(),
+                    e.span().start().column(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("missing-doctype".to_string(), 1, 1),
+                ("end-tag-without-matching-open-element".to_string(), 1, 5),
+                ("end-tag-without-matching-open-element".to_string(), 1, 14),
+                ("misplaced-doctype".to_string(), 2, 1),
+            ],
+            errors
+        );
+
+        // エラーがあっても木の構築は続けられる
+        let body = window
+            .borrow()
+            .document()
+            .borrow()
+            .first_child()
+            .expect("failed to get html")
+            .borrow()
+            .first_child()
+            .expect("failed to get head")
+            .borrow()
+            .next_sibling()
+            .expect("failed to get body");
+        let p = body.borrow().first_child().expect("failed to get p");
+        assert_eq!("a", text_of(&p));
+
+        // <html>の前の</head>はエラーにならないが、他の終了タグは無視してエラーにする
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(
+            "<!DOCTYPE html></x></head><p>b</p>".to_string(),
+        ));
+        parser.construct_tree();
+        let errors: Vec<(String, usize, usize)> = parser
+            .errors()
+            .iter()
+            .map(|e| {
+                (
+                    e.message(),
+                    e.span().start().line(),
+                    e.span().start().column(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![("end-tag-without-matching-open-element".to_string(), 1, 16)],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_generic_elements() {
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(
+            "<!DOCTYPE html><head><meta charset=utf-8></head><body><div>a<span>b</span><br>c</i></div><my-tag></my-tag></body>"
+                .to_string(),
+        ));
+        let window = parser.construct_tree();
+        let html = window
+            .borrow()
+            .document()
+            .borrow()
+            .first_child()
+            .expect("failed to get doctype")
+            .borrow()
+            .next_sibling()
+            .expect("failed to get html");
+        let head = html.borrow().first_child().expect("failed to get head");
+        let meta = head.borrow().first_child().expect("failed to get meta");
+        let element = meta.borrow().get_element().expect("meta is an element");
+        assert_eq!("meta", element.local_name());
+        assert_eq!(None, element.kind());
+        assert_eq!(Some("utf-8".to_string()), element.get_attribute("charset"));
+
+        let body = head.borrow().next_sibling().expect("failed to get body");
+        let div = body.borrow().first_child().expect("failed to get div");
+        assert_eq!(
+            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
+                "div",
+                Vec::new()
+            ))))),
+            div
+        );
+
+        // 要素はテキストノードの兄弟として挿入され、<br>は子を持たない
+        let a = div.borrow().first_child().expect("failed to get text");
+        assert!(is_text(&a, "a"));
+        let span = a.borrow().next_sibling().expect("failed to get span");
+        assert_eq!(
+            Some("span".to_string()),
+            span.borrow().get_element().map(|e| e.local_name())
+        );
+        assert_eq!("b", text_of(&span));
+        let br = span.borrow().next_sibling().expect("failed to get br");
+        assert_eq!(
+            Some("br".to_string()),
+            br.borrow().get_element().map(|e| e.local_name())
+        );
+        assert!(br.borrow().first_child().is_none());
+        let c = br.borrow().next_sibling().expect("failed to get text");
+        assert!(is_text(&c, "c"));
+
+        let my_tag = div.borrow().next_sibling().expect("failed to get my-tag");
+        assert_eq!(
+            Some("my-tag".to_string()),
+            my_tag.borrow().get_element().map(|e| e.local_name())
+        );
+
+        // 開いていない要素の終了タグは無視する
+        let messages: Vec<String> =
+            parser.errors().iter().map(|e| e.message()).collect();
+        assert_eq!(
+            vec!["end-tag-without-matching-open-element".to_string()],
+            messages
+        );
+    }
+
+    #[test]
+    fn test_foreign_elements() {
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(
+            "<!DOCTYPE html><body><svg><a href=x><circle/></a><foreignObject><p>t</p></foreignObject></svg><a href=y>l</a><math><mi>x</mi><p>b</p></body>"
+                .to_string(),
+        ));
+        let window = parser.construct_tree();
+        let body = window
+            .borrow()
+            .document()
+            .borrow()
+            .first_child()
+            .expect("failed to get doctype")
+            .borrow()
+            .next_sibling()
+            .expect("failed to get html")
+            .borrow()
+            .first_child()
+            .expect("failed to get head")
+            .borrow()
+            .next_sibling()
+            .expect("failed to get body");
+        let element_of = |node: &Rc<RefCell<Node>>| {
+            let element =
+                node.borrow().get_element().expect("node is not an element");
+            (element.namespace(), element.local_name(), element.kind())
+        };
+
+        let svg = body.borrow().first_child().expect("failed to get svg");
+        assert_eq!((Namespace::Svg, "svg".to_string(), None), element_of(&svg));
+        // SVGの<a>は、HTMLの<a>とは別の要素になる
+        let svg_a = svg.borrow().first_child().expect("failed to get a");
+        assert_eq!((Namespace::Svg, "a".to_string(), None), element_of(&svg_a));
+        let circle =
+            svg_a.borrow().first_child().expect("failed to get circle");
+        assert_eq!(
+            (Namespace::Svg, "circle".to_string(), None),
+            element_of(&circle)
+        );
+        assert!(circle.borrow().first_child().is_none());
+
+        // <foreignObject>の中はHTMLの要素になる
+        let foreign_object = svg_a
+            .borrow()
+            .next_sibling()
+            .expect("failed to get foreignObject");
+        let p = foreign_object
+            .borrow()
+            .first_child()
+            .expect("failed to get p");
+        assert_eq!(
+            (Namespace::Html, "p".to_string(), Some(ElementKind::P)),
+            element_of(&p)
+        );
+
+        let a = svg.borrow().next_sibling().expect("failed to get a");
+        assert_eq!(
+            (Namespace::Html, "a".to_string(), Some(ElementKind::A)),
+            element_of(&a)
+        );
+
+        let math = a.borrow().next_sibling().expect("failed to get math");
+        assert_eq!(
+            (Namespace::MathMl, "math".to_string(), None),
+            element_of(&math)
+        );
+        let mi = math.borrow().first_child().expect("failed to get mi");
+        assert_eq!(
+            (Namespace::MathMl, "mi".to_string(), None),
+            element_of(&mi)
+        );
+
+        // <p>はMathMLの要素を閉じて、<body>の子になる
+        let p = math.borrow().next_sibling().expect("failed to get p");
+        assert_eq!(
+            (Namespace::Html, "p".to_string(), Some(ElementKind::P)),
+            element_of(&p)
+        );
+        assert_eq!(1, parser.errors().len());
+    }
 }
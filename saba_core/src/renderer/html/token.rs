@@ -1,9 +1,18 @@
+use crate::renderer::diagnostics::LineMap;
+use crate::renderer::diagnostics::ParseError;
+use crate::renderer::diagnostics::SourceKind;
+use crate::renderer::diagnostics::Span;
+use crate::renderer::diagnostics::Spanned;
 use crate::renderer::html::attribute::Attribute;
+use crate::renderer::html::entities::MAX_NAME_LENGTH;
+use crate::renderer::html::entities::NAMED_CHARACTER_REFERENCES;
+use alloc::collections::VecDeque;
+use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 
 /// 字句解析時の状態を表す列挙型
-/// 詳しくは、以下を参考にする。今回は、18種類を実装
+/// 詳しくは、以下を参考にする。今回は、60種類を実装
 /// https://html.spec.whatwg.org/multipage/parsing.html#tokenization
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum State {
@@ -20,11 +29,70 @@ pub enum State {
     AttributeValueUnquoted,
     AfterAttributeValueQuoted,
     SelfClosingStartTag,
+    Rcdata,
+    RcdataLessThanSign,
+    RcdataEndTagOpen,
+    RcdataEndTagName,
+    Rawtext,
+    RawtextLessThanSign,
+    RawtextEndTagOpen,
+    RawtextEndTagName,
     ScriptData,
     ScriptDataLessThanSign,
     ScriptDataEndTagOpen,
     ScriptDataEndTagName,
-    TemporaryBuffer,
+    CharacterReference,
+    NamedCharacterReference,
+    AmbiguousAmpersand,
+    NumericCharacterReference,
+    HexadecimalCharacterReferenceStart,
+    DecimalCharacterReferenceStart,
+    HexadecimalCharacterReference,
+    DecimalCharacterReference,
+    MarkupDeclarationOpen,
+    BogusComment,
+    CommentStart,
+    CommentStartDash,
+    Comment,
+    CommentEndDash,
+    CommentEnd,
+    CommentEndBang,
+    Doctype,
+    BeforeDoctypeName,
+    DoctypeName,
+    AfterDoctypeName,
+    AfterDoctypePublicKeyword,
+    BeforeDoctypePublicIdentifier,
+    DoctypePublicIdentifierDoubleQuoted,
+    DoctypePublicIdentifierSingleQuoted,
+    AfterDoctypePublicIdentifier,
+    BetweenDoctypePublicAndSystemIdentifiers,
+    AfterDoctypeSystemKeyword,
+    BeforeDoctypeSystemIdentifier,
+    DoctypeSystemIdentifierDoubleQuoted,
+    DoctypeSystemIdentifierSingleQuoted,
+    AfterDoctypeSystemIdentifier,
+    BogusDoctype,
+    CdataSection,
+    CdataSectionBracket,
+    CdataSectionEnd,
+}
+
+/// DOCTYPEトークンの識別子の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DoctypeIdentifier {
+    Public,
+    System,
+}
+
+impl DoctypeIdentifier {
+    // エラーコードで使う名前
+    fn name(&self) -> &'static str {
+        match self {
+            DoctypeIdentifier::Public => "public",
+            DoctypeIdentifier::System => "system",
+        }
+    }
 }
 
 /// 字句解析用の構造体
@@ -36,6 +104,23 @@ pub struct HtmlTokenizer {
     latest_token: Option<HtmlToken>,
     input: Vec<char>,
     buf: String,
+    // 文字参照の解析が終わった後に戻る状態
+    return_state: State,
+    // 数値文字参照の値
+    character_reference_code: u32,
+    // 文字参照の結果として、まだ返していない文字
+    pending_chars: VecDeque<char>,
+    // 最後に返した開始タグの名前。RCDATAなどの状態で、終了タグかどうかを判定するために使う
+    last_start_tag: Option<String>,
+    // "<![CDATA["をCDATAセクションとして扱うかどうか
+    // HTMLの要素の中では、CDATAセクションはコメントとして扱われる
+    cdata_allowed: bool,
+    // 入力の位置を行と列に変換するための表
+    line_map: LineMap,
+    // 最後に返したトークンのソース上の範囲
+    latest_span: Span,
+    // 字句解析中に見つかったエラー
+    errors: Vec<ParseError>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -52,6 +137,16 @@ pub enum HtmlToken {
     },
     //文字
     Char(char),
+    // コメント
+    Comment(String),
+    // DOCTYPE
+    // https://html.spec.whatwg.org/multipage/parsing.html#tokenization
+    Doctype {
+        name: Option<String>,
+        public_id: Option<String>,
+        system_id: Option<String>,
+        force_quirks: bool,
+    },
     // ファイルの終了(End of File)
     Eof,
 }
@@ -62,25 +157,78 @@ impl HtmlTokenizer {
     /// # Arguments
     /// * `html` - 解析対象のHTMLの文字列
     pub fn new(html: String) -> Self {
+        let input: Vec<char> = html.chars().collect();
+        let line_map = LineMap::new(&input);
         Self {
             state: State::Data,
             pos: 0,
             reconsume: false,
             latest_token: None,
-            input: html.chars().collect(),
+            input,
             buf: String::new(),
+            return_state: State::Data,
+            character_reference_code: 0,
+            pending_chars: VecDeque::new(),
+            last_start_tag: None,
+            cdata_allowed: false,
+            latest_span: line_map.span(0, 0),
+            line_map,
+            errors: Vec::new(),
         }
     }
 
+    /// 字句解析中に見つかったエラーを返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        self.errors.clone()
+    }
+
+    // 次に消費する文字の位置。再消費する場合は、最後に消費した文字の位置になる
+    fn current_offset(&self) -> usize {
+        let pos = if self.reconsume {
+            self.pos - 1
+        } else {
+            self.pos
+        };
+        core::cmp::min(pos, self.input.len())
+    }
+
+    /// 最後に消費した文字の位置でエラーを記録する
+    /// codeは仕様で定義されたエラーコード
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+    fn parse_error(&mut self, code: &str) {
+        let end = core::cmp::min(self.pos, self.input.len());
+        let start = core::cmp::min(self.pos.saturating_sub(1), end);
+        self.errors.push(ParseError::new(
+            SourceKind::Html,
+            String::from(code),
+            self.line_map.span(start, end),
+        ));
+    }
+
+    /// 字句解析の状態を切り替える
+    /// <title>や<style>のように、中身をタグとして解釈しない要素の開始タグの後に、パーサから呼ばれる。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-elements-that-contain-only-text
+    pub fn switch_to(&mut self, state: State) {
+        self.state = state;
+    }
+
+    /// "<![CDATA["をCDATAセクションとして扱うかどうかを設定する
+    /// SVGやMathMLのような、HTML以外の名前空間の要素の中でのみ有効にする。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#markup-declaration-open-state
+    pub fn set_cdata_allowed(&mut self, cdata_allowed: bool) {
+        self.cdata_allowed = cdata_allowed;
+    }
+
+    // 入力の最後に達した場合は'\0'を返し、is_eofで判定できるようにposだけ進める
     fn consume_next_input(&mut self) -> char {
-        let c = self.input[self.pos];
+        let c = self.input.get(self.pos).copied().unwrap_or('\0');
         self.pos += 1;
         c
     }
 
     fn reconsume_input(&mut self) -> char {
         self.reconsume = false;
-        self.input[self.pos - 1]
+        self.input.get(self.pos - 1).copied().unwrap_or('\0')
     }
 
     fn is_eof(&self) -> bool {
@@ -125,9 +273,24 @@ impl HtmlTokenizer {
         let t = self.latest_token.as_ref().cloned();
         self.latest_token = None;
         assert!(self.latest_token.is_none());
+
+        if let Some(HtmlToken::StartTag { ref tag, .. }) = t {
+            self.last_start_tag = Some(tag.clone());
+        }
         t
     }
 
+    /// 作成中の終了タグが、最後に返した開始タグと同じ名前かどうか
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
+    fn is_appropriate_end_tag(&self) -> bool {
+        match (&self.latest_token, &self.last_start_tag) {
+            (Some(HtmlToken::EndTag { tag }), Some(last_start_tag)) => {
+                tag == last_start_tag
+            }
+            _ => false,
+        }
+    }
+
     fn start_new_attribute(&mut self) {
         assert!(self.latest_token.is_some());
 
@@ -138,6 +301,8 @@ impl HtmlTokenizer {
                     self_closing: _,
                     ref mut attributes,
                 } => attributes.push(Attribute::new()),
+                // 終了タグの属性は無視する
+                HtmlToken::EndTag { .. } => {}
                 _ => panic!("`latest_token` should be either StartTag."),
             }
         }
@@ -157,11 +322,200 @@ impl HtmlTokenizer {
 
                     attributes[len - 1].add_char(c, is_name);
                 }
+                HtmlToken::EndTag { .. } => {}
                 _ => panic!("`latest_token` should be either StartTag."),
             }
         }
     }
 
+    /// 文字参照の解析を始める。解析が終わったらreturn_stateに戻る
+    fn start_character_reference(&mut self, return_state: State) {
+        self.return_state = return_state;
+        self.buf = String::from("&");
+        self.state = State::CharacterReference;
+    }
+
+    /// 属性値の中の文字参照を解析しているかどうか
+    /// https://html.spec.whatwg.org/multipage/parsing.html#charref-in-attribute
+    fn is_consumed_as_part_of_attribute(&self) -> bool {
+        matches!(
+            self.return_state,
+            State::AttributeValueDoubleQuoted
+                | State::AttributeValueSingleQuoted
+                | State::AttributeValueUnquoted
+        )
+    }
+
+    /// 一時バッファの文字を、属性値に追加するか文字トークンとして返す
+    /// 文字トークンの場合は最初の1文字を返し、残りはpending_charsに入れて次回以降に返す。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#flush-code-points-consumed-as-a-character-reference
+    fn flush_character_reference(&mut self) -> Option<HtmlToken> {
+        let buf = core::mem::take(&mut self.buf);
+        if self.is_consumed_as_part_of_attribute() {
+            for c in buf.chars() {
+                self.append_attribute(c, false);
+            }
+            return None;
+        }
+
+        self.pending_chars.extend(buf.chars());
+        self.pending_chars.pop_front().map(HtmlToken::Char)
+    }
+
+    /// startの位置から始まる、最も長い名前付き文字参照の名前を探す
+    /// 見つかった場合は、名前の長さと置き換える文字列を返す。
+    fn match_named_character_reference(
+        &self,
+        start: usize,
+    ) -> Option<(usize, &'static str)> {
+        let end = core::cmp::min(start + MAX_NAME_LENGTH, self.input.len());
+        let candidate: String = self.input[start..end].iter().collect();
+        // 名前は英数字と末尾の";"だけからなる
+        let mut len = 0;
+        for c in candidate.chars() {
+            if c.is_ascii_alphanumeric() {
+                len += 1;
+                continue;
+            }
+            if c == ';' {
+                len += 1;
+            }
+            break;
+        }
+
+        (1..=len).rev().find_map(|l| {
+            NAMED_CHARACTER_REFERENCES
+                .binary_search_by(|(name, _)| name.cmp(&&candidate[..l]))
+                .ok()
+                .map(|i| (l, NAMED_CHARACTER_REFERENCES[i].1))
+        })
+    }
+
+    /// 数値文字参照の値を文字にして、一時バッファに入れる
+    /// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+    fn numeric_character_reference_end(&mut self) -> Option<HtmlToken> {
+        match self.character_reference_code {
+            0 => self.parse_error("null-character-reference"),
+            0xD800..=0xDFFF => {
+                self.parse_error("surrogate-character-reference")
+            }
+            0x110000.. => {
+                self.parse_error("character-reference-outside-unicode-range")
+            }
+            _ => {}
+        }
+        let c =
+            numeric_character_reference_to_char(self.character_reference_code);
+        self.buf = String::new();
+        self.buf.push(c);
+        self.state = self.return_state.clone();
+        self.flush_character_reference()
+    }
+
+    /// 最後に消費した文字から、sが続いているかどうか
+    /// 続いている場合は、sの最後の文字まで消費する。
+    fn consume_if_next_is(&mut self, s: &str, ignore_case: bool) -> bool {
+        let start = self.pos - 1;
+        let len = s.chars().count();
+        if start + len > self.input.len() {
+            return false;
+        }
+
+        let matched = self.input[start..start + len].iter().zip(s.chars()).all(
+            |(a, b)| {
+                if ignore_case {
+                    a.eq_ignore_ascii_case(&b)
+                } else {
+                    *a == b
+                }
+            },
+        );
+        if matched {
+            self.pos = start + len;
+        }
+        matched
+    }
+
+    fn create_comment(&mut self, data: &str) {
+        self.latest_token = Some(HtmlToken::Comment(String::from(data)));
+    }
+
+    fn append_comment(&mut self, s: &str) {
+        assert!(self.latest_token.is_some());
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Comment(ref mut data)) => data.push_str(s),
+            _ => panic!("`latest_token` should be Comment."),
+        }
+    }
+
+    fn create_doctype(&mut self) {
+        self.latest_token = Some(HtmlToken::Doctype {
+            name: None,
+            public_id: None,
+            system_id: None,
+            force_quirks: false,
+        });
+    }
+
+    fn append_doctype_name(&mut self, c: char) {
+        assert!(self.latest_token.is_some());
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype { ref mut name, .. }) => {
+                name.get_or_insert_with(String::new).push(c)
+            }
+            _ => panic!("`latest_token` should be Doctype."),
+        }
+    }
+
+    /// DOCTYPEの公開識別子かシステム識別子を、空文字列にする
+    fn start_doctype_identifier(&mut self, identifier: DoctypeIdentifier) {
+        assert!(self.latest_token.is_some());
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype {
+                ref mut public_id,
+                ref mut system_id,
+                ..
+            }) => match identifier {
+                DoctypeIdentifier::Public => *public_id = Some(String::new()),
+                DoctypeIdentifier::System => *system_id = Some(String::new()),
+            },
+            _ => panic!("`latest_token` should be Doctype."),
+        }
+    }
+
+    fn append_doctype_identifier(
+        &mut self,
+        c: char,
+        identifier: DoctypeIdentifier,
+    ) {
+        assert!(self.latest_token.is_some());
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype {
+                ref mut public_id,
+                ref mut system_id,
+                ..
+            }) => {
+                let id = match identifier {
+                    DoctypeIdentifier::Public => public_id,
+                    DoctypeIdentifier::System => system_id,
+                };
+                id.get_or_insert_with(String::new).push(c);
+            }
+            _ => panic!("`latest_token` should be Doctype."),
+        }
+    }
+
+    fn set_force_quirks_flag(&mut self) {
+        assert!(self.latest_token.is_some());
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype {
+                ref mut force_quirks,
+                ..
+            }) => *force_quirks = true,
+            _ => panic!("`latest_token` should be Doctype."),
+        }
+    }
+
     fn set_self_closing_flag(&mut self) {
         assert!(self.latest_token.is_some());
         if let Some(t) = self.latest_token.as_mut() {
@@ -171,6 +525,7 @@ impl HtmlTokenizer {
                     ref mut self_closing,
                     attributes: _,
                 } => *self_closing = true,
+                HtmlToken::EndTag { .. } => {}
                 _ => panic!("`latest_token` should be either StartTag."),
             }
         }
@@ -181,7 +536,30 @@ impl Iterator for HtmlTokenizer {
     type Item = HtmlToken;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.input.len() {
+        // 文字参照などから作られた残りの文字は、同じ範囲を持つとする
+        let pending = !self.pending_chars.is_empty();
+        let start = self.current_offset();
+        let token = self.consume_token();
+        if !pending {
+            self.latest_span = self.line_map.span(start, self.current_offset());
+        }
+        token
+    }
+}
+
+impl Spanned for HtmlTokenizer {
+    fn latest_span(&self) -> Span {
+        self.latest_span
+    }
+}
+
+impl HtmlTokenizer {
+    fn consume_token(&mut self) -> Option<HtmlToken> {
+        if let Some(c) = self.pending_chars.pop_front() {
+            return Some(HtmlToken::Char(c));
+        }
+
+        if self.pos >= self.input.len() && !self.reconsume {
             return None;
         }
 
@@ -194,6 +572,11 @@ impl Iterator for HtmlTokenizer {
             match self.state {
                 // Data State
                 State::Data => {
+                    if c == '&' {
+                        self.start_character_reference(State::Data);
+                        continue;
+                    }
+
                     if c == '<' {
                         self.state = State::TagOpen;
                         continue;
@@ -207,6 +590,22 @@ impl Iterator for HtmlTokenizer {
                 }
 
                 State::TagOpen => {
+                    if c == '!' {
+                        self.state = State::MarkupDeclarationOpen;
+                        continue;
+                    }
+
+                    // "<?xml ...>"のような処理命令は、コメントとして扱う
+                    if c == '?' {
+                        self.parse_error(
+                            "unexpected-question-mark-instead-of-tag-name",
+                        );
+                        self.create_comment("");
+                        self.reconsume = true;
+                        self.state = State::BogusComment;
+                        continue;
+                    }
+
                     if c == '/' {
                         // '</'形のため、エンドタグを取得する状態に遷移
                         self.state = State::EndTagOpen;
@@ -221,15 +620,18 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-before-tag-name");
                         return Some(HtmlToken::Eof);
                     }
 
+                    self.parse_error("invalid-first-character-of-tag-name");
                     self.reconsume = true;
                     self.state = State::Data;
                 }
 
                 State::EndTagOpen => {
                     if self.is_eof() {
+                        self.parse_error("eof-before-tag-name");
                         return Some(HtmlToken::Eof);
                     }
 
@@ -239,6 +641,18 @@ impl Iterator for HtmlTokenizer {
                         self.create_tag(false);
                         continue;
                     }
+
+                    // "</>"は無視する
+                    if c == '>' {
+                        self.parse_error("missing-end-tag-name");
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    self.parse_error("invalid-first-character-of-tag-name");
+                    self.create_comment("");
+                    self.reconsume = true;
+                    self.state = State::BogusComment;
                 }
 
                 State::TagName => {
@@ -263,6 +677,7 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
 
@@ -320,6 +735,7 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
 
@@ -352,7 +768,14 @@ impl Iterator for HtmlTokenizer {
                         self.state = State::AfterAttributeValueQuoted;
                         continue;
                     }
+                    if c == '&' {
+                        self.start_character_reference(
+                            State::AttributeValueDoubleQuoted,
+                        );
+                        continue;
+                    }
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
                     self.append_attribute(c, false);
@@ -363,7 +786,14 @@ impl Iterator for HtmlTokenizer {
                         self.state = State::AfterAttributeValueQuoted;
                         continue;
                     }
+                    if c == '&' {
+                        self.start_character_reference(
+                            State::AttributeValueSingleQuoted,
+                        );
+                        continue;
+                    }
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
                     self.append_attribute(c, false)
@@ -375,12 +805,20 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '&' {
+                        self.start_character_reference(
+                            State::AttributeValueUnquoted,
+                        );
+                        continue;
+                    }
+
                     if c == '>' {
                         self.state = State::Data;
                         return self.take_latest_token();
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
 
@@ -414,51 +852,89 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
+
+                    self.parse_error("unexpected-solidus-in-tag");
                 }
 
-                State::ScriptData => {
+                // RCDATA、RAWTEXT、スクリプトデータの状態は、適切な終了タグ以外の"<"を文字として扱う
+                // RCDATAの状態でのみ、文字参照を解釈する
+                // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-state
+                // https://html.spec.whatwg.org/multipage/parsing.html#rawtext-state
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-state
+                State::Rcdata | State::Rawtext | State::ScriptData => {
+                    if c == '&' && self.state == State::Rcdata {
+                        self.start_character_reference(State::Rcdata);
+                        continue;
+                    }
+
                     if c == '<' {
-                        self.state = State::ScriptDataLessThanSign;
+                        self.state = less_than_sign_state(&self.state);
                         continue;
                     }
 
                     if self.is_eof() {
                         return Some(HtmlToken::Eof);
                     }
-                    return Some(HtmlToken::Char(c));
+                    if c == '\0' {
+                        self.parse_error("unexpected-null-character");
+                    }
+                    return Some(HtmlToken::Char(replace_null(c)));
                 }
 
-                State::ScriptDataLessThanSign => {
+                State::RcdataLessThanSign
+                | State::RawtextLessThanSign
+                | State::ScriptDataLessThanSign => {
+                    let text_state = text_state(&self.state);
                     if c == '/' {
                         self.buf = String::new();
-                        self.state = State::ScriptDataEndTagOpen;
+                        self.state = end_tag_open_state(&text_state);
                         continue;
                     }
                     self.reconsume = true;
-                    self.state = State::ScriptData;
+                    self.state = text_state;
                     return Some(HtmlToken::Char('<'));
                 }
 
-                State::ScriptDataEndTagOpen => {
+                State::RcdataEndTagOpen
+                | State::RawtextEndTagOpen
+                | State::ScriptDataEndTagOpen => {
+                    let text_state = text_state(&self.state);
                     if c.is_ascii_alphabetic() {
                         self.reconsume = true;
-                        self.state = State::ScriptDataEndTagName;
+                        self.state = end_tag_name_state(&text_state);
                         self.create_tag(false);
                         continue;
                     }
 
+                    // "<"と"/"の2つの文字トークンを返す
                     self.reconsume = true;
-                    self.state = State::ScriptData;
-                    // 仕様では、"<"と"/"の2つの文字トークンを返すことになっているが、nextメソッドからは1つのトークンしか返せないため、"<"のみとする
+                    self.state = text_state;
+                    self.pending_chars.push_back('/');
                     return Some(HtmlToken::Char('<'));
                 }
 
-                State::ScriptDataEndTagName => {
-                    if c == '>' {
-                        self.state = State::Data;
-                        return self.take_latest_token();
+                State::RcdataEndTagName
+                | State::RawtextEndTagName
+                | State::ScriptDataEndTagName => {
+                    // 直前の開始タグと同じ名前の終了タグだけを、タグとして扱う
+                    if self.is_appropriate_end_tag() {
+                        if is_whitespace(c) {
+                            self.state = State::BeforeAttributeName;
+                            continue;
+                        }
+
+                        if c == '/' {
+                            self.state = State::SelfClosingStartTag;
+                            continue;
+                        }
+
+                        if c == '>' {
+                            self.state = State::Data;
+                            return self.take_latest_token();
+                        }
                     }
 
                     if c.is_ascii_alphabetic() {
@@ -467,121 +943,861 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    self.state = State::TemporaryBuffer;
-                    self.buf = String::from("</") + &self.buf;
-                    self.buf.push(c);
-                    continue;
+                    // 終了タグではなかったため、"</"とタグ名として読んだ文字を文字トークンとして返す
+                    self.latest_token = None;
+                    self.pending_chars.push_back('/');
+                    self.pending_chars.extend(self.buf.chars());
+                    self.buf = String::new();
+                    self.reconsume = true;
+                    self.state = text_state(&self.state);
+                    return Some(HtmlToken::Char('<'));
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state
+                State::CharacterReference => {
+                    if c.is_ascii_alphanumeric() {
+                        self.reconsume = true;
+                        self.state = State::NamedCharacterReference;
+                        continue;
+                    }
+
+                    if c == '#' {
+                        self.buf.push(c);
+                        self.state = State::NumericCharacterReference;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                    if let Some(t) = self.flush_character_reference() {
+                        return Some(t);
+                    }
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#named-character-reference-state
+                State::NamedCharacterReference => {
+                    let start = self.pos - 1;
+                    let (len, value) = match self
+                        .match_named_character_reference(start)
+                    {
+                        Some(m) => m,
+                        None => {
+                            // 一致する名前がない場合は"&"をそのまま出力する
+                            self.reconsume = true;
+                            self.state = State::AmbiguousAmpersand;
+                            if let Some(t) = self.flush_character_reference() {
+                                return Some(t);
+                            }
+                            continue;
+                        }
+                    };
+
+                    self.pos = start + len;
+                    let last = self.input[self.pos - 1];
+                    let next = self.input.get(self.pos).copied();
+                    // 属性値の中では、";"で終わらない名前の後に"="か英数字が続く場合は、
+                    // "?a=1&copy=2"のようなURLのために置き換えない
+                    if self.is_consumed_as_part_of_attribute()
+                        && last != ';'
+                        && next.is_some_and(|n| {
+                            n == '=' || n.is_ascii_alphanumeric()
+                        })
+                    {
+                        self.buf.extend(&self.input[start..self.pos]);
+                    } else {
+                        if last != ';' {
+                            self.parse_error(
+                                "missing-semicolon-after-character-reference",
+                            );
+                        }
+                        self.buf = String::from(value);
+                    }
+
+                    self.state = self.return_state.clone();
+                    if let Some(t) = self.flush_character_reference() {
+                        return Some(t);
+                    }
                 }
 
-                State::TemporaryBuffer => {
+                // https://html.spec.whatwg.org/multipage/parsing.html#ambiguous-ampersand-state
+                State::AmbiguousAmpersand => {
+                    if c.is_ascii_alphanumeric() {
+                        if self.is_consumed_as_part_of_attribute() {
+                            self.append_attribute(c, false);
+                            continue;
+                        }
+                        return Some(HtmlToken::Char(c));
+                    }
+
                     self.reconsume = true;
+                    self.state = self.return_state.clone();
+                }
 
-                    if self.buf.chars().count() == 0 {
-                        self.state = State::ScriptData;
+                // https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-state
+                State::NumericCharacterReference => {
+                    self.character_reference_code = 0;
+
+                    if c == 'x' || c == 'X' {
+                        self.buf.push(c);
+                        self.state = State::HexadecimalCharacterReferenceStart;
                         continue;
                     }
 
-                    //
-                    let c = self
-                        .buf
-                        .chars()
-                        .nth(0)
-                        .expect("self.buf should have at least char");
-                    self.buf.remove(0);
-                    return Some(HtmlToken::Char(c));
+                    self.reconsume = true;
+                    self.state = State::DecimalCharacterReferenceStart;
                 }
-            }
-        }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::alloc::string::ToString;
-    use alloc::vec;
+                State::HexadecimalCharacterReferenceStart => {
+                    if c.is_ascii_hexdigit() {
+                        self.reconsume = true;
+                        self.state = State::HexadecimalCharacterReference;
+                        continue;
+                    }
 
-    #[test]
-    fn test_empty() {
-        let html = "".to_string();
-        let mut tokenizer: HtmlTokenizer = HtmlTokenizer::new(html);
-        assert!(tokenizer.next().is_none());
-    }
+                    // "&#x"の後に16進数が続かない場合は、そのまま出力する
+                    self.parse_error(
+                        "absence-of-digits-in-numeric-character-reference",
+                    );
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                    if let Some(t) = self.flush_character_reference() {
+                        return Some(t);
+                    }
+                }
 
-    #[test]
-    fn test_start_and_end_tag() {
-        let html = "<body></body>".to_string();
-        let mut tokenizer = HtmlTokenizer::new(html);
-        let expected = [
-            HtmlToken::StartTag {
-                tag: "body".to_string(),
-                self_closing: false,
-                attributes: Vec::new(),
-            },
-            HtmlToken::EndTag {
-                tag: "body".to_string(),
-            },
-        ];
+                State::DecimalCharacterReferenceStart => {
+                    if c.is_ascii_digit() {
+                        self.reconsume = true;
+                        self.state = State::DecimalCharacterReference;
+                        continue;
+                    }
 
-        for e in expected {
-            assert_eq!(Some(e), tokenizer.next());
-        }
-    }
+                    self.parse_error(
+                        "absence-of-digits-in-numeric-character-reference",
+                    );
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                    if let Some(t) = self.flush_character_reference() {
+                        return Some(t);
+                    }
+                }
 
-    #[test]
-    fn test_attributes() {
-        let html = "<p class=\"A\" id = 'B' foo=bar></p>".to_string();
-        let mut tokenizer = HtmlTokenizer::new(html);
-        let mut attr1 = Attribute::new();
-        attr1.add_char('c', true);
-        attr1.add_char('l', true);
-        attr1.add_char('a', true);
-        attr1.add_char('s', true);
-        attr1.add_char('s', true);
-        attr1.add_char('A', false);
+                State::HexadecimalCharacterReference
+                | State::DecimalCharacterReference => {
+                    let radix = match self.state {
+                        State::HexadecimalCharacterReference => 16,
+                        _ => 10,
+                    };
+                    if let Some(d) = c.to_digit(radix) {
+                        // 大きすぎる値は、範囲外としてU+FFFDにするため0x110000で止める
+                        self.character_reference_code = core::cmp::min(
+                            self.character_reference_code
+                                .saturating_mul(radix)
+                                .saturating_add(d),
+                            0x110000,
+                        );
+                        continue;
+                    }
 
-        let mut attr2 = Attribute::new();
-        attr2.add_char('i', true);
-        attr2.add_char('d', true);
-        attr2.add_char('B', false);
+                    // ";"がない場合は、消費した文字を再消費する
+                    if c != ';' {
+                        self.parse_error(
+                            "missing-semicolon-after-character-reference",
+                        );
+                        self.reconsume = true;
+                    }
+                    if let Some(t) = self.numeric_character_reference_end() {
+                        return Some(t);
+                    }
+                }
 
-        let mut attr3 = Attribute::new();
-        attr3.add_char('f', true);
-        attr3.add_char('o', true);
-        attr3.add_char('o', true);
-        attr3.add_char('b', false);
-        attr3.add_char('a', false);
-        attr3.add_char('r', false);
+                // https://html.spec.whatwg.org/multipage/parsing.html#markup-declaration-open-state
+                State::MarkupDeclarationOpen => {
+                    if self.consume_if_next_is("--", false) {
+                        self.create_comment("");
+                        self.state = State::CommentStart;
+                        continue;
+                    }
 
-        let expected = [
-            HtmlToken::StartTag {
-                tag: "p".to_string(),
-                self_closing: false,
-                attributes: vec![attr1, attr2, attr3],
-            },
-            HtmlToken::EndTag {
-                tag: "p".to_string(),
-            },
-        ];
-        for e in expected {
-            assert_eq!(Some(e), tokenizer.next());
-        }
-    }
+                    if self.consume_if_next_is("DOCTYPE", true) {
+                        self.state = State::Doctype;
+                        continue;
+                    }
 
-    #[test]
-    fn test_self_closing_tag() {
-        let html = "<img />".to_string();
-        let mut tokenizer = HtmlTokenizer::new(html);
-        let expected = [HtmlToken::StartTag {
-            tag: "img".to_string(),
-            self_closing: true,
-            attributes: Vec::new(),
-        }];
+                    if self.consume_if_next_is("[CDATA[", false) {
+                        if self.cdata_allowed {
+                            self.state = State::CdataSection;
+                        } else {
+                            // HTMLの要素の中では、CDATAセクションはコメントになる
+                            self.create_comment("[CDATA[");
+                            self.state = State::BogusComment;
+                        }
+                        continue;
+                    }
 
-        for e in expected {
-            assert_eq!(Some(e), tokenizer.next());
-        }
-    }
+                    self.parse_error("incorrectly-opened-comment");
+                    self.create_comment("");
+                    self.reconsume = true;
+                    self.state = State::BogusComment;
+                }
+
+                State::BogusComment => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment(&String::from(replace_null(c)));
+                }
+
+                State::CommentStart => {
+                    if c == '-' {
+                        self.state = State::CommentStartDash;
+                        continue;
+                    }
+
+                    // "<!-->"は空のコメントとして扱う
+                    if c == '>' {
+                        self.parse_error("abrupt-closing-of-empty-comment");
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                State::CommentStartDash => {
+                    if c == '-' {
+                        self.state = State::CommentEnd;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.parse_error("abrupt-closing-of-empty-comment");
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-comment");
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment("-");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                // 仕様では、"<!--"の入れ子をエラーとして検出するための状態があるが、
+                // コメントの内容には影響しないため実装しない
+                State::Comment => {
+                    if c == '-' {
+                        self.state = State::CommentEndDash;
+                        continue;
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-comment");
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment(&String::from(replace_null(c)));
+                }
+
+                State::CommentEndDash => {
+                    if c == '-' {
+                        self.state = State::CommentEnd;
+                        continue;
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-comment");
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment("-");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                State::CommentEnd => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '!' {
+                        self.state = State::CommentEndBang;
+                        continue;
+                    }
+
+                    if c == '-' {
+                        self.append_comment("-");
+                        continue;
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-comment");
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment("--");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                State::CommentEndBang => {
+                    if c == '-' {
+                        self.append_comment("--!");
+                        self.state = State::CommentEndDash;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.parse_error("incorrectly-closed-comment");
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-comment");
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment("--!");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#doctype-state
+                State::Doctype => {
+                    if is_whitespace(c) {
+                        self.state = State::BeforeDoctypeName;
+                        continue;
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-doctype");
+                        self.create_doctype();
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::BeforeDoctypeName;
+                }
+
+                State::BeforeDoctypeName => {
+                    if is_whitespace(c) {
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.parse_error("missing-doctype-name");
+                        self.create_doctype();
+                        self.set_force_quirks_flag();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-doctype");
+                        self.create_doctype();
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    self.create_doctype();
+                    self.append_doctype_name(replace_null(
+                        c.to_ascii_lowercase(),
+                    ));
+                    self.state = State::DoctypeName;
+                }
+
+                State::DoctypeName => {
+                    if is_whitespace(c) {
+                        self.state = State::AfterDoctypeName;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-doctype");
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_name(replace_null(
+                        c.to_ascii_lowercase(),
+                    ));
+                }
+
+                State::AfterDoctypeName => {
+                    if is_whitespace(c) {
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-doctype");
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if self.consume_if_next_is("PUBLIC", true) {
+                        self.state = State::AfterDoctypePublicKeyword;
+                        continue;
+                    }
+
+                    if self.consume_if_next_is("SYSTEM", true) {
+                        self.state = State::AfterDoctypeSystemKeyword;
+                        continue;
+                    }
+
+                    self.parse_error(
+                        "invalid-character-sequence-after-doctype-name",
+                    );
+                    self.set_force_quirks_flag();
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+
+                // 公開識別子とシステム識別子は、同じ規則で解析する
+                State::AfterDoctypePublicKeyword
+                | State::AfterDoctypeSystemKeyword
+                | State::BeforeDoctypePublicIdentifier
+                | State::BeforeDoctypeSystemIdentifier => {
+                    let (identifier, before_identifier) = match self.state {
+                        State::AfterDoctypePublicKeyword
+                        | State::BeforeDoctypePublicIdentifier => (
+                            DoctypeIdentifier::Public,
+                            State::BeforeDoctypePublicIdentifier,
+                        ),
+                        _ => (
+                            DoctypeIdentifier::System,
+                            State::BeforeDoctypeSystemIdentifier,
+                        ),
+                    };
+
+                    if is_whitespace(c) {
+                        self.state = before_identifier;
+                        continue;
+                    }
+
+                    if c == '"' || c == '\'' {
+                        self.start_doctype_identifier(identifier);
+                        self.state = doctype_identifier_state(identifier, c);
+                        continue;
+                    }
+
+                    self.set_force_quirks_flag();
+
+                    if c == '>' {
+                        self.parse_error(&format!(
+                            "missing-doctype-{}-identifier",
+                            identifier.name()
+                        ));
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-doctype");
+                        return self.take_latest_token();
+                    }
+
+                    self.parse_error(&format!(
+                        "missing-quote-before-doctype-{}-identifier",
+                        identifier.name()
+                    ));
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+
+                State::DoctypePublicIdentifierDoubleQuoted
+                | State::DoctypePublicIdentifierSingleQuoted
+                | State::DoctypeSystemIdentifierDoubleQuoted
+                | State::DoctypeSystemIdentifierSingleQuoted => {
+                    let (identifier, quote) = match self.state {
+                        State::DoctypePublicIdentifierDoubleQuoted => {
+                            (DoctypeIdentifier::Public, '"')
+                        }
+                        State::DoctypePublicIdentifierSingleQuoted => {
+                            (DoctypeIdentifier::Public, '\'')
+                        }
+                        State::DoctypeSystemIdentifierDoubleQuoted => {
+                            (DoctypeIdentifier::System, '"')
+                        }
+                        _ => (DoctypeIdentifier::System, '\''),
+                    };
+
+                    if c == quote {
+                        self.state = match identifier {
+                            DoctypeIdentifier::Public => {
+                                State::AfterDoctypePublicIdentifier
+                            }
+                            DoctypeIdentifier::System => {
+                                State::AfterDoctypeSystemIdentifier
+                            }
+                        };
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.parse_error(&format!(
+                            "abrupt-doctype-{}-identifier",
+                            identifier.name()
+                        ));
+                        self.set_force_quirks_flag();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-doctype");
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_identifier(replace_null(c), identifier);
+                }
+
+                // 公開識別子の後には、システム識別子が続くことができる
+                State::AfterDoctypePublicIdentifier
+                | State::BetweenDoctypePublicAndSystemIdentifiers => {
+                    if is_whitespace(c) {
+                        self.state =
+                            State::BetweenDoctypePublicAndSystemIdentifiers;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '"' || c == '\'' {
+                        self.start_doctype_identifier(
+                            DoctypeIdentifier::System,
+                        );
+                        self.state = doctype_identifier_state(
+                            DoctypeIdentifier::System,
+                            c,
+                        );
+                        continue;
+                    }
+
+                    self.set_force_quirks_flag();
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-doctype");
+                        return self.take_latest_token();
+                    }
+
+                    self.parse_error(
+                        "missing-quote-before-doctype-system-identifier",
+                    );
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+
+                State::AfterDoctypeSystemIdentifier => {
+                    if is_whitespace(c) {
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-doctype");
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    // 不正な文字があっても、quirksモードにはしない
+                    self.parse_error(
+                        "unexpected-character-after-doctype-system-identifier",
+                    );
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+
+                State::BogusDoctype => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        return self.take_latest_token();
+                    }
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#cdata-section-state
+                State::CdataSection => {
+                    if c == ']' {
+                        self.state = State::CdataSectionBracket;
+                        continue;
+                    }
+
+                    if self.is_eof() {
+                        self.parse_error("eof-in-cdata");
+                        return Some(HtmlToken::Eof);
+                    }
+
+                    return Some(HtmlToken::Char(c));
+                }
+
+                State::CdataSectionBracket => {
+                    if c == ']' {
+                        self.state = State::CdataSectionEnd;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::CdataSection;
+                    return Some(HtmlToken::Char(']'));
+                }
+
+                State::CdataSectionEnd => {
+                    if c == ']' {
+                        return Some(HtmlToken::Char(']'));
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    // "]]"の2文字を返す
+                    self.reconsume = true;
+                    self.state = State::CdataSection;
+                    self.pending_chars.push_back(']');
+                    return Some(HtmlToken::Char(']'));
+                }
+            }
+        }
+    }
+}
+
+// DOCTYPEなどの解析で、空白として扱う文字
+fn is_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0c' | ' ')
+}
+
+// RCDATA、RAWTEXT、スクリプトデータの、"<"や終了タグを読む状態に対応する、テキストを読む状態
+fn text_state(state: &State) -> State {
+    match state {
+        State::Rcdata
+        | State::RcdataLessThanSign
+        | State::RcdataEndTagOpen
+        | State::RcdataEndTagName => State::Rcdata,
+        State::Rawtext
+        | State::RawtextLessThanSign
+        | State::RawtextEndTagOpen
+        | State::RawtextEndTagName => State::Rawtext,
+        _ => State::ScriptData,
+    }
+}
+
+fn less_than_sign_state(text_state: &State) -> State {
+    match text_state {
+        State::Rcdata => State::RcdataLessThanSign,
+        State::Rawtext => State::RawtextLessThanSign,
+        _ => State::ScriptDataLessThanSign,
+    }
+}
+
+fn end_tag_open_state(text_state: &State) -> State {
+    match text_state {
+        State::Rcdata => State::RcdataEndTagOpen,
+        State::Rawtext => State::RawtextEndTagOpen,
+        _ => State::ScriptDataEndTagOpen,
+    }
+}
+
+fn end_tag_name_state(text_state: &State) -> State {
+    match text_state {
+        State::Rcdata => State::RcdataEndTagName,
+        State::Rawtext => State::RawtextEndTagName,
+        _ => State::ScriptDataEndTagName,
+    }
+}
+
+// NULL文字をU+FFFDに置き換える
+fn replace_null(c: char) -> char {
+    if c == '\0' {
+        char::REPLACEMENT_CHARACTER
+    } else {
+        c
+    }
+}
+
+// 引用符で囲まれたDOCTYPEの識別子を解析する状態
+fn doctype_identifier_state(
+    identifier: DoctypeIdentifier,
+    quote: char,
+) -> State {
+    match (identifier, quote) {
+        (DoctypeIdentifier::Public, '"') => {
+            State::DoctypePublicIdentifierDoubleQuoted
+        }
+        (DoctypeIdentifier::Public, _) => {
+            State::DoctypePublicIdentifierSingleQuoted
+        }
+        (DoctypeIdentifier::System, '"') => {
+            State::DoctypeSystemIdentifierDoubleQuoted
+        }
+        (DoctypeIdentifier::System, _) => {
+            State::DoctypeSystemIdentifierSingleQuoted
+        }
+    }
+}
+
+/// 数値文字参照の値を文字にする
+/// NULL、サロゲート、範囲外の値はU+FFFDにし、0x80から0x9FはWindows-1252として解釈する。
+/// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+fn numeric_character_reference_to_char(code: u32) -> char {
+    let code = match code {
+        0x80 => 0x20AC,
+        0x82 => 0x201A,
+        0x83 => 0x0192,
+        0x84 => 0x201E,
+        0x85 => 0x2026,
+        0x86 => 0x2020,
+        0x87 => 0x2021,
+        0x88 => 0x02C6,
+        0x89 => 0x2030,
+        0x8A => 0x0160,
+        0x8B => 0x2039,
+        0x8C => 0x0152,
+        0x8E => 0x017D,
+        0x91 => 0x2018,
+        0x92 => 0x2019,
+        0x93 => 0x201C,
+        0x94 => 0x201D,
+        0x95 => 0x2022,
+        0x96 => 0x2013,
+        0x97 => 0x2014,
+        0x98 => 0x02DC,
+        0x99 => 0x2122,
+        0x9A => 0x0161,
+        0x9B => 0x203A,
+        0x9C => 0x0153,
+        0x9E => 0x017E,
+        0x9F => 0x0178,
+        _ => code,
+    };
+    if code == 0 {
+        return char::REPLACEMENT_CHARACTER;
+    }
+    // サロゲートと0x10FFFFより大きい値は、char::from_u32がNoneを返す
+    char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn test_empty() {
+        let html = "".to_string();
+        let mut tokenizer: HtmlTokenizer = HtmlTokenizer::new(html);
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn test_start_and_end_tag() {
+        let html = "<body></body>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let expected = [
+            HtmlToken::StartTag {
+                tag: "body".to_string(),
+                self_closing: false,
+                attributes: Vec::new(),
+            },
+            HtmlToken::EndTag {
+                tag: "body".to_string(),
+            },
+        ];
+
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+    }
+
+    #[test]
+    fn test_attributes() {
+        let html = "<p class=\"A\" id = 'B' foo=bar></p>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let mut attr1 = Attribute::new();
+        attr1.add_char('c', true);
+        attr1.add_char('l', true);
+        attr1.add_char('a', true);
+        attr1.add_char('s', true);
+        attr1.add_char('s', true);
+        attr1.add_char('A', false);
+
+        let mut attr2 = Attribute::new();
+        attr2.add_char('i', true);
+        attr2.add_char('d', true);
+        attr2.add_char('B', false);
+
+        let mut attr3 = Attribute::new();
+        attr3.add_char('f', true);
+        attr3.add_char('o', true);
+        attr3.add_char('o', true);
+        attr3.add_char('b', false);
+        attr3.add_char('a', false);
+        attr3.add_char('r', false);
+
+        let expected = [
+            HtmlToken::StartTag {
+                tag: "p".to_string(),
+                self_closing: false,
+                attributes: vec![attr1, attr2, attr3],
+            },
+            HtmlToken::EndTag {
+                tag: "p".to_string(),
+            },
+        ];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+    }
+
+    #[test]
+    fn test_self_closing_tag() {
+        let html = "<img />".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let expected = [HtmlToken::StartTag {
+            tag: "img".to_string(),
+            self_closing: true,
+            attributes: Vec::new(),
+        }];
+
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+    }
 
     #[test]
     fn test_style_tag_in_head() {
@@ -674,4 +1890,428 @@ mod tests {
             assert_eq!(Some(e), tokenizer.next());
         }
     }
+
+    // 文字トークンだけを集めた文字列
+    fn text(html: &str) -> String {
+        HtmlTokenizer::new(html.to_string())
+            .filter_map(|t| match t {
+                HtmlToken::Char(c) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // 最初の開始タグの、最初の属性の値
+    fn attribute_value(html: &str) -> String {
+        match HtmlTokenizer::new(html.to_string()).next() {
+            Some(HtmlToken::StartTag { attributes, .. }) => {
+                attributes[0].value()
+            }
+            t => panic!("expected a start tag, but got {:?}", t),
+        }
+    }
+
+    #[test]
+    fn test_named_character_reference() {
+        assert_eq!("a&b", text("a&amp;b"));
+        assert_eq!("<p>", text("&lt;p&gt;"));
+        assert_eq!("\u{a0}", text("&nbsp;"));
+        assert_eq!("©2024", text("&copy;2024"));
+        assert_eq!("\u{2233}", text("&CounterClockwiseContourIntegral;"));
+        // 2文字に置き換わる名前
+        assert_eq!("\u{2242}\u{338}", text("&NotEqualTilde;"));
+
+        let mut tokenizer = HtmlTokenizer::new("<p>&lt;</p>".to_string());
+        let expected = [
+            HtmlToken::StartTag {
+                tag: "p".to_string(),
+                self_closing: false,
+                attributes: Vec::new(),
+            },
+            HtmlToken::Char('<'),
+            HtmlToken::EndTag {
+                tag: "p".to_string(),
+            },
+        ];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+    }
+
+    #[test]
+    fn test_named_character_reference_without_semicolon() {
+        assert_eq!("© 2024", text("&copy 2024"));
+        assert_eq!("a&b", text("a&ampb"));
+        assert_eq!("¬it;", text("&notit;"));
+        assert_eq!("∉", text("&notin;"));
+        assert_eq!("&", text("&amp"));
+        // ";"なしでは認識されない名前
+        assert_eq!("&hellip", text("&hellip"));
+    }
+
+    #[test]
+    fn test_ambiguous_ampersand() {
+        assert_eq!("&foo;", text("&foo;"));
+        assert_eq!("AT&T", text("AT&T"));
+        assert_eq!("a & b", text("a & b"));
+        assert_eq!("&", text("&"));
+        assert_eq!("&#", text("&#"));
+    }
+
+    #[test]
+    fn test_numeric_character_reference() {
+        assert_eq!("あ", text("&#x3042;"));
+        assert_eq!("あ", text("&#X3042;"));
+        assert_eq!("あ", text("&#12354;"));
+        assert_eq!("Aa", text("&#65a"));
+        assert_eq!("&#;", text("&#;"));
+        assert_eq!("&#xg", text("&#xg"));
+        assert_eq!("&#x", text("&#x"));
+    }
+
+    #[test]
+    fn test_invalid_numeric_character_reference() {
+        assert_eq!("\u{fffd}", text("&#0;"));
+        assert_eq!("\u{fffd}", text("&#xD800;"));
+        assert_eq!("\u{fffd}", text("&#x110000;"));
+        assert_eq!("\u{fffd}", text("&#99999999999999999999;"));
+        // 0x80から0x9FはWindows-1252の文字として扱う
+        assert_eq!("€", text("&#x80;"));
+        assert_eq!("™", text("&#153;"));
+        assert_eq!("\u{81}", text("&#x81;"));
+    }
+
+    #[test]
+    fn test_character_reference_in_attribute() {
+        assert_eq!("a&b", attribute_value("<a href=\"a&amp;b\">"));
+        assert_eq!("©", attribute_value("<p title='&copy;'>"));
+        assert_eq!("あ<", attribute_value("<p title=&#x3042;&lt>"));
+        // ";"で終わらない名前の後に"="か英数字が続く場合は置き換えない
+        assert_eq!("?a=1&copy=2", attribute_value("<a href=\"?a=1&copy=2\">"));
+        assert_eq!("&copyx", attribute_value("<a href=\"&copyx\">"));
+        assert_eq!("© x", attribute_value("<a title=\"&copy x\">"));
+        assert_eq!("&foo;", attribute_value("<a title=\"&foo;\">"));
+    }
+
+    // DOCTYPEトークンを作る
+    fn doctype(
+        name: Option<&str>,
+        public_id: Option<&str>,
+        system_id: Option<&str>,
+        force_quirks: bool,
+    ) -> HtmlToken {
+        HtmlToken::Doctype {
+            name: name.map(|s| s.to_string()),
+            public_id: public_id.map(|s| s.to_string()),
+            system_id: system_id.map(|s| s.to_string()),
+            force_quirks,
+        }
+    }
+
+    // 最初のトークン
+    fn first(html: &str) -> Option<HtmlToken> {
+        HtmlTokenizer::new(html.to_string()).next()
+    }
+
+    #[test]
+    fn test_comment() {
+        let mut tokenizer =
+            HtmlTokenizer::new("<!-- a -- b -->c<!---->".to_string());
+        let expected = [
+            HtmlToken::Comment(" a -- b ".to_string()),
+            HtmlToken::Char('c'),
+            HtmlToken::Comment("".to_string()),
+        ];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+        assert_eq!(None, tokenizer.next());
+
+        assert_eq!(Some(HtmlToken::Comment("".to_string())), first("<!-->"));
+        assert_eq!(Some(HtmlToken::Comment("".to_string())), first("<!--->"));
+        assert_eq!(
+            Some(HtmlToken::Comment("a-".to_string())),
+            first("<!--a--->")
+        );
+        assert_eq!(
+            Some(HtmlToken::Comment("a--!b".to_string())),
+            first("<!--a--!b--!>")
+        );
+        assert_eq!(
+            Some(HtmlToken::Comment("<p>".to_string())),
+            first("<!--<p>-->")
+        );
+        // 閉じられていないコメントは、入力の最後までをコメントとする
+        assert_eq!(Some(HtmlToken::Comment("a".to_string())), first("<!--a"));
+    }
+
+    #[test]
+    fn test_bogus_comment() {
+        assert_eq!(
+            Some(HtmlToken::Comment("?xml version=\"1.0\"?".to_string())),
+            first("<?xml version=\"1.0\"?>")
+        );
+        assert_eq!(
+            Some(HtmlToken::Comment("foo".to_string())),
+            first("<!foo>")
+        );
+        assert_eq!(Some(HtmlToken::Comment(" 3".to_string())), first("</ 3>"));
+        assert_eq!(Some(HtmlToken::Char('a')), first("</>a"));
+    }
+
+    #[test]
+    fn test_cdata() {
+        // HTMLの要素の中では、CDATAセクションはコメントになる
+        assert_eq!(
+            Some(HtmlToken::Comment("[CDATA[a<b]]".to_string())),
+            first("<![CDATA[a<b]]>")
+        );
+
+        let mut tokenizer =
+            HtmlTokenizer::new("<![CDATA[a<]b]]]>c".to_string());
+        tokenizer.set_cdata_allowed(true);
+        for e in ['a', '<', ']', 'b', ']', 'c'] {
+            assert_eq!(Some(HtmlToken::Char(e)), tokenizer.next());
+        }
+        assert_eq!(None, tokenizer.next());
+
+        let mut tokenizer = HtmlTokenizer::new("<![CDATA[a]]b".to_string());
+        tokenizer.set_cdata_allowed(true);
+        for e in ['a', ']', ']', 'b'] {
+            assert_eq!(Some(HtmlToken::Char(e)), tokenizer.next());
+        }
+    }
+
+    #[test]
+    fn test_doctype() {
+        assert_eq!(
+            Some(doctype(Some("html"), None, None, false)),
+            first("<!DOCTYPE html>")
+        );
+        assert_eq!(
+            Some(doctype(Some("html"), None, None, false)),
+            first("<!doctype HTML >")
+        );
+        assert_eq!(
+            Some(doctype(
+                Some("html"),
+                Some("-//W3C//DTD HTML 4.01//EN"),
+                Some("http://www.w3.org/TR/html4/strict.dtd"),
+                false
+            )),
+            first(
+                "<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \
+                 'http://www.w3.org/TR/html4/strict.dtd'>"
+            )
+        );
+        assert_eq!(
+            Some(doctype(
+                Some("html"),
+                None,
+                Some("about:legacy-compat"),
+                false
+            )),
+            first("<!DOCTYPE html SYSTEM \"about:legacy-compat\">")
+        );
+        assert_eq!(
+            Some(doctype(Some("html"), Some("a"), Some(""), false)),
+            first("<!DOCTYPE html PUBLIC 'a'''>")
+        );
+
+        let mut tokenizer =
+            HtmlTokenizer::new("<!DOCTYPE html><p>".to_string());
+        tokenizer.next();
+        assert_eq!(
+            Some(HtmlToken::StartTag {
+                tag: "p".to_string(),
+                self_closing: false,
+                attributes: Vec::new(),
+            }),
+            tokenizer.next()
+        );
+    }
+
+    #[test]
+    fn test_doctype_force_quirks() {
+        assert_eq!(Some(doctype(None, None, None, true)), first("<!DOCTYPE>"));
+        assert_eq!(
+            Some(doctype(Some("html"), None, None, true)),
+            first("<!DOCTYPE html")
+        );
+        assert_eq!(
+            Some(doctype(Some("html"), None, None, true)),
+            first("<!DOCTYPE html foo>")
+        );
+        assert_eq!(
+            Some(doctype(Some("html"), Some("a"), None, true)),
+            first("<!DOCTYPE html PUBLIC \"a>")
+        );
+        assert_eq!(
+            Some(doctype(Some("html"), None, None, true)),
+            first("<!DOCTYPE html PUBLIC>")
+        );
+        // システム識別子の後の不正な文字は無視する
+        assert_eq!(
+            Some(doctype(Some("html"), None, Some("a"), false)),
+            first("<!DOCTYPE html SYSTEM 'a' b>")
+        );
+    }
+
+    // 開始タグの後に字句解析の状態を切り替えて、文字トークンと終了タグを集める
+    fn text_content(html: &str, state: State) -> (String, Vec<HtmlToken>) {
+        let mut tokenizer = HtmlTokenizer::new(html.to_string());
+        tokenizer.next();
+        tokenizer.switch_to(state);
+        let mut text = String::new();
+        let mut tags = Vec::new();
+        for t in tokenizer {
+            match t {
+                HtmlToken::Char(c) => text.push(c),
+                t => tags.push(t),
+            }
+        }
+        (text, tags)
+    }
+
+    #[test]
+    fn test_rawtext() {
+        let (text, tags) = text_content(
+            "<style>a > b { } </p> <!-- x --> &amp;</style>",
+            State::Rawtext,
+        );
+        assert_eq!("a > b { } </p> <!-- x --> &amp;", text);
+        assert_eq!(
+            vec![HtmlToken::EndTag {
+                tag: "style".to_string()
+            }],
+            tags
+        );
+
+        // 閉じられていない場合は、入力の最後までが中身になる
+        let (text, tags) = text_content("<style></styl", State::Rawtext);
+        assert_eq!("</styl", text);
+        assert_eq!(vec![HtmlToken::Eof], tags);
+    }
+
+    #[test]
+    fn test_rcdata() {
+        let (text, tags) = text_content(
+            "<title>a<b> &lt;&amp; </titlex></TITLE foo=\"bar\">c",
+            State::Rcdata,
+        );
+        assert_eq!("a<b> <& </titlex>c", text);
+        assert_eq!(
+            vec![HtmlToken::EndTag {
+                tag: "title".to_string()
+            }],
+            tags
+        );
+    }
+
+    #[test]
+    fn test_script_data() {
+        let (text, tags) = text_content(
+            "<script>if (a</b) { x = '</scrip' }</script>",
+            State::ScriptData,
+        );
+        assert_eq!("if (a</b) { x = '</scrip' }", text);
+        assert_eq!(
+            vec![HtmlToken::EndTag {
+                tag: "script".to_string()
+            }],
+            tags
+        );
+
+        let (text, _) =
+            text_content("<script>a<</ b</script >", State::ScriptData);
+        assert_eq!("a<</ b", text);
+    }
+
+    // ソース上の(行, 列)
+    type Position = (usize, usize);
+
+    fn spans(html: &str) -> Vec<(HtmlToken, Position, Position)> {
+        let mut tokenizer = HtmlTokenizer::new(html.to_string());
+        let mut result = Vec::new();
+        while let Some(t) = tokenizer.next() {
+            let span = tokenizer.latest_span();
+            result.push((
+                t,
+                (span.start().line(), span.start().column()),
+                (span.end().line(), span.end().column()),
+            ));
+        }
+        result
+    }
+
+    #[test]
+    fn test_token_span() {
+        let p = |tag: &str| HtmlToken::StartTag {
+            tag: tag.to_string(),
+            self_closing: false,
+            attributes: Vec::new(),
+        };
+        assert_eq!(
+            vec![
+                (p("p"), (1, 1), (1, 4)),
+                (HtmlToken::Char('\n'), (1, 4), (2, 1)),
+                (HtmlToken::Char('a'), (2, 1), (2, 2)),
+                // 文字参照は、参照全体の範囲を持つ
+                (HtmlToken::Char('&'), (2, 2), (2, 7)),
+                (
+                    HtmlToken::EndTag {
+                        tag: "p".to_string()
+                    },
+                    (2, 7),
+                    (2, 11)
+                ),
+            ],
+            spans("<p>\na&amp;</p>")
+        );
+    }
+
+    fn errors(html: &str) -> Vec<(String, usize, usize)> {
+        let mut tokenizer = HtmlTokenizer::new(html.to_string());
+        for _ in tokenizer.by_ref() {}
+        tokenizer
+            .errors()
+            .iter()
+            .map(|e| {
+                let start = e.span().start();
+                (e.message(), start.line(), start.column())
+            })
+            .collect()
+    }
+
+    fn error(code: &str, line: usize, column: usize) -> (String, usize, usize) {
+        (code.to_string(), line, column)
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(errors("<p class=\"a\">&amp;</p>").is_empty());
+        assert_eq!(
+            vec![error("missing-semicolon-after-character-reference", 2, 4)],
+            errors("a\n&amp b")
+        );
+        assert_eq!(
+            vec![error("null-character-reference", 1, 4)],
+            errors("&#0;")
+        );
+        assert_eq!(
+            vec![error("invalid-first-character-of-tag-name", 1, 4)],
+            errors("1 < 2")
+        );
+        assert_eq!(vec![error("missing-end-tag-name", 1, 3)], errors("</>"));
+        assert_eq!(vec![error("eof-in-tag", 2, 3)], errors("\n<a"));
+        assert_eq!(vec![error("eof-in-comment", 1, 7)], errors("<!-- x"));
+        assert_eq!(
+            vec![error("missing-doctype-name", 1, 10)],
+            errors("<!DOCTYPE>")
+        );
+        assert_eq!(
+            vec![error("incorrectly-opened-comment", 1, 3)],
+            errors("<!x>")
+        );
+    }
 }
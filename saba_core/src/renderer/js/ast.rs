@@ -1,13 +1,35 @@
-use core::iter::Peekable;
-
 use super::token::JsLexer;
 use super::token::Token;
+use crate::renderer::diagnostics::sort_errors;
+use crate::renderer::diagnostics::ParseError;
+use crate::renderer::diagnostics::SourceKind;
+use crate::renderer::diagnostics::Span;
+use crate::renderer::diagnostics::TokenStream;
+use alloc::format;
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::{rc::Rc, vec::Vec};
 
 // 字句解析からトークンを受け取って、構文解析して、ASTを作る際のノード
+// spanは、ノードのソース上の範囲。構文解析器が作ったノードのみが持つ
+#[derive(Debug, Clone)]
+pub struct Node {
+    kind: NodeKind,
+    span: Option<Span>,
+}
+
+// ソース上の範囲は比較しない
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl Eq for Node {}
+
+// ノードの種類と、子ノード
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Node {
+pub enum NodeKind {
     ExpressionStatement(Option<Rc<Node>>),
     VariableDeclaration {
         declarations: Vec<Option<Rc<Node>>>,
@@ -53,7 +75,9 @@ pub enum Node {
 }
 
 pub struct JsParser {
-    t: Peekable<JsLexer>,
+    t: TokenStream<JsLexer>,
+    // 構文解析中に見つかったエラー
+    errors: Vec<ParseError>,
 }
 
 // ASTを持つ構造体
@@ -64,7 +88,32 @@ pub struct Program {
 
 impl JsParser {
     pub fn new(t: JsLexer) -> Self {
-        Self { t: t.peekable() }
+        Self {
+            t: TokenStream::new(t),
+            errors: Vec::new(),
+        }
+    }
+
+    /// 字句解析と構文解析で見つかったエラーを、ソース上の位置の順に返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        let mut errors = self.t.inner().errors();
+        errors.extend(self.errors.iter().cloned());
+        sort_errors(&mut errors);
+        errors
+    }
+
+    // 次のトークンの位置でエラーを記録する
+    fn parse_error(&mut self, message: String) {
+        let span = self.t.peek_span();
+        self.errors
+            .push(ParseError::new(SourceKind::Js, message, span));
+    }
+
+    // 直前に消費したトークンの位置でエラーを記録する
+    fn parse_error_at_latest(&mut self, message: String) {
+        let span = self.t.span();
+        self.errors
+            .push(ParseError::new(SourceKind::Js, message, span));
     }
 
     pub fn parse_ast(&mut self) -> Program {
@@ -83,6 +132,18 @@ impl JsParser {
         }
     }
 
+    // startから直前に消費したトークンまでを、ノードのソース上の範囲にする
+    fn with_span(
+        &self,
+        node: Option<Rc<Node>>,
+        start: Span,
+    ) -> Option<Rc<Node>> {
+        let node = node?;
+        let mut node = Rc::try_unwrap(node).unwrap_or_else(|n| (*n).clone());
+        node.span = Some(start.to(self.t.span()));
+        Some(Rc::new(node))
+    }
+
     // SourceElement ::= Statement | FunctionDeclaration
     fn source_element(&mut self) -> Option<Rc<Node>> {
         let t = match self.t.peek() {
@@ -92,8 +153,10 @@ impl JsParser {
         match t {
             Token::Keyword(keyword) => {
                 if keyword == "function" {
+                    let start = self.t.peek_span();
                     assert!(self.t.next().is_some());
-                    self.function_declaration()
+                    let node = self.function_declaration();
+                    self.with_span(node, start)
                 } else {
                     self.statement()
                 }
@@ -114,13 +177,11 @@ impl JsParser {
         let mut params = Vec::new();
 
         // '('を消費する。
-        match self.t.next() {
-            Some(t) => match t {
-                Token::Punctuator(c) => assert!(c == '('),
-                _ => unimplemented!("function should have `(` but got {:?}", t),
-            },
-            None => unimplemented!("function should have `(` but got None"),
+        if self.t.peek() != Some(&Token::Punctuator('(')) {
+            self.parse_error("expected '(' after function name".to_string());
+            return params;
         }
+        assert!(self.t.next().is_some());
 
         loop {
             // ')'に到達するまで、paramsに仮引数となる変数を追加する
@@ -133,13 +194,24 @@ impl JsParser {
                         }
                         if c == &',' {
                             assert!(self.t.next().is_some());
+                            continue;
                         }
+                        // 仮引数には、変数名しか書けない
+                        let message =
+                            format!("unexpected {:?} in parameter list", c);
+                        self.parse_error(message);
+                        assert!(self.t.next().is_some());
                     }
                     _ => {
                         params.push(self.identifier());
                     }
                 },
-                None => return params,
+                None => {
+                    self.parse_error(
+                        "missing ')' after parameters".to_string(),
+                    );
+                    return params;
+                }
             }
         }
     }
@@ -147,29 +219,30 @@ impl JsParser {
     // FunctionBody ::= "{" ( SourceElement )? "}"
     fn function_body(&mut self) -> Option<Rc<Node>> {
         // `{`を消費する
-        match self.t.next() {
-            Some(t) => match t {
-                Token::Punctuator(c) => assert!(c == '{'),
-                _ => unimplemented!(
-                    "function shold have open curly but got {:?}",
-                    t
-                ),
-            },
-            None => {
-                unimplemented!("function should have open curly but got None")
-            }
+        if self.t.peek() != Some(&Token::Punctuator('{')) {
+            self.parse_error("expected '{' before function body".to_string());
+            return None;
         }
+        let start = self.t.peek_span();
+        assert!(self.t.next().is_some());
 
         let mut body = Vec::new();
         loop {
-            if let Some(Token::Punctuator(c)) = self.t.peek() {
-                if c == &'}' {
+            match self.t.peek() {
+                Some(Token::Punctuator('}')) => {
                     assert!(self.t.next().is_some());
-                    return Node::new_block_statement(body);
+                    break;
+                }
+                Some(_) => body.push(self.source_element()),
+                None => {
+                    self.parse_error(
+                        "missing '}' after function body".to_string(),
+                    );
+                    break;
                 }
             }
-            body.push(self.source_element());
         }
+        self.with_span(Node::new_block_statement(body), start)
     }
 
     // statementとexpression statementの実装
@@ -178,6 +251,7 @@ impl JsParser {
     // ExpressionStatement ::= AssignmentExpression (";")?
     // ReturnStatement ::= "return" AssigmentExpresion (";")?
     fn statement(&mut self) -> Option<Rc<Node>> {
+        let start = self.t.peek_span();
         let t = match self.t.peek() {
             Some(t) => t,
             None => return None,
@@ -204,15 +278,17 @@ impl JsParser {
                 assert!(self.t.next().is_some());
             }
         }
-        node
+        self.with_span(node, start)
     }
 
     // VariableDeclaration ::= Identifier ( Initializer )? #
     fn variable_declaration(&mut self) -> Option<Rc<Node>> {
+        let start = self.t.peek_span();
         let ident = self.identifier();
 
         let declarator =
             Node::new_variable_declarator(ident, self.initializer());
+        let declarator = self.with_span(declarator, start);
 
         let declarations = [declarator].to_vec();
 
@@ -228,8 +304,17 @@ impl JsParser {
         };
 
         match t {
-            Token::Identifier(name) => Node::new_identifier(name),
-            _ => None,
+            Token::Identifier(name) => {
+                let span = self.t.span();
+                self.with_span(Node::new_identifier(name), span)
+            }
+            _ => {
+                self.parse_error_at_latest(format!(
+                    "expected identifier but got {:?}",
+                    t
+                ));
+                None
+            }
         }
     }
 
@@ -249,6 +334,7 @@ impl JsParser {
 
     // AssignmentExpression ::= AdditiveExpression ( "=" AdditiveExpression )*
     fn assignment_expression(&mut self) -> Option<Rc<Node>> {
+        let start = self.t.peek_span();
         let expr = self.additive_expression();
 
         let t = match self.t.peek() {
@@ -262,10 +348,10 @@ impl JsParser {
             Token::Punctuator('=') => {
                 // '=' を消費する。
                 assert!(self.t.next().is_some());
-                Node::new_assignment_expression(
-                    '=',
-                    expr,
-                    self.assignment_expression(),
+                let right = self.assignment_expression();
+                self.with_span(
+                    Node::new_assignment_expression('=', expr, right),
+                    start,
                 )
             }
             _ => expr,
@@ -274,6 +360,7 @@ impl JsParser {
 
     // AdditiveExpression ::= LeftHandSizeExpression ( AdditiveOperator AssignmentExpression )*
     fn additive_expression(&mut self) -> Option<Rc<Node>> {
+        let start = self.t.peek_span();
         let left = self.left_hand_size_expression();
 
         let t = match self.t.peek() {
@@ -286,10 +373,10 @@ impl JsParser {
                 '+' | '-' => {
                     // '_', '-'の時は、その文字列を消費する
                     assert!(self.t.next().is_some());
-                    Node::new_additive_expression(
-                        c,
-                        left,
-                        self.assignment_expression(),
+                    let right = self.assignment_expression();
+                    self.with_span(
+                        Node::new_additive_expression(c, left, right),
+                        start,
                     )
                 }
                 _ => left,
@@ -300,6 +387,7 @@ impl JsParser {
 
     // LeftHandSizeExpression ::= CallExpression | MemberExpression
     fn left_hand_size_expression(&mut self) -> Option<Rc<Node>> {
+        let start = self.t.peek_span();
         let expr = self.member_expression();
 
         let t = match self.t.peek() {
@@ -311,7 +399,11 @@ impl JsParser {
             Token::Punctuator(c) => {
                 if c == &'(' {
                     assert!(self.t.next().is_some());
-                    return Node::new_call_expression(expr, self.arguments());
+                    let arguments = self.arguments();
+                    return self.with_span(
+                        Node::new_call_expression(expr, arguments),
+                        start,
+                    );
                 }
                 expr
             }
@@ -335,19 +427,25 @@ impl JsParser {
                         }
                         if c == &',' {
                             assert!(self.t.next().is_some());
+                            continue;
                         }
+                        arguments.push(self.assignment_expression());
                     }
                     _ => {
                         arguments.push(self.assignment_expression());
                     }
                 },
-                None => return arguments,
+                None => {
+                    self.parse_error("missing ')' after arguments".to_string());
+                    return arguments;
+                }
             }
         }
     }
 
     // MemberExpression ::= PrimaryExpression ( "." Identifier )?
     fn member_expression(&mut self) -> Option<Rc<Node>> {
+        let start = self.t.peek_span();
         let expr = self.primary_expression();
 
         let t = match self.t.peek() {
@@ -359,7 +457,11 @@ impl JsParser {
             Token::Punctuator(c) => {
                 if c == &'.' {
                     assert!(self.t.next().is_some());
-                    return Node::new_member_expression(expr, self.identifier());
+                    let property = self.identifier();
+                    return self.with_span(
+                        Node::new_member_expression(expr, property),
+                        start,
+                    );
                 }
                 expr
             }
@@ -377,20 +479,42 @@ impl JsParser {
             None => return None,
         };
 
-        match t {
+        let node = match t {
             Token::Number(value) => Node::new_numeric_literal(value),
             Token::StringLiteral(value) => Node::new_string_literal(value),
             Token::Identifier(name) => Node::new_identifier(name),
-            _ => None,
-        }
+            _ => {
+                self.parse_error_at_latest(format!("unexpected token {:?}", t));
+                None
+            }
+        };
+        let span = self.t.span();
+        self.with_span(node, span)
     }
 }
 
 impl Node {
+    pub fn new(kind: NodeKind) -> Self {
+        Self { kind, span: None }
+    }
+
+    fn from_kind(kind: NodeKind) -> Option<Rc<Self>> {
+        Some(Rc::new(Self::new(kind)))
+    }
+
+    pub fn kind(&self) -> &NodeKind {
+        &self.kind
+    }
+
+    /// ソース上の範囲。構文解析器以外で作ったノードはNone
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
     pub fn new_expression_statement(
         expression: Option<Rc<Self>>,
     ) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::ExpressionStatement(expression)))
+        Self::from_kind(NodeKind::ExpressionStatement(expression))
     }
 
     pub fn new_additive_expression(
@@ -398,62 +522,62 @@ impl Node {
         left: Option<Rc<Node>>,
         right: Option<Rc<Node>>,
     ) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::AdditiveExpression {
+        Self::from_kind(NodeKind::AdditiveExpression {
             operator,
             left,
             right,
-        }))
+        })
     }
     pub fn new_assignment_expression(
         operator: char,
         left: Option<Rc<Node>>,
         right: Option<Rc<Node>>,
     ) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::AssignmentExpression {
+        Self::from_kind(NodeKind::AssignmentExpression {
             operator,
             left,
             right,
-        }))
+        })
     }
 
     pub fn new_member_expression(
         object: Option<Rc<Node>>,
         property: Option<Rc<Node>>,
     ) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::MemberExpression { object, property }))
+        Self::from_kind(NodeKind::MemberExpression { object, property })
     }
 
     pub fn new_numeric_literal(value: u64) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::NumericLiteral(value)))
+        Self::from_kind(NodeKind::NumericLiteral(value))
     }
 
     pub fn new_variable_declarator(
         id: Option<Rc<Self>>,
         init: Option<Rc<Self>>,
     ) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::VariableDeclarator { id, init }))
+        Self::from_kind(NodeKind::VariableDeclarator { id, init })
     }
 
     pub fn new_variable_declaration(
         declarations: Vec<Option<Rc<Self>>>,
     ) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::VariableDeclaration { declarations }))
+        Self::from_kind(NodeKind::VariableDeclaration { declarations })
     }
 
     pub fn new_identifier(name: String) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::Identifier(name)))
+        Self::from_kind(NodeKind::Identifier(name))
     }
 
     pub fn new_string_literal(value: String) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::StringLiteral(value)))
+        Self::from_kind(NodeKind::StringLiteral(value))
     }
 
     pub fn new_block_statement(body: Vec<Option<Rc<Self>>>) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::BlockStatement { body }))
+        Self::from_kind(NodeKind::BlockStatement { body })
     }
 
     pub fn new_return_statement(argument: Option<Rc<Self>>) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::ReturnStatement { argument }))
+        Self::from_kind(NodeKind::ReturnStatement { argument })
     }
 
     pub fn new_function_declaration(
@@ -461,14 +585,14 @@ impl Node {
         params: Vec<Option<Rc<Self>>>,
         body: Option<Rc<Self>>,
     ) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::FunctionDeclaration { id, params, body }))
+        Self::from_kind(NodeKind::FunctionDeclaration { id, params, body })
     }
 
     pub fn new_call_expression(
         callee: Option<Rc<Self>>,
         arguments: Vec<Option<Rc<Self>>>,
     ) -> Option<Rc<Self>> {
-        Some(Rc::new(Node::CallExpression { callee, arguments }))
+        Self::from_kind(NodeKind::CallExpression { callee, arguments })
     }
 }
 
@@ -497,6 +621,7 @@ mod tests {
     use super::*;
     use alloc::string::String;
     use alloc::string::ToString;
+    use alloc::vec;
 
     fn create_parser(input: String) -> JsParser {
         JsParser::new(JsLexer::new(input))
@@ -517,8 +642,8 @@ mod tests {
         let mut parser = create_parser(input);
         let mut expected = Program::new();
         expected.set_body(
-            [Rc::new(Node::ExpressionStatement(Some(Rc::new(
-                Node::NumericLiteral(53211),
+            [Rc::new(Node::new(NodeKind::ExpressionStatement(Some(
+                Rc::new(Node::new(NodeKind::NumericLiteral(53211))),
             ))))]
             .to_vec(),
         );
@@ -532,12 +657,16 @@ mod tests {
         let mut parser = create_parser(input);
         let mut expected = Program::new();
         expected.set_body(
-            [Rc::new(Node::ExpressionStatement(Some(Rc::new(
-                Node::AdditiveExpression {
+            [Rc::new(Node::new(NodeKind::ExpressionStatement(Some(
+                Rc::new(Node::new(NodeKind::AdditiveExpression {
                     operator: '+',
-                    left: Some(Rc::new(Node::NumericLiteral(216))),
-                    right: Some(Rc::new(Node::NumericLiteral(222))),
-                },
+                    left: Some(Rc::new(Node::new(NodeKind::NumericLiteral(
+                        216,
+                    )))),
+                    right: Some(Rc::new(Node::new(NodeKind::NumericLiteral(
+                        222,
+                    )))),
+                })),
             ))))]
             .to_vec(),
         );
@@ -551,12 +680,16 @@ mod tests {
         let mut parser = create_parser(input);
         let mut expected = Program::new();
         expected.set_body(
-            [Rc::new(Node::ExpressionStatement(Some(Rc::new(
-                Node::AdditiveExpression {
+            [Rc::new(Node::new(NodeKind::ExpressionStatement(Some(
+                Rc::new(Node::new(NodeKind::AdditiveExpression {
                     operator: '-',
-                    left: Some(Rc::new(Node::NumericLiteral(98765))),
-                    right: Some(Rc::new(Node::NumericLiteral(1234))),
-                },
+                    left: Some(Rc::new(Node::new(NodeKind::NumericLiteral(
+                        98765,
+                    )))),
+                    right: Some(Rc::new(Node::new(NodeKind::NumericLiteral(
+                        1234,
+                    )))),
+                })),
             ))))]
             .to_vec(),
         );
@@ -570,13 +703,19 @@ mod tests {
         let mut parser = create_parser(input);
         let mut expected = Program::new();
         expected.set_body(
-            [Rc::new(Node::VariableDeclaration {
-                declarations: [Some(Rc::new(Node::VariableDeclarator {
-                    id: Some(Rc::new(Node::Identifier("foo".to_string()))),
-                    init: Some(Rc::new(Node::StringLiteral("bar".to_string()))),
-                }))]
+            [Rc::new(Node::new(NodeKind::VariableDeclaration {
+                declarations: [Some(Rc::new(Node::new(
+                    NodeKind::VariableDeclarator {
+                        id: Some(Rc::new(Node::new(NodeKind::Identifier(
+                            "foo".to_string(),
+                        )))),
+                        init: Some(Rc::new(Node::new(
+                            NodeKind::StringLiteral("bar".to_string()),
+                        ))),
+                    },
+                )))]
                 .to_vec(),
-            })]
+            }))]
             .to_vec(),
         );
         assert_eq!(expected, parser.parse_ast());
@@ -591,28 +730,40 @@ var result = foo + 1;"#
         let mut expected = Program::new();
         expected.set_body(
             [
-                Rc::new(Node::VariableDeclaration {
-                    declarations: [Some(Rc::new(Node::VariableDeclarator {
-                        id: Some(Rc::new(Node::Identifier("foo".to_string()))),
-                        init: Some(Rc::new(Node::NumericLiteral(42))),
-                    }))]
-                    .to_vec(),
-                }),
-                Rc::new(Node::VariableDeclaration {
-                    declarations: [Some(Rc::new(Node::VariableDeclarator {
-                        id: Some(Rc::new(Node::Identifier(
-                            "result".to_string(),
-                        ))),
-                        init: Some(Rc::new(Node::AdditiveExpression {
-                            operator: '+',
-                            left: Some(Rc::new(Node::Identifier(
+                Rc::new(Node::new(NodeKind::VariableDeclaration {
+                    declarations: [Some(Rc::new(Node::new(
+                        NodeKind::VariableDeclarator {
+                            id: Some(Rc::new(Node::new(NodeKind::Identifier(
                                 "foo".to_string(),
+                            )))),
+                            init: Some(Rc::new(Node::new(
+                                NodeKind::NumericLiteral(42),
                             ))),
-                            right: Some(Rc::new(Node::NumericLiteral(1))),
-                        })),
-                    }))]
+                        },
+                    )))]
                     .to_vec(),
-                }),
+                })),
+                Rc::new(Node::new(NodeKind::VariableDeclaration {
+                    declarations: [Some(Rc::new(Node::new(
+                        NodeKind::VariableDeclarator {
+                            id: Some(Rc::new(Node::new(NodeKind::Identifier(
+                                "result".to_string(),
+                            )))),
+                            init: Some(Rc::new(Node::new(
+                                NodeKind::AdditiveExpression {
+                                    operator: '+',
+                                    left: Some(Rc::new(Node::new(
+                                        NodeKind::Identifier("foo".to_string()),
+                                    ))),
+                                    right: Some(Rc::new(Node::new(
+                                        NodeKind::NumericLiteral(1),
+                                    ))),
+                                },
+                            ))),
+                        },
+                    )))]
+                    .to_vec(),
+                })),
             ]
             .to_vec(),
         );
@@ -633,36 +784,50 @@ result = 10"#
         let mut expected = Program::new();
         expected.set_body(
             [
-                Rc::new(Node::VariableDeclaration {
-                    declarations: [Some(Rc::new(Node::VariableDeclarator {
-                        id: Some(Rc::new(Node::Identifier("foo".to_string()))),
-                        init: Some(Rc::new(Node::NumericLiteral(42))),
-                    }))]
-                    .to_vec(),
-                }),
-                Rc::new(Node::VariableDeclaration {
-                    declarations: [Some(Rc::new(Node::VariableDeclarator {
-                        id: Some(Rc::new(Node::Identifier(
-                            "result".to_string(),
-                        ))),
-                        init: Some(Rc::new(Node::AdditiveExpression {
-                            operator: '+',
-                            left: Some(Rc::new(Node::Identifier(
+                Rc::new(Node::new(NodeKind::VariableDeclaration {
+                    declarations: [Some(Rc::new(Node::new(
+                        NodeKind::VariableDeclarator {
+                            id: Some(Rc::new(Node::new(NodeKind::Identifier(
                                 "foo".to_string(),
+                            )))),
+                            init: Some(Rc::new(Node::new(
+                                NodeKind::NumericLiteral(42),
                             ))),
-                            right: Some(Rc::new(Node::NumericLiteral(1))),
-                        })),
-                    }))]
+                        },
+                    )))]
                     .to_vec(),
-                }),
-                Rc::new(Node::ExpressionStatement(Some(Rc::new(
-                    Node::AssignmentExpression {
+                })),
+                Rc::new(Node::new(NodeKind::VariableDeclaration {
+                    declarations: [Some(Rc::new(Node::new(
+                        NodeKind::VariableDeclarator {
+                            id: Some(Rc::new(Node::new(NodeKind::Identifier(
+                                "result".to_string(),
+                            )))),
+                            init: Some(Rc::new(Node::new(
+                                NodeKind::AdditiveExpression {
+                                    operator: '+',
+                                    left: Some(Rc::new(Node::new(
+                                        NodeKind::Identifier("foo".to_string()),
+                                    ))),
+                                    right: Some(Rc::new(Node::new(
+                                        NodeKind::NumericLiteral(1),
+                                    ))),
+                                },
+                            ))),
+                        },
+                    )))]
+                    .to_vec(),
+                })),
+                Rc::new(Node::new(NodeKind::ExpressionStatement(Some(
+                    Rc::new(Node::new(NodeKind::AssignmentExpression {
                         operator: '=',
-                        left: Some(Rc::new(Node::Identifier(
+                        left: Some(Rc::new(Node::new(NodeKind::Identifier(
                             "result".to_string(),
+                        )))),
+                        right: Some(Rc::new(Node::new(
+                            NodeKind::NumericLiteral(10),
                         ))),
-                        right: Some(Rc::new(Node::NumericLiteral(10))),
-                    },
+                    })),
                 )))),
             ]
             .to_vec(),
@@ -681,16 +846,20 @@ function foo() {
         .to_string();
         let mut parser = create_parser(input);
         let mut expected = Program::new();
-        let body = [Rc::new(Node::FunctionDeclaration {
-            id: Some(Rc::new(Node::Identifier("foo".to_string()))),
+        let body = [Rc::new(Node::new(NodeKind::FunctionDeclaration {
+            id: Some(Rc::new(Node::new(NodeKind::Identifier(
+                "foo".to_string(),
+            )))),
             params: Vec::new(),
-            body: Some(Rc::new(Node::BlockStatement {
-                body: [Some(Rc::new(Node::ReturnStatement {
-                    argument: Some(Rc::new(Node::NumericLiteral(42))),
-                }))]
+            body: Some(Rc::new(Node::new(NodeKind::BlockStatement {
+                body: [Some(Rc::new(Node::new(NodeKind::ReturnStatement {
+                    argument: Some(Rc::new(Node::new(
+                        NodeKind::NumericLiteral(42),
+                    ))),
+                })))]
                 .to_vec(),
-            })),
-        })]
+            }))),
+        }))]
         .to_vec();
 
         expected.set_body(body);
@@ -707,20 +876,28 @@ function foo(hoge, fuga) {
         .to_string();
         let mut parser = create_parser(input);
         let mut expected = Program::new();
-        let body = [Rc::new(Node::FunctionDeclaration {
-            id: Some(Rc::new(Node::Identifier("foo".to_string()))),
+        let body = [Rc::new(Node::new(NodeKind::FunctionDeclaration {
+            id: Some(Rc::new(Node::new(NodeKind::Identifier(
+                "foo".to_string(),
+            )))),
             params: [
-                Some(Rc::new(Node::Identifier("hoge".to_string()))),
-                Some(Rc::new(Node::Identifier("fuga".to_string()))),
+                Some(Rc::new(Node::new(NodeKind::Identifier(
+                    "hoge".to_string(),
+                )))),
+                Some(Rc::new(Node::new(NodeKind::Identifier(
+                    "fuga".to_string(),
+                )))),
             ]
             .to_vec(),
-            body: Some(Rc::new(Node::BlockStatement {
-                body: [Some(Rc::new(Node::ReturnStatement {
-                    argument: Some(Rc::new(Node::NumericLiteral(42))),
-                }))]
+            body: Some(Rc::new(Node::new(NodeKind::BlockStatement {
+                body: [Some(Rc::new(Node::new(NodeKind::ReturnStatement {
+                    argument: Some(Rc::new(Node::new(
+                        NodeKind::NumericLiteral(42),
+                    ))),
+                })))]
                 .to_vec(),
-            })),
-        })]
+            }))),
+        }))]
         .to_vec();
 
         expected.set_body(body);
@@ -739,32 +916,50 @@ var result = foo() + 555;"#
         let mut parser = create_parser(input);
         let mut expected = Program::new();
         let body = [
-            Rc::new(Node::FunctionDeclaration {
-                id: Some(Rc::new(Node::Identifier("foo".to_string()))),
+            Rc::new(Node::new(NodeKind::FunctionDeclaration {
+                id: Some(Rc::new(Node::new(NodeKind::Identifier(
+                    "foo".to_string(),
+                )))),
                 params: Vec::new(),
-                body: Some(Rc::new(Node::BlockStatement {
-                    body: [Some(Rc::new(Node::ReturnStatement {
-                        argument: Some(Rc::new(Node::NumericLiteral(42))),
-                    }))]
-                    .to_vec(),
-                })),
-            }),
-            Rc::new(Node::VariableDeclaration {
-                declarations: [Some(Rc::new(Node::VariableDeclarator {
-                    id: Some(Rc::new(Node::Identifier("result".to_string()))),
-                    init: Some(Rc::new(Node::AdditiveExpression {
-                        operator: '+',
-                        left: Some(Rc::new(Node::CallExpression {
-                            callee: Some(Rc::new(Node::Identifier(
-                                "foo".to_string(),
+                body: Some(Rc::new(Node::new(NodeKind::BlockStatement {
+                    body: [Some(Rc::new(Node::new(
+                        NodeKind::ReturnStatement {
+                            argument: Some(Rc::new(Node::new(
+                                NodeKind::NumericLiteral(42),
                             ))),
-                            arguments: Vec::new(),
-                        })),
-                        right: Some(Rc::new(Node::NumericLiteral(555))),
-                    })),
-                }))]
+                        },
+                    )))]
+                    .to_vec(),
+                }))),
+            })),
+            Rc::new(Node::new(NodeKind::VariableDeclaration {
+                declarations: [Some(Rc::new(Node::new(
+                    NodeKind::VariableDeclarator {
+                        id: Some(Rc::new(Node::new(NodeKind::Identifier(
+                            "result".to_string(),
+                        )))),
+                        init: Some(Rc::new(Node::new(
+                            NodeKind::AdditiveExpression {
+                                operator: '+',
+                                left: Some(Rc::new(Node::new(
+                                    NodeKind::CallExpression {
+                                        callee: Some(Rc::new(Node::new(
+                                            NodeKind::Identifier(
+                                                "foo".to_string(),
+                                            ),
+                                        ))),
+                                        arguments: Vec::new(),
+                                    },
+                                ))),
+                                right: Some(Rc::new(Node::new(
+                                    NodeKind::NumericLiteral(555),
+                                ))),
+                            },
+                        ))),
+                    },
+                )))]
                 .to_vec(),
-            }),
+            })),
         ]
         .to_vec();
 
@@ -784,34 +979,123 @@ foo(100, 400)"#
         let mut parser = create_parser(input);
         let mut expected = Program::new();
         let body = [
-            Rc::new(Node::FunctionDeclaration {
-                id: Some(Rc::new(Node::Identifier("foo".to_string()))),
+            Rc::new(Node::new(NodeKind::FunctionDeclaration {
+                id: Some(Rc::new(Node::new(NodeKind::Identifier(
+                    "foo".to_string(),
+                )))),
                 params: [
-                    Some(Rc::new(Node::Identifier("hoge".to_string()))),
-                    Some(Rc::new(Node::Identifier("fuga".to_string()))),
+                    Some(Rc::new(Node::new(NodeKind::Identifier(
+                        "hoge".to_string(),
+                    )))),
+                    Some(Rc::new(Node::new(NodeKind::Identifier(
+                        "fuga".to_string(),
+                    )))),
                 ]
                 .to_vec(),
-                body: Some(Rc::new(Node::BlockStatement {
-                    body: [Some(Rc::new(Node::ReturnStatement {
-                        argument: Some(Rc::new(Node::NumericLiteral(42))),
-                    }))]
+                body: Some(Rc::new(Node::new(NodeKind::BlockStatement {
+                    body: [Some(Rc::new(Node::new(
+                        NodeKind::ReturnStatement {
+                            argument: Some(Rc::new(Node::new(
+                                NodeKind::NumericLiteral(42),
+                            ))),
+                        },
+                    )))]
                     .to_vec(),
-                })),
-            }),
-            Rc::new(Node::ExpressionStatement(Some(Rc::new(
-                Node::CallExpression {
-                    callee: Some(Rc::new(Node::Identifier("foo".to_string()))),
+                }))),
+            })),
+            Rc::new(Node::new(NodeKind::ExpressionStatement(Some(Rc::new(
+                Node::new(NodeKind::CallExpression {
+                    callee: Some(Rc::new(Node::new(NodeKind::Identifier(
+                        "foo".to_string(),
+                    )))),
                     arguments: [
-                        Some(Rc::new(Node::NumericLiteral(100))),
-                        Some(Rc::new(Node::NumericLiteral(400))),
+                        Some(Rc::new(Node::new(NodeKind::NumericLiteral(100)))),
+                        Some(Rc::new(Node::new(NodeKind::NumericLiteral(400)))),
                     ]
                     .to_vec(),
-                },
-            )))),
+                }),
+            ))))),
         ]
         .to_vec();
 
         expected.set_body(body);
         assert_eq!(expected, parser.parse_ast());
     }
+
+    fn position(
+        node: &Option<Rc<Node>>,
+    ) -> Option<((usize, usize), (usize, usize))> {
+        node.as_ref().and_then(|node| node.span()).map(|span| {
+            (
+                (span.start().line(), span.start().column()),
+                (span.end().line(), span.end().column()),
+            )
+        })
+    }
+
+    #[test]
+    fn test_node_spans() {
+        let input =
+            "var a = 1;\nfunction f() {\n  return a + 2;\n}".to_string();
+        let mut parser = create_parser(input);
+        let program = parser.parse_ast();
+        assert!(parser.errors().is_empty());
+
+        // 文の範囲は、最後の";"まで含む
+        let var = Some(program.body()[0].clone());
+        assert_eq!(Some(((1, 1), (1, 11))), position(&var));
+        let declarator = match var.as_deref().map(Node::kind) {
+            Some(NodeKind::VariableDeclaration { declarations }) => {
+                declarations[0].clone()
+            }
+            _ => panic!("expected a variable declaration"),
+        };
+        assert_eq!(Some(((1, 5), (1, 10))), position(&declarator));
+        let init = match declarator.as_deref().map(Node::kind) {
+            Some(NodeKind::VariableDeclarator { init, .. }) => init.clone(),
+            _ => panic!("expected a variable declarator"),
+        };
+        assert_eq!(Some(((1, 9), (1, 10))), position(&init));
+
+        let function = Some(program.body()[1].clone());
+        assert_eq!(Some(((2, 1), (4, 2))), position(&function));
+        let body = match function.as_deref().map(Node::kind) {
+            Some(NodeKind::FunctionDeclaration { body, .. }) => body.clone(),
+            _ => panic!("expected a function declaration"),
+        };
+        assert_eq!(Some(((2, 14), (4, 2))), position(&body));
+        let statement = match body.as_deref().map(Node::kind) {
+            Some(NodeKind::BlockStatement { body }) => body[0].clone(),
+            _ => panic!("expected a block statement"),
+        };
+        assert_eq!(Some(((3, 3), (3, 16))), position(&statement));
+        let argument = match statement.as_deref().map(Node::kind) {
+            Some(NodeKind::ReturnStatement { argument }) => argument.clone(),
+            _ => panic!("expected a return statement"),
+        };
+        assert_eq!(Some(((3, 10), (3, 15))), position(&argument));
+
+        // 構文解析器以外で作ったノードは範囲を持たないが、比較では範囲を無視する
+        assert_eq!(None, position(&Node::new_numeric_literal(1)));
+        assert_eq!(Node::new_numeric_literal(1), init);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        // 閉じていない関数本体や引数リストがあっても、解析は終了する
+        let mut parser =
+            create_parser("function f() {\n  return 1;".to_string());
+        parser.parse_ast();
+        let errors = parser.errors();
+        assert_eq!(1, errors.len());
+        assert_eq!("missing '}' after function body", errors[0].message());
+        assert_eq!(2, errors[0].span().start().line());
+        assert_eq!(12, errors[0].span().start().column());
+
+        let mut parser = create_parser("foo(1".to_string());
+        parser.parse_ast();
+        let messages: Vec<String> =
+            parser.errors().iter().map(|e| e.message()).collect();
+        assert_eq!(vec!["missing ')' after arguments".to_string()], messages);
+    }
 }
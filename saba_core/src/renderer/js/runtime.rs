@@ -6,16 +6,19 @@ use core::ops::Add;
 use core::ops::Sub;
 
 use super::ast::Node;
+use super::ast::NodeKind;
 use super::ast::Program;
+use crate::http::cookie::CookieJar;
+use crate::http::cookie::CookieSource;
 use crate::renderer::dom::api::get_element_by_id;
 use crate::renderer::dom::node::Node as DomNode;
 use crate::renderer::dom::node::NodeKind as DomNodeKind;
+use crate::url::Url;
 use alloc::format;
 use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
-use core::borrow::Borrow;
 use core::cell::RefCell;
 
 // 関数定義の情報を保持する構造体
@@ -41,6 +44,9 @@ pub struct JsRuntime {
     dom_root: Rc<RefCell<DomNode>>,
     functions: Vec<Function>,
     env: Rc<RefCell<Environment>>,
+    // document.cookieで読み書きするCookieと、ドキュメントのURL
+    cookie_jar: Option<Rc<RefCell<CookieJar>>>,
+    document_url: Option<Url>,
 }
 
 // 変数名とその変数の値を管理する辞書
@@ -168,6 +174,49 @@ impl JsRuntime {
             dom_root,
             functions: Vec::new(),
             env: Rc::new(RefCell::new(Environment::new(None))),
+            cookie_jar: None,
+            document_url: None,
+        }
+    }
+
+    /// document.cookieで読み書きするCookieの入れ物と、ドキュメントのURLを設定する
+    /// 設定されていない場合、document.cookieは空文字列になり、代入は無視される。
+    pub fn set_cookie_jar(
+        &mut self,
+        cookie_jar: Rc<RefCell<CookieJar>>,
+        document_url: Url,
+    ) {
+        self.cookie_jar = Some(cookie_jar);
+        self.document_url = Some(document_url);
+    }
+
+    // HttpOnlyのCookieは、スクリプトからは読み書きできない
+    fn document_cookie(&self) -> String {
+        match (&self.cookie_jar, &self.document_url) {
+            (Some(cookie_jar), Some(url)) => cookie_jar
+                .as_ref()
+                .borrow()
+                .cookie_header(
+                    &url.host(),
+                    &url.path(),
+                    false,
+                    CookieSource::Script,
+                )
+                .unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+
+    fn set_document_cookie(&mut self, cookie: &str) {
+        if let (Some(cookie_jar), Some(url)) =
+            (&self.cookie_jar, &self.document_url)
+        {
+            cookie_jar.borrow_mut().store(
+                cookie,
+                &url.host(),
+                &url.path(),
+                CookieSource::Script,
+            );
         }
     }
 
@@ -187,8 +236,8 @@ impl JsRuntime {
             None => return None,
         };
 
-        match node.borrow() {
-            Node::FunctionDeclaration { id, params, body } => {
+        match node.kind() {
+            NodeKind::FunctionDeclaration { id, params, body } => {
                 if let Some(RuntimeValue::StringLiteral(id)) =
                     self.eval(&id, env.clone())
                 {
@@ -205,7 +254,7 @@ impl JsRuntime {
                 }
                 None
             }
-            Node::CallExpression { callee, arguments } => {
+            NodeKind::CallExpression { callee, arguments } => {
                 // 新しいスコープをスコープを作成する
                 let new_env = Rc::new(RefCell::new(Environment::new(Some(env))));
                 let callee_value = match self.eval(callee, new_env.clone()) {
@@ -245,7 +294,7 @@ impl JsRuntime {
                 self.eval(&function.body.clone(), new_env.clone())
             }
 
-            Node::BlockStatement { body } => {
+            NodeKind::BlockStatement { body } => {
                 // 関数呼び出し時にスコープ内のステートメント呼び出す。
                 let mut result: Option<RuntimeValue> = None;
                 for statement in body {
@@ -253,13 +302,13 @@ impl JsRuntime {
                 }
                 result
             }
-            Node::ReturnStatement { argument } => {
+            NodeKind::ReturnStatement { argument } => {
                 return self.eval(&argument, env.clone());
             }
-            Node::ExpressionStatement(expr) => {
+            NodeKind::ExpressionStatement(expr) => {
                 return self.eval(expr, env.clone())
             }
-            Node::AdditiveExpression {
+            NodeKind::AdditiveExpression {
                 operator,
                 left,
                 right,
@@ -281,7 +330,7 @@ impl JsRuntime {
                     None
                 }
             }
-            Node::AssignmentExpression {
+            NodeKind::AssignmentExpression {
                 operator,
                 left,
                 right,
@@ -289,9 +338,18 @@ impl JsRuntime {
                 if operator != &'=' {
                     return None;
                 }
+                // document.cookie = "name=value"のようにCookieを設定する
+                if let Some(node) = left {
+                    if is_document_cookie(node) {
+                        if let Some(value) = self.eval(right, env.clone()) {
+                            self.set_document_cookie(&value.to_string());
+                        }
+                        return None;
+                    }
+                }
                 // 変数の再割り当て
                 if let Some(node) = left {
-                    if let Node::Identifier(id) = node.borrow() {
+                    if let NodeKind::Identifier(id) = node.kind() {
                         let new_value = self.eval(right, env.clone());
                         env.borrow_mut()
                             .update_variable(id.to_string(), new_value);
@@ -321,7 +379,13 @@ impl JsRuntime {
                 None
             }
 
-            Node::MemberExpression { object, property } => {
+            NodeKind::MemberExpression { object, property } => {
+                if is_document_cookie(node) {
+                    return Some(RuntimeValue::StringLiteral(
+                        self.document_cookie(),
+                    ));
+                }
+
                 let object_value = match self.eval(object, env.clone()) {
                     Some(value) => value,
                     None => return None,
@@ -351,24 +415,26 @@ impl JsRuntime {
                         + property_value,
                 );
             }
-            Node::NumericLiteral(value) => Some(RuntimeValue::Number(*value)),
-            Node::VariableDeclaration { declarations } => {
+            NodeKind::NumericLiteral(value) => {
+                Some(RuntimeValue::Number(*value))
+            }
+            NodeKind::VariableDeclaration { declarations } => {
                 for dec in declarations {
                     self.eval(dec, env.clone());
                 }
                 None
             }
-            Node::VariableDeclarator { id, init } => {
+            NodeKind::VariableDeclarator { id, init } => {
                 // var a = 10;のような変数定義の時にここに入り、aが、Identifierで、10がRuntimeValueとなる。
                 if let Some(node) = id {
-                    if let Node::Identifier(name) = node.borrow() {
+                    if let NodeKind::Identifier(name) = node.kind() {
                         let init = self.eval(init, env.clone());
                         env.borrow_mut().add_variable(name.to_string(), init);
                     }
                 }
                 None
             }
-            Node::Identifier(name) => {
+            NodeKind::Identifier(name) => {
                 match env.borrow_mut().get_variable(name.to_string()) {
                     Some(v) => Some(v),
                     // 変数名が初めて使用される場合は、まだ値が保存されていないので、文字列として扱う
@@ -377,7 +443,7 @@ impl JsRuntime {
                     None => Some(RuntimeValue::StringLiteral(name.to_string())),
                 }
             }
-            Node::StringLiteral(value) => {
+            NodeKind::StringLiteral(value) => {
                 Some(RuntimeValue::StringLiteral(value.to_string()))
             }
         }
@@ -433,6 +499,15 @@ impl JsRuntime {
     }
 }
 
+// document.cookieへのアクセスかどうか
+fn is_document_cookie(node: &Node) -> bool {
+    if let NodeKind::MemberExpression { object, property } = node.kind() {
+        return matches!(object.as_deref().map(Node::kind), Some(NodeKind::Identifier(o)) if o == "document")
+            && matches!(property.as_deref().map(Node::kind), Some(NodeKind::Identifier(p)) if p == "cookie");
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::string::{String, ToString};
@@ -647,4 +722,58 @@ foo() + a"#
             assert_eq!(runtime.env.borrow_mut().get_variable(name), val);
         }
     }
+
+    #[test]
+    fn test_document_cookie() {
+        let (ast, mut runtime) = create_runtime(
+            r#"
+document.cookie = "theme=dark";
+document.cookie = "session=changed";
+document.cookie"#
+                .to_string(),
+        );
+        let cookie_jar = Rc::new(RefCell::new(CookieJar::new()));
+        cookie_jar.borrow_mut().store(
+            "session=abc; HttpOnly",
+            "example.com",
+            "/",
+            CookieSource::Http,
+        );
+        let url = Url::parse("http://example.com/index.html")
+            .expect("failed to parse url");
+        runtime.set_cookie_jar(cookie_jar.clone(), url);
+
+        // HttpOnlyのCookieは読み書きできない
+        let expected = [
+            None,
+            None,
+            Some(RuntimeValue::StringLiteral("theme=dark".to_string())),
+        ];
+        for (i, node) in ast.body().iter().enumerate() {
+            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+            assert_eq!(expected[i], result);
+        }
+        assert_eq!(
+            Some("session=abc; theme=dark".to_string()),
+            cookie_jar.as_ref().borrow().cookie_header(
+                "example.com",
+                "/",
+                false,
+                CookieSource::Http
+            )
+        );
+    }
+
+    #[test]
+    fn test_document_cookie_without_jar() {
+        let (ast, mut runtime) = create_runtime(
+            r#"document.cookie = "a=b"; document.cookie"#.to_string(),
+        );
+        let expected =
+            [None, Some(RuntimeValue::StringLiteral("".to_string()))];
+        for (i, node) in ast.body().iter().enumerate() {
+            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+            assert_eq!(expected[i], result);
+        }
+    }
 }
@@ -1,3 +1,9 @@
+use crate::renderer::diagnostics::LineMap;
+use crate::renderer::diagnostics::ParseError;
+use crate::renderer::diagnostics::SourceKind;
+use crate::renderer::diagnostics::Span;
+use crate::renderer::diagnostics::Spanned;
+use alloc::format;
 use alloc::string::ToString;
 use alloc::{string::String, vec::Vec};
 
@@ -18,23 +24,78 @@ pub enum Token {
 pub struct JsLexer {
     pos: usize,
     input: Vec<char>,
+    // 入力の位置を行と列に変換するための表
+    line_map: LineMap,
+    // 最後に返したトークンのソース上の範囲
+    latest_span: Span,
+    // 字句解析中に見つかったエラー
+    errors: Vec<ParseError>,
 }
 
 impl JsLexer {
     pub fn new(js: String) -> Self {
+        let input: Vec<char> = js.chars().collect();
+        let line_map = LineMap::new(&input);
         Self {
             pos: 0,
-            input: js.chars().collect(),
+            input,
+            latest_span: line_map.span(0, 0),
+            line_map,
+            errors: Vec::new(),
         }
     }
 
+    /// 字句解析中に見つかったエラーを返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        self.errors.clone()
+    }
+
+    fn parse_error(&mut self, message: String, start: usize) {
+        self.errors.push(ParseError::new(
+            SourceKind::Js,
+            message,
+            self.line_map.span(start, self.pos),
+        ));
+    }
+
+    // 正しいがサポートしていない文字を読み飛ばしたことを記録する
+    fn unsupported(&mut self, message: String, start: usize) {
+        self.errors.push(ParseError::unsupported(
+            SourceKind::Js,
+            message,
+            self.line_map.span(start, self.pos),
+        ));
+    }
+
+    // "//"と"/* */"のコメントを読み飛ばす。コメントがなかった場合はfalseを返す
+    fn skip_comment(&mut self) -> bool {
+        let start = self.pos;
+        if self.contains("//") {
+            while !matches!(self.input.get(self.pos), Some('\n') | None) {
+                self.pos += 1;
+            }
+            return true;
+        }
+        if self.contains("/*") {
+            self.pos += 2;
+            while !self.contains("*/") {
+                if self.pos >= self.input.len() {
+                    self.parse_error("unterminated comment".to_string(), start);
+                    return true;
+                }
+                self.pos += 1;
+            }
+            self.pos += 2;
+            return true;
+        }
+        false
+    }
+
     // keywordが、self.inputの現位置から一致しているかを判断する
     fn contains(&self, keyword: &str) -> bool {
         // self.posから1文字づつ比較して、途中で文字が一致しなくなった場合はfalse
-        for i in 0..keyword.len() {
-            if keyword.chars().nth(i).expect("failed to access to i-th char")
-                != self.input[self.pos + i]
-            {
+        for (i, c) in keyword.chars().enumerate() {
+            if self.input.get(self.pos + i) != Some(&c) {
                 return false;
             }
         }
@@ -61,6 +122,7 @@ impl JsLexer {
             }
             if self.input[self.pos].is_ascii_alphanumeric()
                 || self.input[self.pos] == '$'
+                || self.input[self.pos] == '_'
             {
                 result.push(self.input[self.pos]);
                 self.pos += 1
@@ -70,17 +132,24 @@ impl JsLexer {
         }
     }
 
-    fn consume_string(&mut self) -> String {
+    // quoteで囲まれた文字列を消費する。quoteは'"'か'\''
+    fn consume_string(&mut self, quote: char) -> String {
+        let start = self.pos;
         let mut result = String::new();
         self.pos += 1;
 
         loop {
             if self.pos >= self.input.len() {
+                // 閉じるクォートがないまま入力が終わった
+                self.parse_error(
+                    "unterminated string literal".to_string(),
+                    start,
+                );
                 return result;
             }
 
-            // ダブルクォーとが出てきた時点で、文字列は終了
-            if self.input[self.pos] == '"' {
+            // 開始と同じクォートが出てきた時点で、文字列は終了
+            if self.input[self.pos] == quote {
                 self.pos += 1;
                 return result;
             }
@@ -116,41 +185,73 @@ impl Iterator for JsLexer {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.input.len() {
-            return None;
-        }
-
-        while self.input[self.pos] == ' ' || self.input[self.pos] == '\n' {
-            self.pos += 1;
+        loop {
+            match self.input.get(self.pos) {
+                Some(c) if c.is_whitespace() => {
+                    self.pos += 1;
+                    continue;
+                }
+                Some(_) => {}
+                None => {
+                    self.latest_span = self.line_map.span(self.pos, self.pos);
+                    return None;
+                }
+            }
+            if self.skip_comment() {
+                continue;
+            }
+            let start = self.pos;
 
-            if self.pos >= self.input.len() {
-                return None;
+            // 予約後が現れたら、Keywordトークンを返す
+            if let Some(reserved_word) = self.check_reserved_word() {
+                self.pos += reserved_word.len();
+                self.latest_span = self.line_map.span(start, self.pos);
+                return Some(Token::Keyword(reserved_word));
             }
-        }
 
-        // 予約後が現れたら、Keywordトークンを返す
-        if let Some(reserved_word) = self.check_reserved_word() {
-            self.pos += reserved_word.len();
-            return Some(Token::Keyword(reserved_word));
+            let c = self.input[self.pos];
+            let token = match c {
+                '+' | '-' | ';' | '=' | '(' | ')' | '{' | '}' | ',' | '.' => {
+                    let t = Token::Punctuator(c);
+                    self.pos += 1;
+                    t
+                }
+                // 文字の始まりが、変数名として定義できるもののとき
+                'a'..='z' | 'A'..='Z' | '_' | '$' => {
+                    Token::Identifier(self.consume_identifier())
+                }
+                '0'..='9' => Token::Number(self.consume_number()),
+                // 文字列の開始
+                '"' | '\'' => Token::StringLiteral(self.consume_string(c)),
+                // "<"や"["のような、サポートしていない演算子や区切り文字は読み飛ばす
+                '[' | ']' | '<' | '>' | '*' | '/' | '%' | '!' | '&' | '|'
+                | '^' | '~' | '?' | ':' | '`' => {
+                    self.pos += 1;
+                    self.unsupported(
+                        format!("unsupported character {:?}", c),
+                        start,
+                    );
+                    continue;
+                }
+                // JavaScriptで使えない文字は、エラーとして読み飛ばす
+                _ => {
+                    self.pos += 1;
+                    self.parse_error(
+                        format!("unexpected character {:?}", c),
+                        start,
+                    );
+                    continue;
+                }
+            };
+            self.latest_span = self.line_map.span(start, self.pos);
+            return Some(token);
         }
+    }
+}
 
-        let c = self.input[self.pos];
-        let token = match c {
-            '+' | '-' | ';' | '=' | '(' | ')' | '{' | '}' | ',' | '.' => {
-                let t = Token::Punctuator(c);
-                self.pos += 1;
-                t
-            }
-            // 文字の始まりが、変数名として定義できるもののとき
-            'a'..='z' | 'A'..='Z' | '_' | '$' => {
-                Token::Identifier(self.consume_identifier())
-            }
-            '0'..='9' => Token::Number(self.consume_number()),
-            // 文字列の開始
-            '"' => Token::StringLiteral(self.consume_string()),
-            _ => unimplemented!("char {:?} is not supported yet", c),
-        };
-        return Some(token);
+impl Spanned for JsLexer {
+    fn latest_span(&self) -> Span {
+        self.latest_span
     }
 }
 
@@ -285,4 +386,59 @@ var result = foo() + 1;
             i += 1;
         }
     }
+
+    #[test]
+    fn test_span_and_errors() {
+        let input = "var a = 1 < 2;\n  a".to_string();
+        let mut lexer = JsLexer::new(input);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next() {
+            let span = lexer.latest_span();
+            tokens.push((
+                token,
+                (span.start().line(), span.start().column()),
+                (span.end().line(), span.end().column()),
+            ));
+        }
+        // サポートしていない'<'は読み飛ばされる
+        let expected = [
+            (Token::Keyword("var".to_string()), (1, 1), (1, 4)),
+            (Token::Identifier("a".to_string()), (1, 5), (1, 6)),
+            (Token::Punctuator('='), (1, 7), (1, 8)),
+            (Token::Number(1), (1, 9), (1, 10)),
+            (Token::Number(2), (1, 13), (1, 14)),
+            (Token::Punctuator(';'), (1, 14), (1, 15)),
+            (Token::Identifier("a".to_string()), (2, 3), (2, 4)),
+        ];
+        assert_eq!(expected.to_vec(), tokens);
+
+        let errors = lexer.errors();
+        assert_eq!(1, errors.len());
+        assert_eq!("unsupported character '<'", errors[0].message());
+        assert!(errors[0].is_unsupported());
+        assert_eq!(1, errors[0].span().start().line());
+        assert_eq!(11, errors[0].span().start().column());
+    }
+
+    #[test]
+    fn test_comments_and_quotes() {
+        let input = "// comment\nvar a = 'x'; /* a\nb */ a\n#".to_string();
+        let mut lexer = JsLexer::new(input);
+        let tokens: Vec<Token> = lexer.by_ref().collect();
+        let expected = [
+            Token::Keyword("var".to_string()),
+            Token::Identifier("a".to_string()),
+            Token::Punctuator('='),
+            Token::StringLiteral("x".to_string()),
+            Token::Punctuator(';'),
+            Token::Identifier("a".to_string()),
+        ];
+        assert_eq!(expected.to_vec(), tokens);
+
+        // JavaScriptで使えない文字だけが構文エラーになる
+        let errors = lexer.errors();
+        assert_eq!(1, errors.len());
+        assert_eq!("unexpected character '#'", errors[0].message());
+        assert!(!errors[0].is_unsupported());
+    }
 }
@@ -243,8 +243,8 @@ impl FontSize {
     fn default(node: &Rc<RefCell<Node>>) -> Self {
         match &node.borrow().kind() {
             NodeKind::Element(elem) => match elem.kind() {
-                ElementKind::H1 => FontSize::XXLarge,
-                ElementKind::H2 => FontSize::XLarge,
+                Some(ElementKind::H1) => FontSize::XXLarge,
+                Some(ElementKind::H2) => FontSize::XLarge,
                 _ => FontSize::Medium,
             },
             _ => FontSize::Medium,
@@ -264,6 +264,13 @@ impl DisplayType {
         match &node.borrow().kind() {
             NodeKind::Document => DisplayType::Block,
             NodeKind::Element(e) => {
+                // 表示しない要素。<iframe>は子のページの読み込みをサポートしていないため、表示しない
+                // https://html.spec.whatwg.org/multipage/rendering.html#hidden-elements
+                if e.is_hidden_element()
+                    || e.kind() == Some(ElementKind::Iframe)
+                {
+                    return DisplayType::DisplayNone;
+                }
                 if e.is_block_element() {
                     DisplayType::Block
                 } else {
@@ -271,6 +278,10 @@ impl DisplayType {
                 }
             }
             NodeKind::Text(_) => DisplayType::Inline,
+            // コメントと文書型宣言は描画しない
+            NodeKind::Comment(_) | NodeKind::DocumentType { .. } => {
+                DisplayType::DisplayNone
+            }
         }
     }
 
@@ -297,7 +308,7 @@ impl TextDecoration {
     fn default(node: &Rc<RefCell<Node>>) -> Self {
         match &node.borrow().kind() {
             NodeKind::Element(element) => match element.kind() {
-                ElementKind::A => TextDecoration::Underline,
+                Some(ElementKind::A) => TextDecoration::Underline,
                 _ => TextDecoration::None,
             },
             _ => TextDecoration::None,
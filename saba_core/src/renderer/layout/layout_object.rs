@@ -150,9 +150,9 @@ impl LayoutObject {
                     }
                     false
                 }
-                // このnodeのnodekindが一致している場合は、true
+                // このnodeのタグ名が一致している場合は、true
                 Selector::TypeSelector(tag) => {
-                    if elem.kind().to_string() == *tag {
+                    if elem.local_name() == *tag {
                         return true;
                     }
                     false
@@ -240,6 +240,11 @@ impl LayoutObject {
                 DisplayType::Inline => LayoutObjectKind::Inline,
             },
             NodeKind::Text(_) => LayoutObjectKind::Text,
+            NodeKind::Comment(_) | NodeKind::DocumentType { .. } => {
+                panic!(
+                    "should not create a layout object for a non-rendered node"
+                )
+            }
         };
     }
 
@@ -425,6 +430,10 @@ impl LayoutObject {
         self.node.borrow().kind().clone()
     }
 
+    pub fn node(&self) -> Rc<RefCell<Node>> {
+        self.node.clone()
+    }
+
     pub fn set_first_child(
         &mut self,
         first_child: Option<Rc<RefCell<LayoutObject>>>,
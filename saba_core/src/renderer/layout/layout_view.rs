@@ -177,6 +177,34 @@ impl LayoutView {
         }
     }
 
+    /// DOMノードに対応するレイアウトオブジェクトを探す
+    /// display: noneなどで描画されないノードの場合はNoneを返す。
+    pub fn find_node_by_dom(
+        &self,
+        target: &Rc<RefCell<Node>>,
+    ) -> Option<Rc<RefCell<LayoutObject>>> {
+        Self::find_node_by_dom_internal(&self.root(), target)
+    }
+
+    fn find_node_by_dom_internal(
+        node: &Option<Rc<RefCell<LayoutObject>>>,
+        target: &Rc<RefCell<Node>>,
+    ) -> Option<Rc<RefCell<LayoutObject>>> {
+        let n = node.as_ref()?;
+        if Rc::ptr_eq(&n.borrow().node(), target) {
+            return Some(n.clone());
+        }
+
+        let first_child = n.borrow().first_child();
+        if let Some(found) =
+            Self::find_node_by_dom_internal(&first_child, target)
+        {
+            return Some(found);
+        }
+        let next_sibling = n.borrow().next_sibling();
+        Self::find_node_by_dom_internal(&next_sibling, target)
+    }
+
     // レイアウトツリーの各ノードのサイズを再帰的に計算する
     fn calculate_node_size(
         node: &Option<Rc<RefCell<LayoutObject>>>,
@@ -399,4 +427,22 @@ mod tests {
             .next_sibling()
             .is_none());
     }
+
+    #[test]
+    fn test_generic_elements() {
+        let html = "<html><head><style>span{display:none;}</style></head><body><span>a</span><div>b</div><template>c</template></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+
+        // タイプセレクタは、ElementKindにない要素にも適用される
+        let root = layout_view.root().expect("root should exist");
+        let div = root.borrow().first_child().expect("div should exist");
+        assert_eq!(LayoutObjectKind::Block, div.borrow().kind());
+        assert_eq!(
+            NodeKind::Element(Element::new("div", Vec::new())),
+            div.borrow().node_kind()
+        );
+
+        // <template>は描画しない
+        assert!(div.borrow().next_sibling().is_none());
+    }
 }
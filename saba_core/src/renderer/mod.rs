@@ -1,4 +1,5 @@
 pub mod css;
+pub mod diagnostics;
 pub mod dom;
 pub mod html;
 pub mod js;
@@ -5,15 +5,25 @@ use crate::http::HttpResponse;
 use crate::renderer::css::cssom::StyleSheet;
 use crate::renderer::css::parser::CssParser;
 use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::diagnostics::sort_errors;
+use crate::renderer::diagnostics::ParseError;
+use crate::renderer::diagnostics::SourcePosition;
+use crate::renderer::dom::api::get_element_by_id;
 use crate::renderer::dom::api::get_js_content;
 use crate::renderer::dom::api::get_style_content;
+use crate::renderer::dom::api::get_target_element_node;
 use crate::renderer::dom::window::Window;
 use crate::renderer::html::parser::HtmlParser;
 use crate::renderer::html::token::HtmlTokenizer;
 use crate::renderer::js::ast::JsParser;
 use crate::renderer::js::runtime::JsRuntime;
 use crate::renderer::js::token::JsLexer;
+use crate::renderer::layout::layout_object::LayoutPoint;
+use crate::renderer::layout::layout_object::LayoutSize;
 use crate::renderer::layout::layout_view::LayoutView;
+use crate::url::percent_decode;
+use crate::url::Scheme;
+use crate::url::Url;
 use crate::utils::convert_dom_to_string;
 
 use alloc::rc::Rc;
@@ -23,29 +33,41 @@ use alloc::vec::Vec;
 use core::cell::RefCell;
 
 use super::dom::element::ElementKind;
+use super::dom::node::Node;
 use super::dom::node::NodeKind;
 
 // Browserのタブを管理する構造体
 #[derive(Debug, Clone)]
 pub struct Page {
     browser: Weak<RefCell<Browser>>,
+    // 表示しているドキュメントのURL。document.cookieの対象や、相対URLの解決に使用する
+    url: Option<Url>,
     frame: Option<Rc<RefCell<Window>>>,
     style: Option<StyleSheet>,
     layout_view: Option<LayoutView>,
     display_items: Vec<DisplayItem>,
+    // コンテンツエリアの縦方向のスクロール量
+    scroll_y: i64,
+    // ドキュメントのHTML、CSS、JavaScriptのパース中に見つかったエラー
+    diagnostics: Vec<ParseError>,
 }
 
 impl Page {
     pub fn new() -> Self {
         Self {
             browser: Weak::new(),
+            url: None,
             frame: None,
             style: None,
             layout_view: None,
             display_items: Vec::new(),
+            scroll_y: 0,
+            diagnostics: Vec::new(),
         }
     }
 
+    /// クリックされたリンクの移動先のURLを返す
+    /// href属性の値は、ドキュメントのベースURLを基準にして絶対URLに解決する。
     pub fn clicked(&self, position: (i64, i64)) -> Option<String> {
         // レイアウトビューで、どのノードがクリックされたかを判断し、aたぐで、　href属性があれば、それを返す。
         let view = match &self.layout_view {
@@ -53,11 +75,14 @@ impl Page {
             None => return None,
         };
 
+        // クリックされた位置を、スクロールする前の位置に変換する
+        let position = (position.0, position.1 + self.scroll_y);
         if let Some(n) = view.find_node_by_position(position) {
             if let Some(parent) = n.borrow().parent().upgrade() {
                 if let NodeKind::Element(e) = parent.borrow().node_kind() {
-                    if e.kind() == ElementKind::A {
-                        return e.get_attribute("href");
+                    if e.kind() == Some(ElementKind::A) {
+                        let href = e.get_attribute("href")?;
+                        return self.resolve_url(&href).map(|url| url.href());
                     }
                 }
             }
@@ -70,6 +95,104 @@ impl Page {
         self.browser = browser;
     }
 
+    /// 次に受け取るレスポンスのURLを設定する
+    pub fn set_url(&mut self, url: Url) {
+        self.url = Some(url);
+    }
+
+    /// 表示しているドキュメントのURL
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    /// urlが、表示しているドキュメントとフラグメントだけが異なるURLかを判断する
+    /// その場合は、ドキュメントを取得し直さずにnavigate_to_fragmentで移動する。
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#navigate-fragid
+    pub fn is_same_document(&self, url: &Url) -> bool {
+        let current = match (&self.url, &self.frame) {
+            (Some(current), Some(_)) => current,
+            _ => return false,
+        };
+        url.fragment().is_some()
+            && strip_fragment(&current.href()) == strip_fragment(&url.href())
+    }
+
+    /// 同じドキュメント内のフラグメントに移動し、対象の要素が上端にくるようにスクロールする
+    pub fn navigate_to_fragment(&mut self, url: Url) {
+        if let Some(frame) = &self.frame {
+            frame.borrow_mut().set_url(url.clone());
+        }
+        self.url = Some(url);
+        self.scroll_to_fragment();
+        self.add_history();
+    }
+
+    // 表示したURLをBrowserの履歴に追加する。about:のページは追加しない
+    fn add_history(&self) {
+        if let (Some(browser), Some(url)) = (self.browser.upgrade(), &self.url)
+        {
+            if url.scheme() != Scheme::About {
+                browser.borrow_mut().add_history(url.href());
+            }
+        }
+    }
+
+    /// コンテンツエリアの縦方向のスクロール量
+    pub fn scroll_y(&self) -> i64 {
+        self.scroll_y
+    }
+
+    // URLのフラグメントが示す要素までスクロールする
+    // フラグメントが空か"top"の場合は、ドキュメントの先頭までスクロールする。
+    // 対象の要素が見つからない場合は、スクロール位置を変えない。
+    // https://html.spec.whatwg.org/multipage/browsing-the-web.html#scroll-to-the-fragment-identifier
+    fn scroll_to_fragment(&mut self) {
+        let fragment = match self.url.as_ref().and_then(|url| url.fragment()) {
+            Some(fragment) => fragment,
+            None => return,
+        };
+        if let Some(y) = self.fragment_position(&fragment) {
+            self.scroll_y = y;
+        } else if fragment.is_empty() || fragment.eq_ignore_ascii_case("top") {
+            self.scroll_y = 0;
+        }
+    }
+
+    // フラグメントと同じidを持つ要素の、レイアウトツリー上のy座標を返す
+    // 見つからない場合は、パーセントデコードしたフラグメントでも探す。
+    fn fragment_position(&self, fragment: &str) -> Option<i64> {
+        let document = self.document()?;
+        let view = self.layout_view.as_ref()?;
+        let decoded =
+            String::from_utf8_lossy(&percent_decode(fragment)).to_string();
+        [fragment.to_string(), decoded].iter().find_map(|id| {
+            let element = get_element_by_id(Some(document.clone()), id)?;
+            let layout_object = view.find_node_by_dom(&element)?;
+            let y = layout_object.borrow().point().y();
+            Some(y)
+        })
+    }
+
+    /// ドキュメントのベースURLを基準にして、urlを解決する
+    /// 解決できない場合はNoneを返す。
+    pub fn resolve_url(&self, url: &str) -> Option<Url> {
+        match &self.frame {
+            Some(frame) => frame.borrow().resolve_url(url).ok(),
+            None => match &self.url {
+                Some(base) => base.resolve(url).ok(),
+                None => Url::parse(url).ok(),
+            },
+        }
+    }
+
+    /// ドキュメントのベースURL
+    pub fn base_url(&self) -> Option<Url> {
+        match &self.frame {
+            Some(frame) => frame.borrow().base_url(),
+            None => self.url.clone(),
+        }
+    }
+
     fn set_layout_view(&mut self) {
         let dom = match &self.frame {
             Some(frame) => frame.borrow().document(),
@@ -90,6 +213,9 @@ impl Page {
         self.execute_js();
         self.set_layout_view();
         self.paint_tree();
+        self.scroll_y = 0;
+        self.scroll_to_fragment();
+        self.add_history();
 
         // デバッグ用にDOMツリーを文字列として返す
         if let Some(frame) = &self.frame {
@@ -103,15 +229,27 @@ impl Page {
 
     fn create_frame(&mut self, html: String) {
         let html_tokenizer = HtmlTokenizer::new(html);
-        let frame = HtmlParser::new(html_tokenizer).construct_tree();
+        let mut html_parser = HtmlParser::new(html_tokenizer);
+        let frame = html_parser.construct_tree();
+        if let Some(url) = &self.url {
+            frame.borrow_mut().set_url(url.clone());
+        }
         let dom = frame.borrow().document();
+        let mut diagnostics = html_parser.errors();
 
-        let style = get_style_content(dom);
+        let style = get_style_content(dom.clone());
         let css_tokenizer = CssTokenizer::new(style);
-        let cssom = CssParser::new(css_tokenizer).parse_stylesheet();
+        let mut css_parser = CssParser::new(css_tokenizer);
+        let cssom = css_parser.parse_stylesheet();
+        // CSSのエラーの位置を、HTMLのソース上の位置にする
+        let base = content_position(&dom, ElementKind::Style);
+        diagnostics
+            .extend(css_parser.errors().iter().map(|e| e.offset_by(base)));
+        sort_errors(&mut diagnostics);
 
         self.frame = Some(frame);
         self.style = Some(cssom);
+        self.diagnostics = diagnostics;
     }
 
     fn paint_tree(&mut self) {
@@ -120,14 +258,34 @@ impl Page {
         }
     }
 
+    /// DOMツリーのルート(Documentノード)を返す
+    pub fn document(&self) -> Option<Rc<RefCell<Node>>> {
+        self.frame.as_ref().map(|frame| frame.borrow().document())
+    }
+
+    pub fn layout_view(&self) -> Option<LayoutView> {
+        self.layout_view.clone()
+    }
+
+    /// スクロール量を反映したディスプレイアイテムを返す
+    /// コンテンツエリアの上端より上にはみ出す部分は取り除く。
     pub fn display_items(&self) -> Vec<DisplayItem> {
-        self.display_items.clone()
+        self.display_items
+            .iter()
+            .filter_map(|item| scroll_display_item(item, self.scroll_y))
+            .collect()
     }
 
     pub fn clear_display_items(&mut self) {
         self.display_items.clear();
     }
 
+    /// ドキュメントのパース中に見つかったエラーを、HTMLのソース上の位置の順に返す
+    /// <style>と<script>の中のエラーも、HTMLのソース上の位置で表す。
+    pub fn diagnostics(&self) -> Vec<ParseError> {
+        self.diagnostics.clone()
+    }
+
     fn execute_js(&mut self) {
         let dom = match &self.frame {
             Some(frame) => frame.borrow().document(),
@@ -137,7 +295,316 @@ impl Page {
         let lexer = JsLexer::new(js);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
+        let base = content_position(&dom, ElementKind::Script);
+        self.diagnostics
+            .extend(parser.errors().iter().map(|e| e.offset_by(base)));
+        sort_errors(&mut self.diagnostics);
+        // 構文エラーがあるスクリプトは実行しない
+        if !parser.errors().is_empty() {
+            return;
+        }
         let mut runtime = JsRuntime::new(dom);
+        if let (Some(browser), Some(url)) = (self.browser.upgrade(), &self.url)
+        {
+            runtime.set_cookie_jar(browser.borrow().cookie_jar(), url.clone());
+        }
         runtime.execute(&ast);
     }
 }
+
+// <style>や<script>の中身のテキストが、HTMLのソース上で始まる位置
+fn content_position(
+    dom: &Rc<RefCell<Node>>,
+    element_kind: ElementKind,
+) -> SourcePosition {
+    get_target_element_node(Some(dom.clone()), element_kind)
+        .and_then(|node| node.borrow().first_child())
+        .and_then(|text| text.borrow().source_span())
+        .map(|span| span.start())
+        .unwrap_or(SourcePosition::new(1, 1))
+}
+
+// URLからフラグメントを取り除く
+fn strip_fragment(href: &str) -> &str {
+    match href.find('#') {
+        Some(i) => &href[..i],
+        None => href,
+    }
+}
+
+// ディスプレイアイテムをscroll_yだけ上に移動する
+fn scroll_display_item(
+    item: &DisplayItem,
+    scroll_y: i64,
+) -> Option<DisplayItem> {
+    match item {
+        DisplayItem::Rect {
+            style,
+            layout_point,
+            layout_size,
+        } => {
+            let top = layout_point.y() - scroll_y;
+            let bottom = top + layout_size.height();
+            if bottom <= 0 {
+                return None;
+            }
+            let top = top.max(0);
+            Some(DisplayItem::Rect {
+                style: style.clone(),
+                layout_point: LayoutPoint::new(layout_point.x(), top),
+                layout_size: LayoutSize::new(layout_size.width(), bottom - top),
+            })
+        }
+        DisplayItem::Text {
+            text,
+            style,
+            layout_point,
+        } => {
+            let y = layout_point.y() - scroll_y;
+            if y < 0 {
+                return None;
+            }
+            Some(DisplayItem::Text {
+                text: text.clone(),
+                style: style.clone(),
+                layout_point: LayoutPoint::new(layout_point.x(), y),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_url::DataUrl;
+    use crate::renderer::diagnostics::SourceKind;
+    use alloc::format;
+    use alloc::vec;
+
+    fn load(url: &str, html: &str) -> Page {
+        let mut page = Page::new();
+        page.set_url(Url::parse(url).expect("failed to parse url"));
+        let response =
+            HttpResponse::new(format!("HTTP/1.1 200 OK\r\n\r\n{}", html))
+                .expect("failed to parse http response");
+        page.receive_response(response);
+        page
+    }
+
+    fn resolve(page: &Page, url: &str) -> Option<String> {
+        page.resolve_url(url).map(|url| url.href())
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        let page = load(
+            "http://example.com:8000/docs/index.html",
+            "<html><body><a href=\"page2.html\">a</a></body></html>",
+        );
+        assert_eq!(
+            Some("http://example.com:8000/docs/page2.html".to_string()),
+            resolve(&page, "page2.html")
+        );
+        assert_eq!(
+            Some("http://example.com:8000/docs".to_string()),
+            resolve(&page, "/docs")
+        );
+        assert_eq!(
+            Some("http://other.com/".to_string()),
+            resolve(&page, "http://other.com")
+        );
+    }
+
+    #[test]
+    fn test_base_href() {
+        let page = load(
+            "http://example.com/a/index.html",
+            "<html><head><base href=\"/static/\"><base href=\"/ignored/\">\
+             </head><body><p>text</p></body></html>",
+        );
+        assert_eq!(
+            Some("http://example.com/static/".to_string()),
+            page.base_url().map(|url| url.href())
+        );
+        assert_eq!(
+            Some("http://example.com/static/page2.html".to_string()),
+            resolve(&page, "page2.html")
+        );
+
+        // hrefを持たない<base>は飛ばす
+        let page = load(
+            "http://example.com/a/index.html",
+            "<html><head><base target=_blank><base href=/x/></head></html>",
+        );
+        assert_eq!(
+            Some("http://example.com/x/".to_string()),
+            page.base_url().map(|url| url.href())
+        );
+    }
+
+    #[test]
+    fn test_no_url() {
+        let page = Page::new();
+        assert_eq!(None, resolve(&page, "page2.html"));
+        assert_eq!(
+            Some("http://example.com/".to_string()),
+            resolve(&page, "http://example.com")
+        );
+    }
+
+    const SECTIONS: &str = "<html><body><p id=\"first\">one</p>\
+         <p id=\"second\">two</p><p id=\"caf%C3%A9\">three</p>\
+         <p id=\"café\">four</p><a href=\"#top\">top</a></body></html>";
+
+    fn text_y(page: &Page, target: &str) -> Option<i64> {
+        page.display_items().iter().find_map(|item| match item {
+            DisplayItem::Text {
+                text, layout_point, ..
+            } if text == target => Some(layout_point.y()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_fragment_on_load() {
+        let page = load("http://example.com/index.html#second", SECTIONS);
+        assert!(page.scroll_y() > 0);
+        assert_eq!(Some(0), text_y(&page, "two"));
+        // 上端より上にはみ出したアイテムは描画しない
+        assert_eq!(None, text_y(&page, "one"));
+
+        let page = load("http://example.com/index.html#missing", SECTIONS);
+        assert_eq!(0, page.scroll_y());
+    }
+
+    #[test]
+    fn test_navigate_to_fragment() {
+        let mut page = load("http://example.com/index.html", SECTIONS);
+        let url = page.resolve_url("#second").expect("failed to resolve");
+        assert!(page.is_same_document(&url));
+        page.navigate_to_fragment(url);
+        assert_eq!(
+            Some("http://example.com/index.html#second".to_string()),
+            page.url().map(|url| url.href())
+        );
+        assert_eq!(Some(0), text_y(&page, "two"));
+        let second = page.scroll_y();
+
+        // idそのものと、パーセントデコードしたidのどちらでも探す
+        let url = page.resolve_url("#caf%C3%A9").expect("failed to resolve");
+        page.navigate_to_fragment(url);
+        assert_eq!(Some(0), text_y(&page, "three"));
+        let url = page.resolve_url("#café").expect("failed to resolve");
+        page.navigate_to_fragment(url);
+        assert!(page.scroll_y() > second);
+
+        // 見つからないフラグメントの場合は、スクロール位置を変えない
+        let scroll_y = page.scroll_y();
+        let url = page.resolve_url("#missing").expect("failed to resolve");
+        page.navigate_to_fragment(url);
+        assert_eq!(scroll_y, page.scroll_y());
+
+        let url = page.resolve_url("#top").expect("failed to resolve");
+        page.navigate_to_fragment(url);
+        assert_eq!(0, page.scroll_y());
+    }
+
+    #[test]
+    fn test_is_same_document() {
+        let page = load("http://example.com/index.html?q=1#a", SECTIONS);
+        let same = |url: &str| {
+            page.is_same_document(&Url::parse(url).expect("failed to parse"))
+        };
+        assert!(same("http://example.com/index.html?q=1#b"));
+        assert!(same("http://example.com/index.html?q=1#"));
+        assert!(!same("http://example.com/index.html?q=1"));
+        assert!(!same("http://example.com/index.html?q=2#b"));
+        assert!(!same("http://example.com/other.html#b"));
+
+        let empty = Page::new();
+        assert!(!empty.is_same_document(
+            &Url::parse("http://example.com/#a").expect("failed to parse")
+        ));
+    }
+
+    #[test]
+    fn test_click_after_scroll() {
+        let page = load("http://example.com/index.html#second", SECTIONS);
+        let y = page.display_items().iter().find_map(|item| match item {
+            DisplayItem::Text {
+                text, layout_point, ..
+            } if text == "top" => Some(layout_point.y()),
+            _ => None,
+        });
+        let y = y.expect("link should be displayed");
+        assert_eq!(
+            Some("http://example.com/index.html#top".to_string()),
+            page.clicked((1, y + 1))
+        );
+    }
+
+    #[test]
+    fn test_data_url() {
+        let url = "data:text/html,<p%20id=a>hello</p>";
+        let mut page = Page::new();
+        let url = Url::parse(url).expect("failed to parse url");
+        let response = DataUrl::parse(&url)
+            .and_then(|data| data.to_response())
+            .expect("failed to create response");
+        page.set_url(url);
+        page.receive_response(response);
+        assert_eq!(Some(0), text_y(&page, "hello"));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let page = load(
+            "http://example.com",
+            "<!DOCTYPE html>\n<html><head><style>p { color red; }</style></head>\n<body><script>var a = 1 < 2;</script></body></html>",
+        );
+        // <style>と<script>の中のエラーは、HTML上の位置に変換される
+        let diagnostics: Vec<(SourceKind, String, usize, usize)> = page
+            .diagnostics()
+            .iter()
+            .map(|e| {
+                (
+                    e.kind(),
+                    e.message(),
+                    e.span().start().line(),
+                    e.span().start().column(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    SourceKind::Css,
+                    "expected ':' after color".to_string(),
+                    2,
+                    30
+                ),
+                (
+                    SourceKind::Js,
+                    "unsupported character '<'".to_string(),
+                    3,
+                    25
+                ),
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_script_with_syntax_error() {
+        // 構文エラーのあるスクリプトは、エラーを記録するだけで実行しない
+        let page = load(
+            "http://example.com",
+            "<html><body><p>text</p><script>if (a) { b(); }</script></body></html>",
+        );
+        assert!(page
+            .diagnostics()
+            .iter()
+            .any(|e| e.kind() == SourceKind::Js));
+        assert_eq!(Some(0), text_y(&page, "text"));
+    }
+}
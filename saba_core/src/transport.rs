@@ -0,0 +1,278 @@
+// HTTPクライアントが使用する通信路の抽象化
+// noli(wasabi OS)やstd::net、テスト用のインメモリ実装を差し替えられるようにする。
+
+use crate::error::Error;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::cell::RefCell;
+
+/// 確立済みの1本の接続。バイト列の送受信を行う。
+pub trait Connection {
+    /// bufの内容を送信し、送信したバイト数を返す
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error>;
+    /// 受信したデータをbufに書き込み、そのバイト数を返す。0の場合は相手が接続を閉じたことを表す
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+}
+
+/// ホストとポートから接続を作成する
+pub trait Transport {
+    type Connection: Connection;
+
+    fn connect(&self, host: &str, port: u16)
+        -> Result<Self::Connection, Error>;
+}
+
+// (ホスト, ポート, リクエストターゲット)をキーにして、生のレスポンスを保持する
+type FixtureMap = BTreeMap<(String, u16, String), Vec<u8>>;
+
+/// あらかじめ登録したレスポンスを返すインメモリのTransport
+/// ネットワークを使わずにHttpClientやページの読み込みをテストするために使用する。
+/// cloneしたインスタンス同士はフィクスチャと受信したリクエストを共有する。
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTransport {
+    fixtures: Rc<RefCell<FixtureMap>>,
+    requests: Rc<RefCell<Vec<Vec<u8>>>>,
+    // 作成した接続の数
+    connection_count: Rc<Cell<usize>>,
+    // close_connectionsが呼ばれるたびに増える。これより古い接続は閉じられたものとして扱う
+    generation: Rc<Cell<usize>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// host:portへの、targetに対するリクエストに返すレスポンスを登録する
+    /// target: リクエストラインのリクエストターゲット(example: "/index.html")
+    pub fn add_response(
+        &mut self,
+        host: &str,
+        port: u16,
+        target: &str,
+        raw_response: &[u8],
+    ) {
+        self.fixtures.borrow_mut().insert(
+            (host.to_string(), port, target.to_string()),
+            raw_response.to_vec(),
+        );
+    }
+
+    /// これまでに受信したリクエストを、受信した順に返す
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .borrow()
+            .iter()
+            .map(|r| String::from_utf8_lossy(r).to_string())
+            .collect()
+    }
+
+    /// これまでに作成した接続の数
+    pub fn connection_count(&self) -> usize {
+        self.connection_count.get()
+    }
+
+    /// 作成済みの接続を、サーバー側から閉じられた状態にする
+    pub fn close_connections(&self) {
+        self.generation.set(self.generation.get() + 1);
+    }
+}
+
+impl Transport for MemoryTransport {
+    type Connection = MemoryConnection;
+
+    fn connect(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Result<MemoryConnection, Error> {
+        // 1つもレスポンスが登録されていないホストには接続できない
+        let known_host = self
+            .fixtures
+            .borrow()
+            .keys()
+            .any(|(h, p, _)| h == host && *p == port);
+        if !known_host {
+            return Err(Error::Network(format!(
+                "Failed to connect to {}:{}",
+                host, port
+            )));
+        }
+
+        self.connection_count.set(self.connection_count.get() + 1);
+        Ok(MemoryConnection {
+            host: host.to_string(),
+            port,
+            fixtures: self.fixtures.clone(),
+            requests: self.requests.clone(),
+            transport_generation: self.generation.clone(),
+            generation: self.generation.get(),
+            written: Vec::new(),
+            response: None,
+            pos: 0,
+        })
+    }
+}
+
+/// MemoryTransportが作成する接続
+#[derive(Debug)]
+pub struct MemoryConnection {
+    host: String,
+    port: u16,
+    fixtures: Rc<RefCell<FixtureMap>>,
+    requests: Rc<RefCell<Vec<Vec<u8>>>>,
+    transport_generation: Rc<Cell<usize>>,
+    generation: usize,
+    // 送信されたリクエストのバイト列
+    written: Vec<u8>,
+    // 返却中のレスポンスと、その読み出し位置
+    response: Option<Vec<u8>>,
+    pos: usize,
+}
+
+impl MemoryConnection {
+    // 送信されたリクエストのリクエストラインからターゲットを取り出し、対応するレスポンスを探す
+    fn find_response(&self) -> Vec<u8> {
+        let request = String::from_utf8_lossy(&self.written);
+        let target = request
+            .lines()
+            .next()
+            .and_then(|line| line.split(' ').nth(1))
+            .unwrap_or("")
+            .to_string();
+
+        match self.fixtures.borrow().get(&(
+            self.host.clone(),
+            self.port,
+            target,
+        )) {
+            Some(response) => response.clone(),
+            None => {
+                b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec()
+            }
+        }
+    }
+}
+
+impl Connection for MemoryConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        // 前のレスポンスを返し終わった後の書き込みは、新しいリクエストとして扱う
+        if let Some(response) = &self.response {
+            if self.pos >= response.len() {
+                self.response = None;
+                self.written = Vec::new();
+                self.pos = 0;
+            }
+        }
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        // 閉じられた接続からは何も読み込めない
+        if self.generation != self.transport_generation.get() {
+            return Ok(0);
+        }
+
+        if self.response.is_none() {
+            self.requests.borrow_mut().push(self.written.clone());
+            self.response = Some(self.find_response());
+        }
+
+        let response = match &self.response {
+            Some(r) => r,
+            None => return Ok(0),
+        };
+        let size = core::cmp::min(buf.len(), response.len() - self.pos);
+        buf[..size].copy_from_slice(&response[self.pos..self.pos + size]);
+        self.pos += size;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(conn: &mut MemoryConnection) -> Vec<u8> {
+        let mut received = Vec::new();
+        loop {
+            let mut buf = [0u8; 8];
+            let size = conn.read(&mut buf).expect("failed to read");
+            if size == 0 {
+                return received;
+            }
+            received.extend_from_slice(&buf[..size]);
+        }
+    }
+
+    #[test]
+    fn test_unknown_host() {
+        let transport = MemoryTransport::new();
+        assert!(transport.connect("example.com", 80).is_err());
+    }
+
+    #[test]
+    fn test_fixture() {
+        let mut transport = MemoryTransport::new();
+        transport.add_response(
+            "example.com",
+            80,
+            "/index.html",
+            b"HTTP/1.1 200 OK\r\n\r\nbody",
+        );
+
+        let mut conn =
+            transport.connect("example.com", 80).expect("failed to connect");
+        conn.write(b"GET /index.html HTTP/1.1\r\n\r\n")
+            .expect("failed to write");
+        assert_eq!(
+            b"HTTP/1.1 200 OK\r\n\r\nbody".to_vec(),
+            read_all(&mut conn)
+        );
+        assert_eq!(
+            ["GET /index.html HTTP/1.1\r\n\r\n".to_string()].to_vec(),
+            transport.requests()
+        );
+    }
+
+    #[test]
+    fn test_not_found() {
+        let mut transport = MemoryTransport::new();
+        transport.add_response(
+            "example.com",
+            80,
+            "/",
+            b"HTTP/1.1 200 OK\r\n\r\n",
+        );
+
+        let mut conn =
+            transport.connect("example.com", 80).expect("failed to connect");
+        conn.write(b"GET /missing HTTP/1.1\r\n\r\n").expect("failed to write");
+        let response = read_all(&mut conn);
+        assert!(response.starts_with(b"HTTP/1.1 404 Not Found"));
+    }
+
+    #[test]
+    fn test_close_connections() {
+        let mut transport = MemoryTransport::new();
+        transport.add_response(
+            "example.com",
+            80,
+            "/",
+            b"HTTP/1.1 200 OK\r\n\r\n",
+        );
+
+        let mut conn =
+            transport.connect("example.com", 80).expect("failed to connect");
+        transport.close_connections();
+        conn.write(b"GET / HTTP/1.1\r\n\r\n").expect("failed to write");
+        assert!(read_all(&mut conn).is_empty());
+        assert_eq!(1, transport.connection_count());
+    }
+}
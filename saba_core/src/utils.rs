@@ -1,4 +1,10 @@
+use crate::display_item::DisplayItem;
+use crate::renderer::diagnostics::ParseError;
+use crate::renderer::diagnostics::SourceKind;
 use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeKind;
+use crate::renderer::layout::computed_style::Color;
+use crate::renderer::layout::layout_object::LayoutObject;
 use alloc::format;
 use alloc::rc::Rc;
 use alloc::string::String;
@@ -35,3 +41,228 @@ fn convert_dom_to_string_internal(
         None => (),
     }
 }
+
+/// DOMツリーを、1行1ノードのテキスト形式に変換する。
+/// convert_dom_to_stringと違い、Debug出力に依存しないため、テストやCIでの比較に使用できる。
+pub fn dump_dom(root: &Option<Rc<RefCell<Node>>>) -> String {
+    let mut result = String::new();
+    dump_dom_internal(root, 0, &mut result);
+    result
+}
+
+fn dump_dom_internal(
+    node: &Option<Rc<RefCell<Node>>>,
+    depth: usize,
+    result: &mut String,
+) {
+    if let Some(n) = node {
+        result.push_str(&"  ".repeat(depth));
+        result.push_str(&node_to_string(n));
+        result.push('\n');
+        dump_dom_internal(&n.borrow().first_child(), depth + 1, result);
+        dump_dom_internal(&n.borrow().next_sibling(), depth, result);
+    }
+}
+
+// ノードを1行の文字列に変換する。要素は属性付きのタグ、テキストはクォートした文字列になる。
+fn node_to_string(node: &Rc<RefCell<Node>>) -> String {
+    match node.borrow().kind() {
+        NodeKind::Document => String::from("#document"),
+        NodeKind::Element(e) => {
+            let mut s = format!("<{}", e.local_name());
+            for attr in e.attributes() {
+                s.push_str(&format!(" {}={:?}", attr.name(), attr.value()));
+            }
+            s.push('>');
+            s
+        }
+        NodeKind::Text(t) => format!("{:?}", t),
+        NodeKind::Comment(c) => format!("<!--{}-->", c),
+        NodeKind::DocumentType {
+            name,
+            public_id,
+            system_id,
+        } => {
+            if public_id.is_empty() && system_id.is_empty() {
+                format!("<!DOCTYPE {}>", name)
+            } else {
+                format!("<!DOCTYPE {} {:?} {:?}>", name, public_id, system_id)
+            }
+        }
+    }
+}
+
+/// レイアウトツリーを、1行1オブジェクトのテキスト形式に変換する。
+pub fn dump_layout_tree(root: &Option<Rc<RefCell<LayoutObject>>>) -> String {
+    let mut result = String::new();
+    dump_layout_tree_internal(root, 0, &mut result);
+    result
+}
+
+fn dump_layout_tree_internal(
+    node: &Option<Rc<RefCell<LayoutObject>>>,
+    depth: usize,
+    result: &mut String,
+) {
+    if let Some(n) = node {
+        let obj = n.borrow();
+        result.push_str(&"  ".repeat(depth));
+        result.push_str(&format!(
+            "{:?} {} ({}, {}) {}x{}\n",
+            obj.kind(),
+            node_to_string(&obj.node()),
+            obj.point().x(),
+            obj.point().y(),
+            obj.size().width(),
+            obj.size().height(),
+        ));
+        dump_layout_tree_internal(&obj.first_child(), depth + 1, result);
+        dump_layout_tree_internal(&obj.next_sibling(), depth, result);
+    }
+}
+
+/// ディスプレイアイテムのリストを、1行1アイテムのテキスト形式に変換する。
+pub fn dump_display_items(items: &[DisplayItem]) -> String {
+    let mut result = String::new();
+    for item in items {
+        match item {
+            DisplayItem::Rect {
+                style,
+                layout_point,
+                layout_size,
+            } => result.push_str(&format!(
+                "Rect ({}, {}) {}x{} background={}\n",
+                layout_point.x(),
+                layout_point.y(),
+                layout_size.width(),
+                layout_size.height(),
+                color_to_string(&style.background_color()),
+            )),
+            DisplayItem::Text {
+                text,
+                style,
+                layout_point,
+            } => result.push_str(&format!(
+                "Text ({}, {}) color={} font-size={:?} text-decoration={:?} {:?}\n",
+                layout_point.x(),
+                layout_point.y(),
+                color_to_string(&style.color()),
+                style.font_size(),
+                style.text_decoration(),
+                text,
+            )),
+        }
+    }
+    result
+}
+
+/// パースエラーを、"行:列: 種類: メッセージ"の形式で1行ずつ出力する
+pub fn dump_diagnostics(errors: &[ParseError]) -> String {
+    let mut result = String::new();
+    for error in errors {
+        let kind = match error.kind() {
+            SourceKind::Html => "html",
+            SourceKind::Css => "css",
+            SourceKind::Js => "js",
+        };
+        result.push_str(&format!(
+            "{}:{}: {}: {}\n",
+            error.span().start().line(),
+            error.span().start().column(),
+            kind,
+            error.message(),
+        ));
+    }
+    result
+}
+
+fn color_to_string(color: &Color) -> String {
+    format!("#{:06x}", color.code_u32())
+}
+
+/// HTMLのテキストや属性値として埋め込めるように、&, <, >, "を文字参照に置き換える
+/// ディレクトリの一覧やabout:のページなど、ブラウザが生成するHTMLで使用する。
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+    use crate::http::HttpResponse;
+    use crate::renderer::page::Page;
+
+    fn create_page(html: &str) -> Page {
+        let mut raw = "HTTP/1.1 200 OK\n\n".to_string();
+        raw.push_str(html);
+        let mut page = Page::new();
+        page.receive_response(
+            HttpResponse::new(raw).expect("failed to parse http response"),
+        );
+        page
+    }
+
+    #[test]
+    fn test_dump_dom() {
+        let page = create_page(
+            "<html><head></head><body><p id=\"a\">text</p></body></html>",
+        );
+        let expected = r#"#document
+  <html>
+    <head>
+    <body>
+      <p id="a">
+        "text"
+"#;
+        assert_eq!(expected, dump_dom(&page.document()));
+    }
+
+    #[test]
+    fn test_dump_layout_tree() {
+        let page =
+            create_page("<html><head></head><body><p>text</p></body></html>");
+        let expected = r#"Block <body> (0, 0) 590x20
+  Block <p> (0, 0) 590x20
+    Text "text" (0, 0) 32x20
+"#;
+        assert_eq!(
+            expected,
+            dump_layout_tree(
+                &page.layout_view().expect("layout view should exist").root()
+            )
+        );
+    }
+
+    #[test]
+    fn test_dump_display_items() {
+        let page =
+            create_page("<html><head></head><body><a>link</a></body></html>");
+        let expected = r#"Rect (0, 0) 590x20 background=#ffffff
+Text (0, 0) color=#000000 font-size=Medium text-decoration=Underline "link"
+"#;
+        assert_eq!(expected, dump_display_items(&page.display_items()));
+    }
+
+    #[test]
+    fn test_dump_diagnostics() {
+        let page = create_page(
+            "<!DOCTYPE html>\n<style>\np { color red; }</style>\n</p>",
+        );
+        let expected = r#"3:11: css: expected ':' after color
+4:1: html: end-tag-without-matching-open-element
+"#;
+        assert_eq!(expected, dump_diagnostics(&page.diagnostics()));
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            "&lt;a href=&quot;?a=1&amp;b=2&quot;&gt;",
+            escape_html("<a href=\"?a=1&b=2\">")
+        );
+    }
+}
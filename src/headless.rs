@@ -0,0 +1,150 @@
+// wasabi OSを使わずに、ホスト上でページを読み込んで、DOMツリー・レイアウトツリー・ディスプレイアイテム・パースエラーを出力するバイナリ
+//
+// 使い方:
+//   cargo run --no-default-features --features headless --bin rusa_headless -- [--dom] [--layout] [--display-items] [--diagnostics] <file path or URL>
+// 出力する項目を指定しなかった場合は、すべての項目を出力する。
+
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use net_std::clock::SystemClock;
+use net_std::file::FileHandler;
+use net_std::http::HttpClient;
+use net_std::transport::TcpTransport;
+use saba_core::about::AboutHandler;
+use saba_core::browser::Browser;
+use saba_core::error::Error;
+use saba_core::fetcher::FetchResponse;
+use saba_core::fetcher::Fetcher;
+use saba_core::fetcher::SchemeFetcher;
+use saba_core::http::HttpResponse;
+use saba_core::url::Scheme;
+use saba_core::url::Url;
+use saba_core::utils::dump_diagnostics;
+use saba_core::utils::dump_display_items;
+use saba_core::utils::dump_dom;
+use saba_core::utils::dump_layout_tree;
+
+const USAGE: &str =
+    "usage: rusa_headless [--dom] [--layout] [--display-items] [--diagnostics] <file path or URL>";
+
+// URLの場合はスキームに応じて読み込み、それ以外の場合はローカルファイルとして読み込む
+// HTTPの場合はリダイレクトを辿る。data:, file:, about:のURLはネットワークを使わずに読み込む
+fn load(
+    target: &str,
+    browser: &Rc<RefCell<Browser>>,
+) -> Result<FetchResponse, Error> {
+    if Url::parse(target).is_ok() {
+        let mut client = HttpClient::new(TcpTransport::new());
+        client.set_clock(Rc::new(SystemClock::new()));
+        client.set_cookie_jar(browser.borrow().cookie_jar());
+        client.set_cache(browser.borrow().http_cache());
+        client.set_net_log(browser.borrow().net_log());
+        let mut fetcher = SchemeFetcher::new(client);
+        fetcher.register(Scheme::File, Box::new(FileHandler::new()));
+        let mut about = AboutHandler::new(Rc::downgrade(browser));
+        about.set_features(vec!["headless".to_string()]);
+        fetcher.register(Scheme::About, Box::new(about));
+        return fetcher.fetch(target.to_string());
+    }
+
+    match fs::read(target) {
+        // ファイルの中身をHTTPレスポンスのボディとして扱う。文字エンコーディングは<meta charset>などから判定する
+        Ok(html) => {
+            let mut raw = b"HTTP/1.1 200 OK\n\n".to_vec();
+            raw.extend_from_slice(&html);
+            let response = HttpResponse::from_bytes(&raw)?;
+            Ok(FetchResponse::new(target.to_string(), response))
+        }
+        Err(e) => {
+            Err(Error::Other(format!("failed to read {}: {}", target, e)))
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut show_dom = false;
+    let mut show_layout = false;
+    let mut show_display_items = false;
+    let mut show_diagnostics = false;
+    let mut target = None;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--dom" => show_dom = true,
+            "--layout" => show_layout = true,
+            "--display-items" => show_display_items = true,
+            "--diagnostics" => show_diagnostics = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ if target.is_none() && !arg.starts_with("--") => {
+                target = Some(arg)
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let target = match target {
+        Some(t) => t,
+        None => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // 何も指定されなかった場合は全て出力する
+    if !show_dom && !show_layout && !show_display_items && !show_diagnostics {
+        show_dom = true;
+        show_layout = true;
+        show_display_items = true;
+        show_diagnostics = true;
+    }
+
+    let browser = Browser::new();
+    let cookie_jar = browser.borrow().cookie_jar();
+    cookie_jar.borrow_mut().set_clock(Rc::new(SystemClock::new()));
+
+    let response = match load(&target, &browser) {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("failed to load {}: {:?}", target, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let page = browser.borrow().current_page();
+    let mut page = page.borrow_mut();
+    if let Ok(url) = Url::parse(&response.url()) {
+        page.set_url(url);
+    }
+    page.receive_response(response.response());
+
+    if show_dom {
+        println!("== DOM ==");
+        print!("{}", dump_dom(&page.document()));
+    }
+    if show_layout {
+        println!("== Layout ==");
+        if let Some(layout_view) = page.layout_view() {
+            print!("{}", dump_layout_tree(&layout_view.root()));
+        }
+    }
+    if show_display_items {
+        println!("== Display Items ==");
+        print!("{}", dump_display_items(&page.display_items()));
+    }
+    if show_diagnostics {
+        println!("== Diagnostics ==");
+        print!("{}", dump_diagnostics(&page.diagnostics()));
+    }
+
+    ExitCode::SUCCESS
+}
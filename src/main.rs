@@ -3,25 +3,27 @@
 
 extern crate alloc;
 
-use crate::alloc::string::String;
-use crate::alloc::string::ToString;
-use alloc::format;
+use alloc::boxed::Box;
 use alloc::rc::Rc;
+use alloc::string::ToString;
+use alloc::vec;
 use core::cell::RefCell;
+use net_wasabi::clock::SystemClock;
 use net_wasabi::http::HttpClient;
+use net_wasabi::transport::TcpTransport;
 use noli::*;
-use saba_core::error::Error;
-use saba_core::url::Url;
 use ui_wasabi::app::WasabiUI;
 
+use saba_core::about::AboutHandler;
 use saba_core::browser::Browser;
-use saba_core::http::HttpResponse;
+use saba_core::fetcher::SchemeFetcher;
 use saba_core::renderer::css::parser::CssParser;
 use saba_core::renderer::css::token::CssTokenizer;
 use saba_core::renderer::dom::api::get_style_content;
 use saba_core::renderer::dom::window::Window;
 use saba_core::renderer::html::parser::HtmlParser;
 use saba_core::renderer::html::token::HtmlTokenizer;
+use saba_core::url::Scheme;
 
 static TEST_HTTP_RESPONSE: &str = r#"HTTP/1.1 200 OK
 Data: xx xx xx
@@ -46,66 +48,37 @@ Data: xx xx xx
 </html>
 "#;
 
-fn handle_url(url: String) -> Result<HttpResponse, Error> {
-    // URLを解釈する
-    let mut get_count = 0;
-    let client = HttpClient::new();
-    let mut response = None;
-
-    let mut url = url;
-    while true {
-        let parsed_url = match Url::new(url.clone()).parse() {
-            Ok(url) => url,
-            Err(e) => {
-                return Err(Error::UnexpectedInput(format!(
-                    "input html is not supported: {:?}",
-                    e,
-                )));
-            }
-        };
-        get_count += 1;
-
-        // HTTPリクエストを送信する
-        response = match client.get(
-            parsed_url.host(),
-            parsed_url.port().parse::<u16>().expect(&format!(
-                "port number should be u16 but got {}",
-                parsed_url.port()
-            )),
-            parsed_url.path(),
-        ) {
-            Ok(response) => {
-                // リダイレクトが5回以上続いたら最後のレスポンスを返す
-                // リダイレクトの場合はリダイレクト先のURLにリクエストを送る
-                if get_count < 5 && response.status_code() == 302 {
-                    let location = match response.header_value("Location") {
-                        Ok(value) => value,
-                        Err(_) => return Ok(response),
-                    };
-                    url = location;
-                    continue;
-                }
-                Some(response)
-            }
-            Err(e) => {
-                return Err(Error::Network(format!(
-                    "failed to get http response: {:?}",
-                    3
-                )));
-            }
-        };
-        break;
-    }
-    Ok(response.expect("response should not be None"))
-}
-
 fn main() -> u64 {
     let browser = Browser::new();
+    let clock = Rc::new(SystemClock::new());
+    let cookie_jar = browser.borrow().cookie_jar();
+    // Cookieの有効期限を判断するために使う
+    cookie_jar.borrow_mut().set_clock(clock.clone());
+    let http_cache = browser.borrow().http_cache();
+    let net_log = browser.borrow().net_log();
+
+    let mut about = AboutHandler::new(Rc::downgrade(&browser));
+    about.set_features(vec!["wasabi".to_string()]);
 
     let ui = Rc::new(RefCell::new(WasabiUI::new(browser)));
+    let mut client = HttpClient::new(TcpTransport::new());
+    // アイドル接続の破棄、キャッシュの有効期間の判断、リクエストにかかった時間の計測に使う
+    client.set_clock(clock);
+    // ページのdocument.cookieと同じCookieを送受信する
+    client.set_cookie_jar(cookie_jar);
+    // 同じURLを再び読み込む時は、保存したレスポンスを検証して再利用する
+    client.set_cache(http_cache);
+    // 送信したリクエストをabout:net-internalsに表示する
+    client.set_net_log(net_log);
+
+    // about:のページはブラウザが生成する。wasabi OSではfile:は登録しない
+    let mut fetcher = SchemeFetcher::new(client);
+    fetcher.register(Scheme::About, Box::new(about));
 
     // アプリを起動
-    match ui.borrow_mut().start(handle_url) {
+    // clientは、keep-aliveの接続やキャッシュを使い回すため、ナビゲーションをまたいで同じものを使う。
+    // リダイレクトはclientが辿る
+    match ui.borrow_mut().start(fetcher) {
         Ok(_) => {}
         Err(e) => {
             println!("browser fails to start: {:?}", e);
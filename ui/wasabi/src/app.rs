@@ -11,8 +11,10 @@ use noli::window::StringSize;
 use noli::window::Window;
 use noli::{print, println};
 use saba_core::display_item::DisplayItem;
-use saba_core::http::HttpResponse;
+use saba_core::fetcher::Fetcher;
+use saba_core::omnibox::Omnibox;
 use saba_core::renderer::layout::computed_style::{FontSize, TextDecoration};
+use saba_core::url::Url;
 
 use saba_core::browser::Browser;
 use saba_core::error::Error;
@@ -45,6 +47,8 @@ pub struct WasabiUI {
     // UIウィンドウィの管理を行う
     window: Window,
     cursor: Cursor,
+    // アドレスバーの入力を移動先のURLに変換する
+    omnibox: Omnibox,
 }
 
 impl WasabiUI {
@@ -63,9 +67,15 @@ impl WasabiUI {
             )
             .unwrap(),
             cursor: Cursor::new(),
+            omnibox: Omnibox::new(),
         }
     }
 
+    /// URLではない入力を検索する時のURLのテンプレートを設定する。"%s"が検索語に置き換えられる
+    pub fn set_search_url(&mut self, search_url: String) {
+        self.omnibox.set_search_url(search_url);
+    }
+
     fn setup_toolbar(&mut self) -> OsResult<()> {
         // ツールバーの背景の矩形を描画
         // self.window.fill_rect(LIGHTGREY, 0, 0, WINDOW_WIDTH, TOOLBAR_HEIGHT)?;
@@ -110,12 +120,11 @@ impl WasabiUI {
         Ok(())
     }
 
-    pub fn start(
-        &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
-    ) -> Result<(), Error> {
+    // fetcherは、URLを受け取り、HTTPレスポンスを返す。
+    // fn(String) -> Result<HttpResponse, Error>の関数やsaba_core::http::HttpClientを渡すことができる。
+    pub fn start<F: Fetcher>(&mut self, mut fetcher: F) -> Result<(), Error> {
         self.setup()?;
-        self.run_app(handle_url)?;
+        self.run_app(&mut fetcher)?;
         Ok(())
     }
 
@@ -132,18 +141,18 @@ impl WasabiUI {
 
     fn run_app(
         &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
+        fetcher: &mut dyn Fetcher,
     ) -> Result<(), Error> {
         loop {
-            self.handle_mouse_input(handle_url)?;
-            self.handle_key_input(handle_url)?;
+            self.handle_mouse_input(fetcher)?;
+            self.handle_key_input(fetcher)?;
         }
     }
 
     // マウスの入力を処理する
     fn handle_mouse_input(
         &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
+        fetcher: &mut dyn Fetcher,
     ) -> Result<(), Error> {
         if let Some(MouseEvent { button, position }) =
             Api::get_mouse_cursor_info()
@@ -200,7 +209,7 @@ impl WasabiUI {
                 if let Some(url) = next_destination {
                     self.input_url = url.clone();
                     self.update_address_bar()?;
-                    self.start_navigation(handle_url, url)?;
+                    self.start_navigation(fetcher, url)?;
                 }
             }
         }
@@ -209,10 +218,10 @@ impl WasabiUI {
     }
 
     // ユーザーの入力を処理する
-    // handle_urlは、URLを受け取り、HTTPリクエストを送信する関数
+    // fetcherは、URLを受け取り、HTTPリクエストを送信する
     fn handle_key_input(
         &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
+        fetcher: &mut dyn Fetcher,
     ) -> Result<(), Error> {
         match self.input_mode {
             InputMode::Normal => {
@@ -222,13 +231,15 @@ impl WasabiUI {
                 if let Some(c) = Api::read_key() {
                     if c == 0x0A as char {
                         // Enter key (LF: Line Feed) is pressed
-                        self.start_navigation(
-                            handle_url,
-                            self.input_url.clone(),
-                        )?;
-
-                        self.input_url = String::new();
+                        // アドレスバーには、ナビゲーション後のURLが表示される
+                        // スキームを省略したホスト名はhttp:のURLにし、それ以外の入力は検索する
                         self.input_mode = InputMode::Normal;
+                        let destination =
+                            match self.omnibox.navigation_url(&self.input_url) {
+                                Some(url) => url,
+                                None => return Ok(()),
+                            };
+                        return self.start_navigation(fetcher, destination);
                     }
 
                     if (c == 0x7F as char || c == 0x08 as char)
@@ -338,20 +349,41 @@ impl WasabiUI {
     // ブラウザのナビゲーションを開始する
     fn start_navigation(
         &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
+        fetcher: &mut dyn Fetcher,
         destination: String,
     ) -> Result<(), Error> {
         self.clear_content_area()?;
 
-        match handle_url(destination) {
+        // 表示中のドキュメントとフラグメントだけが異なる場合は、取得し直さずにスクロールする
+        let page = self.browser.borrow().current_page();
+        let destination_url = page.borrow().resolve_url(&destination);
+        if let Some(url) = destination_url {
+            if page.borrow().is_same_document(&url) {
+                self.input_url = url.href();
+                page.borrow_mut().navigate_to_fragment(url);
+                self.update_address_bar()?;
+                self.update_ui()?;
+                return Ok(());
+            }
+        }
+
+        match fetcher.fetch(destination) {
             Ok(response) => {
                 // HttpResponse内のテキストをパースして、DOM, CSSOM, レンダリングツリーを作成する。
-                let page = self.browser.borrow().current_page();
-                page.borrow_mut().receive_response(response);
+                // リダイレクトされた場合は、リダイレクト先のURLをページのURLとする
+                // フラグメントは、Fetcherがリダイレクト先のURLに引き継いでいる
+                if let Ok(url) = Url::parse(&response.url()) {
+                    self.input_url = url.href();
+                    page.borrow_mut().set_url(url);
+                } else {
+                    self.input_url = response.url();
+                }
+                page.borrow_mut().receive_response(response.response());
             }
             Err(e) => return Err(e),
         }
 
+        self.update_address_bar()?;
         self.update_ui()?;
         Ok(())
     }